@@ -1,4 +1,6 @@
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use core::cell::Cell;
 use anchor_lang::solana_program::account_info::AccountInfo;
 use anchor_lang::solana_program::hash::hashv;
 use anchor_lang::solana_program::sysvar::clock::Clock;
@@ -8,6 +10,14 @@ use pyth_sdk::Price;
 use pyth_sdk::PriceFeed;
 use pyth_sdk_solana::load_price_feed_from_account_info;
 
+// Switchboard on-demand pull feeds (optional backend for OracleSource)
+#[cfg(feature = "switchboard")]
+use switchboard_on_demand::{PullFeedAccountData, ON_DEMAND_DEVNET_PID, ON_DEMAND_MAINNET_PID};
+
+// real SPL custody (optional; default build keeps simulated counters)
+#[cfg(feature = "token-custody")]
+use anchor_spl::token::{self, Burn, Mint, MintTo, Token, TokenAccount, Transfer};
+
 declare_id!("35uJBHPvfJB91PtkhaeFSUEQ8RuGNBzaf2FnWaNGjGKC");
 
 /// ------------------------------------------------------------
@@ -15,7 +25,8 @@ declare_id!("35uJBHPvfJB91PtkhaeFSUEQ8RuGNBzaf2FnWaNGjGKC");
 /// ------------------------------------------------------------
 /// Simulated vault that:
 /// - Tracks staking exposure (staked_sol) and a simulated perp hedge notional (hedge_notional_usd)
-/// - Uses Pyth SOL/USD and SOL/USDC feeds for:
+/// - Tracks per-depositor UserPosition PDAs: shares minted against NAV + cost basis
+/// - Uses Pyth SOL/USD and SOL/USDC feeds (or a Switchboard / authority-set manual source) for:
 ///     - oracle spot price (mark-to-market + hedge sizing)
 ///     - oracle EMA price (drift trigger; less noisy)
 ///     - confidence/staleness gating
@@ -44,30 +55,122 @@ declare_id!("35uJBHPvfJB91PtkhaeFSUEQ8RuGNBzaf2FnWaNGjGKC");
 /// - circuit breaker: if oracle invalid, policy updates freeze and only extreme drift hedge can pass
 ///
 /// Notes:
-/// - No CPI calls; all accounting is simulated/deterministic.
+/// - Default build: no CPI calls; all accounting is simulated/deterministic.
+/// - `token-custody` feature: deposits/withdrawals also move a real SPL mint (wSOL or an LST)
+///   between depositor token accounts and a vault-owned custody token account via token CPI.
+///   Counters (staked_sol, reserve_sol, ...) are then denominated in that mint's base units.
+///   Shares are also issued as a transferable SPL share token (vault-owned mint), minted on
+///   deposit and burned on withdraw; mint supply ==
+///   total_shares - DEAD_SHARES - locked_shares - fee_shares_unclaimed (fee shares are minted on claim).
 ///
 /// IMPORTANT PYTH NOTE:
 /// - Pyth `Price` gives `publish_time` (unix seconds), not a Solana slot.
 /// - So we do staleness gating in *seconds* using `Clock::get()?.unix_timestamp`.
 /// - We keep the field names `*_slot` for compatibility with the rest of the program,
 ///   but `oracle_publish_slot` actually stores `publish_time` (unix seconds) in this implementation.
-pub const N_RETURNS: usize = 32;
+pub const N_RETURNS: usize = 32; // default vault returns window; asset-book rings stay at this length
+
+// Vault returns ring (separate PDA, capacity fixed at init)
+pub const MIN_RETURNS_CAPACITY: u16 = N_RETURNS as u16;
+pub const MAX_RETURNS_CAPACITY: u16 = 1_024;
+
+// OHLC bars for range-based vol (separate PDA; capacity keeps init under the 10 KiB CPI limit)
+pub const MIN_PRICE_BARS: u16 = 8;
+pub const MAX_PRICE_BARS: u16 = 200;
+pub const PARKINSON_K_FP: i128 = 360_673_760; // 1 / (4 ln 2), RET_FP_SCALE
+pub const GARMAN_KLASS_C_FP: i128 = 386_294_361; // 2 ln 2 - 1, RET_FP_SCALE
+
+// Realized vol needs this many effective samples (stdev divides by n - 1)
+pub const MIN_EFFECTIVE_SAMPLES: u8 = 2;
+// 95% confidence interval on realized vol (normal approx): vol * (1 +/- z / sqrt(2 n eff))
+pub const VOL_CI_Z_BPS: u128 = 19_600;
+// estimator efficiency vs close-to-close stdev (bps); range figures are per bar
+pub const EFF_STDEV_BPS: u128 = 10_000;
+pub const EFF_MAD_BPS: u128 = 3_700;
+pub const EFF_PARKINSON_BPS: u128 = 52_000;
+pub const EFF_GARMAN_KLASS_BPS: u128 = 74_000;
+pub const EFF_ROGERS_SATCHELL_BPS: u128 = 60_000;
+
+// Median oracle aggregation: feeds are picked by bit in oracle_feed_mask
+pub const MAX_ORACLE_FEEDS: usize = 4;
+pub const ORACLE_FEED_BIT_SOL_USD: u8 = 1 << 0;
+pub const ORACLE_FEED_BIT_SOL_USDC: u8 = 1 << 1;
+pub const ORACLE_FEED_BIT_SWITCHBOARD: u8 = 1 << 2;
+pub const ORACLE_FEED_BIT_MANUAL: u8 = 1 << 3;
+pub const ORACLE_FEED_MASK_ALL: u8 = (1 << MAX_ORACLE_FEEDS) - 1;
+// reason codes for the median path (single-feed gates use 1-3 / 10-12)
+pub const ORACLE_REASON_NO_QUORUM: u8 = 20;
+pub const ORACLE_REASON_DISAGREEMENT: u8 = 21;
+// SOL/USDC -> SOL/USD cross-rate through a USDC/USD feed
+pub const ORACLE_REASON_USDC_DEPEG: u8 = 22; // USDC/USD off its peg by more than max_usdc_depeg_bps
+pub const ORACLE_REASON_USDC_FEED: u8 = 23; // USDC/USD feed failed its own gates
+pub const ORACLE_REASON_NO_FEED_ID: u8 = 24; // pull mode: no feed id pinned for that slot
+pub const DEFAULT_MAX_USDC_DEPEG_BPS: u16 = 100;
+pub const USDC_USD_NOT_READ: i64 = -1; // OraclePriceUpdated.implied_usdc_usd_fp: leg not read
+
+// Pyth pull oracle (receiver program PriceUpdateV2 accounts)
+// rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ
+pub const PYTH_RECEIVER_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
+    12, 183, 250, 187, 82, 247, 166, 72, 187, 91, 49, 125, 154, 1, 139, 144, 87, 203, 2, 71, 116, 250, 254, 1, 230, 196,
+    223, 152, 204, 56, 88, 129,
+]);
+
+// GARCH(1,1) forecast horizon, in return samples
+pub const MAX_GARCH_HORIZON_STEPS: u64 = 100_000;
 
 // Fixed-point scales
-pub const RET_FP_SCALE: i64 = 1_000_000; // returns i32 scaled 1e6
+pub const RET_FP_SCALE: i64 = 1_000_000_000; // returns i32 scaled 1e9 (the ±25% clamp fits an i32)
 pub const PRICE_FP_SCALE: i64 = 1_000_000; // prices i64 scaled 1e6
 
 pub const BPS_DENOM: u16 = 10_000;
-pub const MAX_VOL_BPS: u16 = 10_000;
+pub const MAX_VOL_BPS: u16 = 10_000; // vol score / band bound: the score saturates at 100%/yr
+pub const MAX_ANNUAL_VOL_BPS: u16 = 30_000; // annualized vol outputs (300%/yr; sqrt(MAX_VAR_FP2) is ~316%)
 
 // Clamps/safety
-pub const MAX_RETURN_ABS_FP: i32 = 250_000; // 25% per sample clamp (scaled 1e6)
+pub const MAX_RETURN_ABS_FP: i32 = 250_000_000; // 25% per sample clamp (scaled 1e9)
 pub const MAX_PRICE_FP: i64 = 10_000_000_000_000i64; // 10,000,000 * 1e6
-pub const MAX_VAR_FP2: u128 = 10_000_000_000_000_000u128; // variance clamp (FP^2)
+pub const MAX_VAR_FP2: u128 = 10_000_000_000_000_000_000u128; // variance clamp (FP^2); fits the u64 ewma_var_fp2
 
 // Keepers
 pub const MAX_KEEPERS: usize = 8;
 
+// Staking yield accrual (staking_bps_per_day is per 86_400 s)
+pub const SECONDS_PER_DAY: i64 = 86_400;
+
+// Fees (management fee is bps/year, accrued per epoch)
+pub const SECONDS_PER_YEAR: i64 = 31_536_000;
+pub const MAX_MANAGEMENT_FEE_BPS: u16 = 500;
+pub const MAX_PERFORMANCE_FEE_BPS: u16 = 5_000;
+
+// Shares: locked on the first mint (first-depositor inflation guard)
+pub const DEAD_SHARES: u64 = 1_000;
+
+// Unstake queue
+pub const FILL_FACTOR_ONE: u128 = 1_000_000_000_000_000_000; // unfilled fraction of the matured unstake pool
+pub const MAX_UNSTAKE_COOLDOWN_EPOCHS: u64 = 64;
+
+// Validator delegation book
+pub const MAX_VALIDATORS: usize = 16;
+
+// Global vault registry (one PDA per program)
+pub const MAX_REGISTERED_VAULTS: usize = 64;
+pub const VAULT_NAME_LEN: usize = 32;
+
+// VaultState layout (zero-copy). v1 is the first versioned layout; the only earlier
+// deployed one is the Borsh layout (LegacyVaultState, version 0). Bump
+// VAULT_LAYOUT_VERSION when a change needs a migrate_vault step (realloc or non-zero
+// defaults); fields carved from _reserved that are valid at 0 don't.
+pub const VAULT_LAYOUT_VERSION: u8 = 1;
+pub const VAULT_RESERVED_LEN: usize = 264;
+pub const VAULT_AUTHORITY_OFFSET: usize = 8; // after the discriminator
+pub const VAULT_LAYOUT_VERSION_OFFSET: usize = VAULT_AUTHORITY_OFFSET + 32;
+pub const LEGACY_N_RETURNS: usize = 32; // in-account ring of the pre-versioning (Borsh) layout
+
+// Multi-LST asset book
+pub const MAX_ASSETS: usize = 4;
+pub const WEIGHT_FP_SCALE: u128 = 1_000_000; // weights during risk-parity iteration
+pub const RISK_PARITY_ITERS: usize = 24;
+
 // Default stability knobs
 pub const DEFAULT_MAX_POLICY_SLEW_BPS: u16 = 1_000; // 10%
 pub const DEFAULT_HYSTERESIS_BPS: u16 = 100; // 1%
@@ -80,6 +183,31 @@ pub enum VolMode {
     Stdev = 0,
     Ewma = 1,
     Mad = 2,
+    // range-based: read completed OHLC bars from PriceBars
+    Parkinson = 3,
+    GarmanKlass = 4,
+    RogersSatchell = 5,
+    // GARCH(1,1) on annualized variance samples; policy reads the h-step forecast
+    Garch = 6,
+}
+
+#[repr(u8)]
+pub enum ReturnKind {
+    Simple = 0, // (p - p0) / p0
+    Log = 1,    // ln(p / p0)
+}
+
+#[repr(u8)]
+pub enum WeightingMode {
+    InverseVol = 0,
+    RiskParity = 1,
+}
+
+/// Minimum PriceUpdateV2 verification (matches the receiver's borsh tag)
+#[repr(u8)]
+pub enum PullVerification {
+    Partial = 0, // Wormhole partial verification with >= pyth_pull_min_signatures
+    Full = 1,
 }
 
 #[repr(u8)]
@@ -87,6 +215,14 @@ pub enum OracleFeedChoice {
     SolUsd = 1,
     SolUsdc = 2,
     AutoPreferUsdThenUsdc = 3,
+    Switchboard = 4, // switchboard_feed (needs the `switchboard` feature)
+    Manual = 5,      // authority-signed ManualPriceFeed PDA
+    Median = 6,      // median of the oracle_feed_mask feeds, with a disagreement breaker
+}
+
+fn is_valid_feed_choice(choice: u8) -> bool {
+    (OracleFeedChoice::SolUsd as u8..=OracleFeedChoice::Median as u8).contains(&choice)
+        && (cfg!(feature = "switchboard") || choice != OracleFeedChoice::Switchboard as u8)
 }
 
 #[program]
@@ -108,7 +244,10 @@ pub mod vol_weighted_staking {
         );
 
         // anti-gaming / stability
-        require!(params.min_samples > 0 && params.min_samples <= (N_RETURNS as u8), ErrorCode::InvalidParams);
+        require!(
+            params.min_samples >= MIN_EFFECTIVE_SAMPLES && params.min_samples <= (N_RETURNS as u8),
+            ErrorCode::InvalidParams
+        );
         require!(params.min_return_spacing_slots > 0, ErrorCode::InvalidParams);
         require!(params.policy_update_min_slots > 0, ErrorCode::InvalidParams);
         require!(
@@ -123,11 +262,9 @@ pub mod vol_weighted_staking {
         require!(params.max_hedge_per_sol_usd_fp > 0, ErrorCode::InvalidParams);
         require!(params.min_reserve_bps <= BPS_DENOM, ErrorCode::InvalidParams);
 
-        // vol mode
+        // vol mode (Garch is selected later via set_vol_model, once set_garch_params has run)
         require!(
-            params.vol_mode == VolMode::Stdev as u8
-                || params.vol_mode == VolMode::Ewma as u8
-                || params.vol_mode == VolMode::Mad as u8,
+            params.vol_mode <= VolMode::RogersSatchell as u8,
             ErrorCode::InvalidParams
         );
         if params.vol_mode == VolMode::Ewma as u8 {
@@ -141,10 +278,13 @@ pub mod vol_weighted_staking {
         require!(params.max_price_age_slots > 0, ErrorCode::InvalidParams);
         require!(params.max_confidence_bps <= BPS_DENOM, ErrorCode::InvalidParams);
         require!(params.max_price_jump_bps <= BPS_DENOM, ErrorCode::InvalidParams);
+        // Median needs set_oracle_aggregation first
         require!(
-            params.oracle_feed_choice == OracleFeedChoice::SolUsd as u8
-                || params.oracle_feed_choice == OracleFeedChoice::SolUsdc as u8
-                || params.oracle_feed_choice == OracleFeedChoice::AutoPreferUsdThenUsdc as u8,
+            is_valid_feed_choice(params.oracle_feed_choice) && params.oracle_feed_choice != OracleFeedChoice::Median as u8,
+            ErrorCode::InvalidParams
+        );
+        require!(
+            params.oracle_sol_usd != Pubkey::default() && params.oracle_sol_usdc != Pubkey::default(),
             ErrorCode::InvalidParams
         );
 
@@ -158,12 +298,18 @@ pub mod vol_weighted_staking {
         // keeper rate limits/bond (simulated)
         require!(params.max_updates_per_epoch > 0, ErrorCode::InvalidParams);
 
-        let state = &mut ctx.accounts.vault_state;
+        // unstake queue
+        require!(params.unstake_cooldown_epochs <= MAX_UNSTAKE_COOLDOWN_EPOCHS, ErrorCode::InvalidParams);
+
+        let mut state = ctx.accounts.vault_state.load_init()?;
 
         state.authority = ctx.accounts.authority.key();
+        state.layout_version = VAULT_LAYOUT_VERSION;
         state.pending_authority = Pubkey::default();
         state.keeper_admin = ctx.accounts.authority.key();
         state.vault_bump = ctx.bumps.vault_state;
+        state.vault_index = params.vault_index;
+        state.name = params.name;
 
         state.config_version = 1;
         state.config_hash = [0u8; 32];
@@ -175,6 +321,21 @@ pub mod vol_weighted_staking {
         state.staked_sol = 0;
         state.reserve_sol = 0;
         state.hedge_notional_usd = 0;
+        state.total_shares = 0;
+        state.locked_shares = 0;
+
+        // unstake queue
+        state.unstake_cooldown_epochs = params.unstake_cooldown_epochs;
+        state.deactivating_sol = 0;
+        state.withdraw_owed_sol = 0;
+
+        // slashing
+        state.total_slashed_sol = 0;
+
+        // token custody (set by initialize_custody)
+        state.custody_mint = Pubkey::default();
+        state.custody_authority_bump = 0;
+        state.share_mint = Pubkey::default();
 
         // caps/guardrails
         state.max_staked_sol = params.max_staked_sol;
@@ -183,15 +344,16 @@ pub mod vol_weighted_staking {
         state.min_reserve_bps = params.min_reserve_bps;
 
         // returns buffer (oracle-driven)
-        state.returns_ring = [0i32; N_RETURNS];
-        state.returns_idx = 0;
-        state.nonzero_samples = 0;
+        state.returns_window = N_RETURNS as u16; // samples live in ReturnsRing (initialize_returns_ring)
         state.last_return_slot = 0;
+        state.last_return_price_fp = 0;
+        state.last_return_ts = 0;
         state.min_samples = params.min_samples;
         state.min_return_spacing_slots = params.min_return_spacing_slots;
 
         // realized vol model
         state.vol_mode = params.vol_mode;
+        state.return_kind = ReturnKind::Simple as u8;
         state.ewma_alpha_bps = params.ewma_alpha_bps;
         state.ewma_var_fp2 = 0;
 
@@ -222,12 +384,16 @@ pub mod vol_weighted_staking {
         state.max_price_age_slots = params.max_price_age_slots;
         state.max_confidence_bps = params.max_confidence_bps;
         state.max_price_jump_bps = params.max_price_jump_bps;
+        state.max_usdc_depeg_bps = DEFAULT_MAX_USDC_DEPEG_BPS;
+        state.oracle_sol_usd = params.oracle_sol_usd;
+        state.oracle_sol_usdc = params.oracle_sol_usdc;
+        state.oracle_switchboard_feed = params.oracle_switchboard_feed;
 
         state.oracle_price_fp = 0;
         state.oracle_ema_price_fp = 0;
         state.oracle_conf_fp = 0;
         state.oracle_publish_slot = 0; // actually publish_time (unix seconds) in this impl
-        state.oracle_ok = false;
+        state.oracle_ok = 0;
 
         state.last_oracle_price_fp = 0;
         state.last_oracle_ema_price_fp = 0;
@@ -245,24 +411,44 @@ pub mod vol_weighted_staking {
         state.borrow_bps_per_day = 0;
         state.staking_bps_per_day = 0;
 
+        // staking accrual
+        state.staking_accrued_usd = 0;
+        state.last_accrual_ts = Clock::get()?.unix_timestamp;
+        state.compound_staking_yield = 0;
+
+        // hedge carry accrual
+        state.hedge_carry_accrued_usd = 0;
+        state.carry_accrued_epoch_usd = 0;
+        state.last_carry_accrual_ts = state.last_accrual_ts;
+
+        // fees (off until set_fees)
+        state.fee_recipient = ctx.accounts.authority.key();
+        state.management_fee_bps = 0;
+        state.performance_fee_bps = 0;
+        state.high_water_mark_fp = 0;
+        state.fee_shares_unclaimed = 0;
+        state.last_fee_ts = state.last_accrual_ts;
+
         // circuit breaker
-        state.oracle_degraded = false;
+        state.oracle_degraded = 0;
         state.extreme_drift_bps = params.extreme_drift_bps;
 
         // hedge confirm tracking
         state.last_hedge_request_slot = 0;
         state.last_hedge_request_id = 0;
-        state.request_outstanding = false;
+        state.request_outstanding = 0;
 
         state.last_fill_slot = 0;
         state.hedge_fill_count = 0;
         state.avg_fill_slippage_bps = 0;
+        state.hedge_entry_price_fp = 0;
+        state.hedge_realized_pnl_usd = 0;
         state.missed_confirms = 0;
         state.max_confirm_delay_slots = params.max_confirm_delay_slots;
 
         // safety toggles
-        state.paused = false;
-        state.emergency_withdraw_enabled = false;
+        state.paused = 0;
+        state.emergency_withdraw_enabled = 0;
 
         // keepers + rate limits/bond (simulated)
         state.keepers = [Pubkey::default(); MAX_KEEPERS];
@@ -322,43 +508,523 @@ pub mod vol_weighted_staking {
 
             max_updates_per_epoch: state.max_updates_per_epoch,
             keeper_bond_required_lamports: state.keeper_bond_required_lamports,
+
+            unstake_cooldown_epochs: state.unstake_cooldown_epochs,
+            vault_index: state.vault_index,
+            name: state.name,
+        });
+
+        let vault_key = ctx.accounts.vault_state.key();
+        if let Some(registry) = ctx.accounts.vault_registry.as_mut() {
+            let entry_index = registry.upsert(vault_key, &state)?;
+            emit_vault_registered(registry, entry_index)?;
+        }
+
+        Ok(())
+    }
+
+    /// Create the global vault registry (any payer; it grants no privileges)
+    pub fn initialize_vault_registry(ctx: Context<InitializeVaultRegistry>) -> Result<()> {
+        let registry = &mut ctx.accounts.vault_registry;
+        registry.bump = ctx.bumps.vault_registry;
+        registry.vault_count = 0;
+        registry.entries = [VaultEntry::default(); MAX_REGISTERED_VAULTS];
+        Ok(())
+    }
+
+    /// Vault authority: add its vault to the registry or refresh its config hash/version
+    pub fn sync_vault_registry(ctx: Context<SyncVaultRegistry>) -> Result<()> {
+        let vault_key = ctx.accounts.vault_state.key();
+        let registry = &mut ctx.accounts.vault_registry;
+        let entry_index = registry.upsert(vault_key, &*ctx.accounts.vault_state.load()?)?;
+        emit_vault_registered(registry, entry_index)?;
+        Ok(())
+    }
+
+    /// Vault authority (checked against the live vault, not the registry entry): drop the
+    /// vault's entry (frees the slot)
+    pub fn deregister_vault(ctx: Context<DeregisterVault>) -> Result<()> {
+        let vault = ctx.accounts.vault_state.key();
+        let registry = &mut ctx.accounts.vault_registry;
+        let entry_index = registry.entry_index(&vault).ok_or(ErrorCode::VaultNotRegistered)?;
+        registry.remove(entry_index);
+
+        emit!(VaultDeregistered {
+            slot: Clock::get()?.slot,
+            vault,
+            authority: ctx.accounts.authority.key(),
+            vault_count: registry.vault_count,
+        });
+        Ok(())
+    }
+
+    /// Authority: upgrade a vault account written by an older layout (including the
+    /// pre-versioning Borsh one) in place.
+    /// Grows the account to `VaultState::SPACE` (authority tops up rent), runs
+    /// the per-version upgrade steps, then stamps `VAULT_LAYOUT_VERSION`.
+    pub fn migrate_vault(ctx: Context<MigrateVault>) -> Result<()> {
+        let info = ctx.accounts.vault_state.to_account_info();
+        let old_len = info.data_len();
+
+        // header is read raw: the account may be shorter than the current struct.
+        // authority sits at the same offset in every layout, including the legacy Borsh one.
+        let (old_version, legacy) = {
+            let data = info.try_borrow_data()?;
+            let header = read_vault_header(&data)?;
+            require!(
+                data[VAULT_AUTHORITY_OFFSET..VAULT_AUTHORITY_OFFSET + 32] == ctx.accounts.authority.key().to_bytes(),
+                ErrorCode::Unauthorized
+            );
+            header
+        };
+        require!(old_version <= VAULT_LAYOUT_VERSION, ErrorCode::VaultLayoutInvalid);
+        // below v1 only the legacy Borsh layout exists
+        require!(old_version > 0 || legacy.is_some(), ErrorCode::VaultLayoutInvalid);
+        require!(
+            old_version < VAULT_LAYOUT_VERSION || old_len < VaultState::SPACE,
+            ErrorCode::VaultAlreadyMigrated
+        );
+
+        if old_len < VaultState::SPACE {
+            let rent_due = Rent::get()?.minimum_balance(VaultState::SPACE).saturating_sub(info.lamports());
+            if rent_due > 0 {
+                anchor_lang::system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        anchor_lang::system_program::Transfer {
+                            from: ctx.accounts.authority.to_account_info(),
+                            to: info.clone(),
+                        },
+                    ),
+                    rent_due,
+                )?;
+            }
+            // new bytes are zeroed, so fields carved from the tail start at 0
+            info.realloc(VaultState::SPACE, true)?;
+        }
+
+        let mut data = info.try_borrow_mut_data()?;
+        let state = upgrade_vault_data(&mut data, legacy, Clock::get()?.unix_timestamp);
+
+        emit!(VaultMigrated {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            vault: info.key(),
+            old_version,
+            new_version: state.layout_version,
+            old_len: old_len as u32,
+            new_len: old_len.max(VaultState::SPACE) as u32,
+        });
+
+        Ok(())
+    }
+
+    /// User: open per-depositor position PDA (seeded by vault + owner)
+    pub fn open_user_position(ctx: Context<OpenUserPosition>) -> Result<()> {
+        let vault_key = ctx.accounts.vault_state.key();
+        let state = ctx.accounts.vault_state.load()?;
+
+        let position = &mut ctx.accounts.user_position;
+        position.vault = vault_key;
+        position.owner = ctx.accounts.owner.key();
+        position.bump = ctx.bumps.user_position;
+
+        position.shares = 0;
+        position.deposited_sol = 0;
+        position.cost_basis_usd = 0;
+        position.last_deposit_slot = 0;
+        position.last_deposit_epoch = 0;
+
+        emit!(UserPositionOpened {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            vault: vault_key,
+            owner: position.owner,
         });
 
         Ok(())
     }
 
     /// User: simulated staking deposit (no token transfers)
-    pub fn deposit_and_stake(ctx: Context<UserWithVault>, amount_sol: u64) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
+    /// Mints vault shares to the depositor's UserPosition, priced against pre-deposit NAV.
+    pub fn deposit_and_stake(ctx: Context<UserDeposit>, amount_sol: u64) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.require_not_paused()?;
+        state.accrue_to_now()?;
+        state.require_fresh_mark(Clock::get()?.unix_timestamp)?;
+        state.sync_validator_registry(ctx.accounts.validator_registry.as_deref_mut().map(|r| &mut **r))?;
 
         require!(amount_sol > 0, ErrorCode::InvalidParams);
 
         let new_staked = state.staked_sol.checked_add(amount_sol).ok_or(ErrorCode::MathOverflow)?;
         require!(new_staked <= state.max_staked_sol, ErrorCode::CapExceeded);
 
+        // share pricing (NAV before this deposit lands)
+        let nav_before_usd = state.compute_nav_usd()?;
+        let deposit_value_usd = state.sol_value_usd(amount_sol)?;
+        let shares_minted = state.shares_for_deposit_usd(deposit_value_usd, nav_before_usd)?;
+        require!(shares_minted > 0, ErrorCode::ZeroShares);
+
+        // first mint: DEAD_SHARES guard against first-depositor inflation, and NAV already in
+        // the vault (e.g. reserve funded before any share existed) backs locked shares rather
+        // than being captured by the depositor
+        let (dead_shares, nav_locked_shares) = if state.total_shares == 0 {
+            (DEAD_SHARES, nav_before_usd.max(0) as u64)
+        } else {
+            (0, 0)
+        };
+        let locked = dead_shares.checked_add(nav_locked_shares).ok_or(ErrorCode::MathOverflow)?;
+        require!(shares_minted > locked, ErrorCode::MinInitialDeposit);
+        let user_shares = shares_minted - locked;
+        state.locked_shares = state.locked_shares.checked_add(nav_locked_shares).ok_or(ErrorCode::MathOverflow)?;
+
         state.staked_sol = new_staked;
+        state.total_shares = state.total_shares.checked_add(shares_minted).ok_or(ErrorCode::MathOverflow)?;
         state.enforce_reserve_ratio()?;
 
+        if let Some(registry) = ctx.accounts.validator_registry.as_mut() {
+            registry.allocate(amount_sol)?;
+        }
+
+        let slot = Clock::get()?.slot;
+
+        let position = &mut ctx.accounts.user_position;
+        position.shares = position.shares.checked_add(user_shares).ok_or(ErrorCode::MathOverflow)?;
+        position.deposited_sol = position.deposited_sol.checked_add(amount_sol).ok_or(ErrorCode::MathOverflow)?;
+        position.cost_basis_usd = position
+            .cost_basis_usd
+            .checked_add(deposit_value_usd)
+            .ok_or(ErrorCode::MathOverflow)?;
+        position.last_deposit_slot = slot;
+        position.last_deposit_epoch = state.epoch;
+
+        #[cfg(feature = "token-custody")]
+        custody_pull(
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.custody_token_account,
+            &ctx.accounts.owner,
+            &ctx.accounts.token_program,
+            amount_sol,
+        )?;
+        #[cfg(feature = "token-custody")]
+        share_mint_to(
+            &ctx.accounts.share_mint,
+            &ctx.accounts.user_share_account,
+            &ctx.accounts.custody_authority,
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault_state.key(),
+            state.custody_authority_bump,
+            user_shares,
+        )?;
+
         emit!(StakeAllocated {
             epoch: state.epoch,
-            slot: Clock::get()?.slot,
+            slot,
             amount_sol,
             new_staked_sol: state.staked_sol,
             reserve_sol: state.reserve_sol,
         });
 
+        emit!(SharesMinted {
+            epoch: state.epoch,
+            slot,
+            owner: position.owner,
+            amount_sol,
+            deposit_value_usd,
+            nav_before_usd,
+            shares_minted: user_shares,
+            dead_shares,
+            nav_locked_shares,
+            position_shares: position.shares,
+            total_shares: state.total_shares,
+            position_cost_basis_usd: position.cost_basis_usd,
+        });
+
         Ok(())
     }
 
     /// User: simulated reserve buffer deposit (slashing buffer)
-    pub fn deposit_reserve(ctx: Context<UserWithVault>, amount_sol: u64) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
+    pub fn deposit_reserve(ctx: Context<ReserveDeposit>, amount_sol: u64) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.require_not_paused()?;
+        state.accrue_to_now()?;
+        require!(amount_sol > 0, ErrorCode::InvalidParams);
+
+        state.reserve_sol = state.reserve_sol.checked_add(amount_sol).ok_or(ErrorCode::MathOverflow)?;
+        state.enforce_reserve_ratio()?;
+
+        #[cfg(feature = "token-custody")]
+        custody_pull(
+            &ctx.accounts.depositor_token_account,
+            &ctx.accounts.custody_token_account,
+            &ctx.accounts.depositor,
+            &ctx.accounts.token_program,
+            amount_sol,
+        )?;
+
+        emit!(ReserveUpdated {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            reserve_sol: state.reserve_sol,
+            min_reserve_bps: state.min_reserve_bps,
+        });
+
+        Ok(())
+    }
+
+    /// User: burn shares and enqueue an unstake ticket.
+    /// The SOL value leaves staked_sol now (deactivating) and can be filled once
+    /// `unstake_cooldown_epochs` epochs have passed (see fulfill_unstake_queue).
+    /// What staked_sol can't cover is taken from the reserve not owed to tickets; the
+    /// last holder's exit is capped at that liquidity (USD-only NAV has no SOL behind it).
+    pub fn request_unstake(ctx: Context<UserUnstake>, shares: u64) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.require_not_paused()?;
+        state.accrue_to_now()?;
+        state.require_fresh_mark(Clock::get()?.unix_timestamp)?;
+        state.sync_validator_registry(ctx.accounts.validator_registry.as_deref_mut().map(|r| &mut **r))?;
+        require!(shares > 0, ErrorCode::InvalidParams);
+
+        // token-custody: the share token balance is the claim (the burn enforces it)
+        let position = &mut ctx.accounts.user_position;
+        #[cfg(not(feature = "token-custody"))]
+        require!(shares <= position.shares, ErrorCode::InsufficientShares);
+
+        let value_usd = state.shares_value_usd(shares)?;
+        let mut amount_sol = state.usd_to_sol(value_usd)?;
+        let liquid_sol = state.staked_sol.saturating_add(state.free_reserve_sol());
+        if amount_sol > liquid_sol && shares >= state.redeemable_shares() {
+            amount_sol = liquid_sol;
+        }
         require!(amount_sol > 0, ErrorCode::InvalidParams);
+        require!(amount_sol <= liquid_sol, ErrorCode::InsufficientLiquidity);
+
+        let prev_staked = state.staked_sol;
+        let prev_hedge = state.hedge_notional_usd;
+        let from_staked = amount_sol.min(prev_staked);
+        let from_reserve_sol = amount_sol - from_staked;
+        state.staked_sol = prev_staked - from_staked;
+        state.reserve_sol -= from_reserve_sol;
+        state.deactivating_sol = state.deactivating_sol.checked_add(amount_sol).ok_or(ErrorCode::MathOverflow)?;
+        if let Some(registry) = ctx.accounts.validator_registry.as_mut() {
+            registry.deallocate(from_staked);
+        }
+        state.total_shares = state.total_shares.checked_sub(shares).ok_or(ErrorCode::MathOverflow)?;
+        let position_burn = shares.min(position.shares);
+        position.burn_shares(position_burn)?;
+
+        #[cfg(feature = "token-custody")]
+        share_burn_from(
+            &ctx.accounts.share_mint,
+            &ctx.accounts.user_share_account,
+            &ctx.accounts.owner,
+            &ctx.accounts.token_program,
+            shares,
+        )?;
+
+        // exits shrink the hedge with the book, then re-check guardrails
+        state.scale_hedge_for_unstake(prev_staked)?;
+        state.enforce_reserve_ratio()?;
+        state.enforce_leverage_guardrail()?;
+
+        let claimable_epoch = state
+            .epoch
+            .checked_add(state.unstake_cooldown_epochs)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let queue = &mut ctx.accounts.withdraw_queue;
+        let ticket_id = queue.next_ticket_id;
+        queue.next_ticket_id = ticket_id.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        queue.open_tickets = queue.open_tickets.saturating_add(1);
+
+        let ticket = &mut ctx.accounts.ticket;
+        ticket.vault = ctx.accounts.vault_state.key();
+        ticket.owner = position.owner;
+        ticket.id = ticket_id;
+        ticket.bump = ctx.bumps.ticket;
+        ticket.amount_sol = amount_sol;
+        ticket.claimed_sol = 0;
+        ticket.request_epoch = state.epoch;
+        ticket.claimable_epoch = claimable_epoch;
+        ticket.matured = false;
+        ticket.matured_fill_factor = 0;
+        ticket.matured_generation = 0;
+
+        let slot = Clock::get()?.slot;
+        state.emit_hedge_scaled_down(slot, prev_hedge)?;
+
+        emit!(UnstakeEnqueued {
+            epoch: state.epoch,
+            slot,
+            owner: position.owner,
+            ticket_id,
+            shares_burned: shares,
+            amount_sol,
+            from_reserve_sol,
+            value_usd,
+            claimable_epoch,
+            new_staked_sol: state.staked_sol,
+            deactivating_sol: state.deactivating_sol,
+            position_shares: position.shares,
+            total_shares: state.total_shares,
+            open_tickets: queue.open_tickets,
+        });
+
+        Ok(())
+    }
 
+    /// Permissionless: move a ticket whose cooldown has passed into the matured pool.
+    /// Its stake is swept from deactivating_sol into reserve_sol and owed to the pool.
+    pub fn mature_unstake_ticket(ctx: Context<MatureUnstakeTicket>) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        let queue = &ctx.accounts.withdraw_queue;
+        let ticket = &mut ctx.accounts.ticket;
+        require!(!ticket.matured, ErrorCode::InvalidParams);
+        require!(ticket.claimable_epoch <= state.epoch, ErrorCode::TicketNotDue);
+
+        ticket.matured = true;
+        ticket.matured_fill_factor = queue.fill_factor;
+        ticket.matured_generation = queue.fill_generation;
+
+        let amount_sol = ticket.amount_sol;
+        state.deactivating_sol = state.deactivating_sol.checked_sub(amount_sol).ok_or(ErrorCode::MathOverflow)?;
         state.reserve_sol = state.reserve_sol.checked_add(amount_sol).ok_or(ErrorCode::MathOverflow)?;
+        state.withdraw_owed_sol = state.withdraw_owed_sol.checked_add(amount_sol).ok_or(ErrorCode::MathOverflow)?;
+
+        emit!(UnstakeTicketMatured {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            owner: ticket.owner,
+            ticket_id: ticket.id,
+            amount_sol,
+            withdraw_owed_sol: state.withdraw_owed_sol,
+            deactivating_sol: state.deactivating_sol,
+        });
+
+        Ok(())
+    }
+
+    /// Permissionless: fill the matured pool from reserve_sol. Fills only draw the reserve
+    /// down to the min_reserve_bps floor, so when the buffer is short every matured ticket
+    /// is filled by the same fraction (tracked by the pool's fill factor) and the remainder
+    /// carries over to the next crank.
+    pub fn fulfill_unstake_queue(ctx: Context<WithdrawQueueCrank>) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.require_not_paused()?;
+
+        let queue = &mut ctx.accounts.withdraw_queue;
+        let epoch = state.epoch;
+
+        let due_sol = state.withdraw_owed_sol;
+        let available_sol = state.reserve_sol.saturating_sub(state.required_reserve_sol()?);
+        let filled_sol = due_sol.min(available_sol);
+
+        if filled_sol > 0 {
+            if filled_sol == due_sol {
+                // pool paid in full: every matured ticket is filled, restart the factor
+                queue.fill_generation = queue.fill_generation.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+                queue.fill_factor = FILL_FACTOR_ONE;
+            } else {
+                // rounds up: tickets never count as more filled than the pool paid
+                let left = (due_sol - filled_sol) as u128;
+                queue.fill_factor = queue
+                    .fill_factor
+                    .checked_mul(left)
+                    .ok_or(ErrorCode::MathOverflow)?
+                    .div_ceil(due_sol as u128);
+            }
+        }
+        state.reserve_sol = state.reserve_sol.checked_sub(filled_sol).ok_or(ErrorCode::MathOverflow)?;
+        state.withdraw_owed_sol = state.withdraw_owed_sol.checked_sub(filled_sol).ok_or(ErrorCode::MathOverflow)?;
+
+        let fill_ratio_bps = if due_sol == 0 {
+            BPS_DENOM
+        } else {
+            ((filled_sol as u128) * (BPS_DENOM as u128) / (due_sol as u128)) as u16
+        };
+
+        emit!(UnstakeFulfilled {
+            epoch,
+            slot: Clock::get()?.slot,
+            due_sol,
+            filled_sol,
+            fill_ratio_bps,
+            fill_factor: queue.fill_factor,
+            fill_generation: queue.fill_generation,
+            reserve_sol: state.reserve_sol,
+            withdraw_owed_sol: state.withdraw_owed_sol,
+            deactivating_sol: state.deactivating_sol,
+        });
+
+        Ok(())
+    }
+
+    /// User: claim the filled part of an unstake ticket (simulated payout, no token transfer).
+    /// The ticket account is closed back to its owner once fully claimed.
+    pub fn claim_unstake(ctx: Context<ClaimUnstake>) -> Result<()> {
+        let queue = &mut ctx.accounts.withdraw_queue;
+        let ticket = &mut ctx.accounts.ticket;
+        let filled_sol = ticket.filled_sol(queue);
+        require!(filled_sol > ticket.claimed_sol, ErrorCode::NothingToClaim);
+
+        let amount_sol = filled_sol - ticket.claimed_sol;
+        ticket.claimed_sol = filled_sol;
+        let ticket_id = ticket.id;
+        let owner = ticket.owner;
+
+        let fully_claimed = filled_sol == ticket.amount_sol;
+        if fully_claimed {
+            queue.open_tickets = queue.open_tickets.saturating_sub(1);
+            ctx.accounts.ticket.close(ctx.accounts.owner.to_account_info())?;
+        }
+
+        #[cfg(feature = "token-custody")]
+        custody_push(
+            &ctx.accounts.custody_token_account,
+            &ctx.accounts.owner_token_account,
+            &ctx.accounts.custody_authority,
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault_state.key(),
+            ctx.accounts.vault_state.load()?.custody_authority_bump,
+            amount_sol,
+        )?;
+
+        emit!(UnstakeClaimed {
+            epoch: ctx.accounts.vault_state.load()?.epoch,
+            slot: Clock::get()?.slot,
+            owner,
+            ticket_id,
+            amount_sol,
+            fully_claimed,
+            open_tickets: ctx.accounts.withdraw_queue.open_tickets,
+        });
+
+        Ok(())
+    }
+
+    /// Authority: withdraw from the reserve buffer (must keep min_reserve_bps; SOL owed to
+    /// matured unstake tickets is not withdrawable)
+    pub fn withdraw_reserve(ctx: Context<ReserveWithdraw>, amount_sol: u64) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.require_not_paused()?;
+        state.accrue_to_now()?;
+        require!(amount_sol > 0, ErrorCode::InvalidParams);
+        require!(amount_sol <= state.free_reserve_sol(), ErrorCode::InsufficientLiquidity);
+
+        state.reserve_sol -= amount_sol;
         state.enforce_reserve_ratio()?;
+        state.enforce_leverage_guardrail()?;
+
+        #[cfg(feature = "token-custody")]
+        custody_push(
+            &ctx.accounts.custody_token_account,
+            &ctx.accounts.destination_token_account,
+            &ctx.accounts.custody_authority,
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault_state.key(),
+            state.custody_authority_bump,
+            amount_sol,
+        )?;
 
         emit!(ReserveUpdated {
             epoch: state.epoch,
@@ -370,15 +1036,97 @@ pub mod vol_weighted_staking {
         Ok(())
     }
 
+    /// User: emergency exit (bypasses `paused`, requires emergency_withdraw_enabled).
+    /// Pays out a pro-rata slice of staked_sol + the free reserve by share count; no oracle needed.
+    pub fn emergency_withdraw(ctx: Context<UserDeposit>, shares: u64) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        require!(state.emergency_withdraw_enabled != 0, ErrorCode::EmergencyWithdrawDisabled);
+        require!(shares > 0, ErrorCode::InvalidParams);
+        state.accrue_to_now()?;
+        state.sync_validator_registry(ctx.accounts.validator_registry.as_deref_mut().map(|r| &mut **r))?;
+
+        let position = &mut ctx.accounts.user_position;
+        #[cfg(not(feature = "token-custody"))]
+        require!(shares <= position.shares, ErrorCode::InsufficientShares);
+        require!(shares <= state.redeemable_shares(), ErrorCode::InsufficientShares);
+
+        // both legs round down: an exit never takes more than its pro-rata slice, and the
+        // reserve leg only shares in what is not already owed to matured tickets
+        let total = state.total_shares as u128;
+        let free_reserve = state.free_reserve_sol();
+        let staked_out = (state.staked_sol as u128)
+            .checked_mul(shares as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / total;
+        let reserve_out = (free_reserve as u128)
+            .checked_mul(shares as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / total;
+        let staked_out = (staked_out as u64).min(state.staked_sol);
+        let reserve_out = (reserve_out as u64).min(free_reserve);
+
+        let prev_staked = state.staked_sol;
+        let prev_hedge = state.hedge_notional_usd;
+        state.staked_sol -= staked_out;
+        state.reserve_sol -= reserve_out;
+        if let Some(registry) = ctx.accounts.validator_registry.as_mut() {
+            registry.deallocate(staked_out);
+        }
+        state.total_shares -= shares;
+        let position_burn = shares.min(position.shares);
+        position.burn_shares(position_burn)?;
+
+        state.scale_hedge_for_unstake(prev_staked)?;
+        state.enforce_reserve_ratio()?;
+        state.enforce_leverage_guardrail()?;
+
+        #[cfg(feature = "token-custody")]
+        custody_push(
+            &ctx.accounts.custody_token_account,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.custody_authority,
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault_state.key(),
+            state.custody_authority_bump,
+            staked_out.checked_add(reserve_out).ok_or(ErrorCode::MathOverflow)?,
+        )?;
+        #[cfg(feature = "token-custody")]
+        share_burn_from(
+            &ctx.accounts.share_mint,
+            &ctx.accounts.user_share_account,
+            &ctx.accounts.owner,
+            &ctx.accounts.token_program,
+            shares,
+        )?;
+
+        let slot = Clock::get()?.slot;
+        state.emit_hedge_scaled_down(slot, prev_hedge)?;
+
+        emit!(EmergencyWithdrawn {
+            epoch: state.epoch,
+            slot,
+            owner: position.owner,
+            shares_burned: shares,
+            staked_out_sol: staked_out,
+            reserve_out_sol: reserve_out,
+            new_staked_sol: state.staked_sol,
+            reserve_sol: state.reserve_sol,
+            position_shares: position.shares,
+            total_shares: state.total_shares,
+        });
+
+        Ok(())
+    }
+
     /// Keeper: (optional) feed implied vol bps
     pub fn update_implied_vol(ctx: Context<KeeperWithVault>, implied_vol_bps: u16) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.require_not_paused()?;
 
         state.require_keeper_feeder(&ctx.accounts.signer.key())?;
         state.require_keeper_rate_limit_ok(&ctx.accounts.signer.key())?;
 
-        require!(implied_vol_bps <= MAX_VOL_BPS, ErrorCode::VolOutOfRange);
+        require!(implied_vol_bps <= MAX_ANNUAL_VOL_BPS, ErrorCode::VolOutOfRange);
         state.implied_vol_bps = implied_vol_bps;
 
         let slot = Clock::get()?.slot;
@@ -400,12 +1148,15 @@ pub mod vol_weighted_staking {
         borrow_bps_per_day: i32,
         staking_bps_per_day: i32,
     ) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.require_not_paused()?;
 
         state.require_keeper_feeder(&ctx.accounts.signer.key())?;
         state.require_keeper_rate_limit_ok(&ctx.accounts.signer.key())?;
 
+        // settle accruals at the old rates
+        state.accrue_to_now()?;
+
         state.funding_bps_per_day = funding_bps_per_day;
         state.borrow_bps_per_day = borrow_bps_per_day;
         state.staking_bps_per_day = staking_bps_per_day;
@@ -425,43 +1176,119 @@ pub mod vol_weighted_staking {
         Ok(())
     }
 
-    /// Keeper: update oracle price (spot + EMA) from Pyth accounts.
+    /// Keeper: update oracle price (spot + EMA) from the configured oracle source.
     /// Also updates oracle-driven return ring (deterministic) with min spacing gate.
     pub fn update_oracle_price(ctx: Context<UpdateOraclePrice>) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.require_not_paused()?;
+        state.require_oracle_accounts_set()?;
 
         // allow keeper/authority/keeper_admin
         let signer = ctx.accounts.signer.key();
         state.require_keeper_feeder(&signer)?;
         state.require_keeper_rate_limit_ok(&signer)?;
 
+        // range modes estimate from bars: every accepted price has to reach them
+        if is_range_vol_mode(state.vol_mode) {
+            require!(ctx.accounts.price_bars.is_some(), ErrorCode::PriceBarsRequired);
+        }
+
         let clock = Clock::get()?;
         let slot = clock.slot;
         let now_ts: i64 = clock.unix_timestamp;
 
-        let (chosen, spot_price_fp, ema_price_fp, conf_fp, publish_time_u64, ok, reason) = read_pyth_best_effort(
-            state.oracle_feed_choice,
-            &ctx.accounts.pyth_sol_usd,
-            &ctx.accounts.pyth_sol_usdc,
-            slot,
-            now_ts,
-            state.max_price_age_slots, // interpreted as max_age_seconds here
-            state.max_confidence_bps,
-            state.max_price_jump_bps,
-            state.last_oracle_price_fp,
-        )?;
+        // pyth_sol_usd / pyth_sol_usdc are PriceUpdateV2 accounts when pull mode is on
+        let pull_usd = state.pyth_pull_source(state.pyth_sol_usd_feed_id);
+        let pull_usdc = state.pyth_pull_source(state.pyth_sol_usdc_feed_id);
+        let (usd_source, usdc_source): (&dyn OracleSource, &dyn OracleSource) = if state.pyth_pull_enabled != 0 {
+            (&pull_usd, &pull_usdc)
+        } else {
+            (&PythSource, &PythSource)
+        };
 
-        // update oracle fields
-        state.oracle_price_fp = spot_price_fp;
-        state.oracle_ema_price_fp = ema_price_fp;
-        state.oracle_conf_fp = conf_fp;
-        state.oracle_publish_slot = publish_time_u64; // publish_time seconds
-        state.oracle_ok = ok;
+        // SOL/USDC is converted to SOL/USD through the pinned USDC/USD feed (1:1 if none)
+        let pull_usdc_usd = state.pyth_pull_source(state.pyth_usdc_usd_feed_id);
+        let usdc_usd_feed = ctx.accounts.pyth_usdc_usd.as_ref().map(|a| a.to_account_info());
+        let cross_rate_enabled = state.oracle_usdc_usd != Pubkey::default();
+        let usdc_leg = UsdcCrossRateSource {
+            sol_usdc: usdc_source,
+            usdc_usd: if state.pyth_pull_enabled != 0 { &pull_usdc_usd } else { &PythSource },
+            usdc_usd_acct: usdc_usd_feed.as_ref(),
+            enabled: cross_rate_enabled,
+            max_depeg_bps: state.max_usdc_depeg_bps,
+            now_unix_ts: now_ts,
+            max_age_seconds: state.max_price_age_slots,
+            max_conf_bps: state.max_confidence_bps,
+            // 1:1 unless a pinned USDC/USD feed is read below
+            usdc_usd_fp: Cell::new(if cross_rate_enabled { USDC_USD_NOT_READ } else { PRICE_FP_SCALE }),
+        };
+
+        let switchboard_feed = ctx.accounts.switchboard_feed.as_ref().map(|a| a.to_account_info());
+        let manual_price = ctx.accounts.manual_price.as_ref().map(|a| a.to_account_info());
+        let (chosen, spot_price_fp, ema_price_fp, conf_fp, publish_time_u64, ok, reason) =
+            if state.oracle_feed_choice == OracleFeedChoice::Median as u8 {
+                let agg = read_oracle_median(
+                    state.oracle_feed_mask,
+                    state.oracle_min_feeds,
+                    state.oracle_max_spread_bps,
+                    usd_source,
+                    &ctx.accounts.pyth_sol_usd,
+                    &usdc_leg,
+                    &ctx.accounts.pyth_sol_usdc,
+                    switchboard_feed.as_ref(),
+                    manual_price.as_ref(),
+                    ctx.accounts.vault_state.key(),
+                    slot,
+                    now_ts,
+                    state.max_price_age_slots, // interpreted as max_age_seconds here
+                    state.max_confidence_bps,
+                    state.max_price_jump_bps,
+                    state.last_oracle_price_fp,
+                )?;
+                emit!(OracleAggregated {
+                    epoch: state.epoch,
+                    slot,
+                    feeds_read: agg.feeds_read,
+                    feeds_used: agg.feeds_used,
+                    median_price_fp: agg.reading.price_fp,
+                    spread_bps: agg.spread_bps,
+                    max_spread_bps: state.oracle_max_spread_bps,
+                    ok: agg.ok,
+                    reason_code: agg.reason,
+                });
+                let r = agg.reading;
+                (OracleFeedChoice::Median as u8, r.price_fp, r.ema_fp, r.conf_fp, r.publish_time, agg.ok, agg.reason)
+            } else {
+                read_oracle_best_effort(
+                    state.oracle_feed_choice,
+                    usd_source,
+                    &ctx.accounts.pyth_sol_usd,
+                    &usdc_leg,
+                    &ctx.accounts.pyth_sol_usdc,
+                    switchboard_feed.as_ref(),
+                    manual_price.as_ref(),
+                    ctx.accounts.vault_state.key(),
+                    slot,
+                    now_ts,
+                    state.max_price_age_slots, // interpreted as max_age_seconds here
+                    state.max_confidence_bps,
+                    state.max_price_jump_bps,
+                    state.last_oracle_price_fp,
+                )?
+            };
+
+        // update oracle fields; a rejected reading never becomes the mark
+        state.oracle_ok = ok as u8;
+        if ok {
+            state.oracle_price_fp = spot_price_fp;
+            state.oracle_ema_price_fp = ema_price_fp;
+            state.oracle_conf_fp = conf_fp;
+            state.oracle_publish_slot = publish_time_u64; // publish_time seconds
+        }
 
         // circuit breaker tracking
         if !ok {
-            state.oracle_degraded = true;
+            state.oracle_degraded = 1;
             emit!(OracleDegraded {
                 epoch: state.epoch,
                 slot,
@@ -471,14 +1298,30 @@ pub mod vol_weighted_staking {
             });
         } else {
             // If oracle OK now, clear degraded flag
-            state.oracle_degraded = false;
+            state.oracle_degraded = 0;
             state.last_oracle_price_fp = spot_price_fp;
             state.last_oracle_ema_price_fp = ema_price_fp;
         }
 
         // oracle-driven return ring (only when ok AND we have previous price)
         if ok {
-            state.try_record_oracle_return(slot, spot_price_fp)?;
+            state.try_record_oracle_return(&mut ctx.accounts.returns_ring, slot, spot_price_fp, publish_time_u64 as i64)?;
+
+            if let Some(bars) = ctx.accounts.price_bars.as_mut() {
+                if let Some(bar) = bars.observe(spot_price_fp, publish_time_u64 as i64) {
+                    emit!(PriceBarClosed {
+                        epoch: state.epoch,
+                        slot,
+                        start_ts: bar.start_ts,
+                        open_fp: bar.open_fp,
+                        high_fp: bar.high_fp,
+                        low_fp: bar.low_fp,
+                        close_fp: bar.close_fp,
+                        obs_count: bar.obs_count,
+                        bar_count: bars.bar_count,
+                    });
+                }
+            }
         }
 
         state.bump_keeper_heartbeat_and_updates(&signer, slot)?;
@@ -491,8 +1334,9 @@ pub mod vol_weighted_staking {
             oracle_ema_price_fp: state.oracle_ema_price_fp,
             oracle_conf_fp: state.oracle_conf_fp,
             oracle_publish_slot: state.oracle_publish_slot,
-            oracle_ok: state.oracle_ok,
-            oracle_degraded: state.oracle_degraded,
+            oracle_ok: state.oracle_ok != 0,
+            oracle_degraded: state.oracle_degraded != 0,
+            implied_usdc_usd_fp: usdc_leg.usdc_usd_fp.get(),
         });
 
         Ok(())
@@ -500,11 +1344,11 @@ pub mod vol_weighted_staking {
 
     /// Keeper: epoch + policy update
     /// - policy cooldown
-    /// - realized vol gate via min_samples non-zero returns
+    /// - realized vol gate via min_samples effective (recorded) samples
     /// - hysteresis + slew
     /// - if oracle degraded: freeze policy updates (keep existing band/interval)
-    pub fn update_epoch_and_policy(ctx: Context<KeeperWithVault>) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
+    pub fn update_epoch_and_policy(ctx: Context<UpdateEpochAndPolicy>) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.require_not_paused()?;
 
         state.require_keeper_feeder(&ctx.accounts.signer.key())?;
@@ -514,7 +1358,7 @@ pub mod vol_weighted_staking {
 
         // Policy cooldown
         if state.last_policy_update_slot != 0 {
-            let elapsed = slot.checked_sub(state.last_policy_update_slot).unwrap_or(0);
+            let elapsed = slot.saturating_sub(state.last_policy_update_slot);
             require!(elapsed >= state.policy_update_min_slots, ErrorCode::PolicyCooldown);
         }
         state.last_policy_update_slot = slot;
@@ -523,22 +1367,75 @@ pub mod vol_weighted_staking {
         state.epoch = state.epoch.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
         state.keeper_updates_this_epoch = [0u16; MAX_KEEPERS];
 
+        // staking yield + hedge carry up to now (before the NAV snapshot)
+        let now_ts = Clock::get()?.unix_timestamp;
+        state.accrue_staking_yield(now_ts, slot)?;
+        let carry_elapsed_secs = now_ts.saturating_sub(state.last_carry_accrual_ts).max(0);
+        state.accrue_hedge_carry(now_ts)?;
+        emit!(CarryAccrued {
+            epoch: state.epoch,
+            slot,
+            elapsed_secs: carry_elapsed_secs,
+            hedge_notional_usd: state.hedge_notional_usd,
+            funding_bps_per_day: state.funding_bps_per_day,
+            borrow_bps_per_day: state.borrow_bps_per_day,
+            epoch_carry_usd: state.carry_accrued_epoch_usd,
+            hedge_carry_accrued_usd: state.hedge_carry_accrued_usd,
+        });
+        state.carry_accrued_epoch_usd = 0;
+
+        // fees on the accrued NAV (deferred while the mark is not fresh)
+        if state.mark_is_fresh(now_ts) {
+            state.accrue_fees(now_ts, slot)?;
+        }
+
         // If oracle degraded, freeze policy mapping (but still emit snapshot)
         let mut realized_updated = false;
+        let mut forecast_steps = 0u64;
         let prev_band = state.band_bps;
         let prev_interval = state.min_hedge_interval_slots;
 
-        if !state.oracle_degraded {
+        let window = ctx.accounts.returns_ring.window(state.returns_window);
+        let window_nonzero = window.iter().filter(|x| x.ret_fp != 0).count() as u16;
+
+        let bars = ctx.accounts.price_bars.as_deref().map(|b| &**b);
+        let samples_ready = if is_range_vol_mode(state.vol_mode) {
+            require!(bars.is_some(), ErrorCode::PriceBarsRequired);
+            bars.map_or(0, |b| b.usable_bar_count(now_ts))
+        } else {
+            effective_sample_count(&window)
+        };
+        let mut vol_ci = (0u16, MAX_ANNUAL_VOL_BPS);
+
+        if state.oracle_degraded == 0 {
             // realized update gate
-            if state.nonzero_samples >= (state.min_samples as u16) {
-                let realized = compute_realized_vol_bps_mode(state.vol_mode, &state.returns_ring, state.ewma_var_fp2)?;
+            if samples_ready >= (state.min_samples.max(MIN_EFFECTIVE_SAMPLES) as u16) {
+                let model_var_fp2 = if state.vol_mode == VolMode::Garch as u8 {
+                    state.garch_var_fp2
+                } else {
+                    state.ewma_var_fp2
+                };
+                let realized = compute_realized_vol_bps_mode(state.vol_mode, &window, bars, model_var_fp2 as u128, now_ts)?;
                 state.realized_vol_bps = realized;
                 realized_updated = true;
+                vol_ci = realized_vol_ci_bps(state.vol_mode, realized, samples_ready, state.ewma_alpha_bps);
+
+                if state.vol_mode == VolMode::Garch as u8 {
+                    forecast_steps = state.garch_horizon_steps();
+                    state.vol_forecast_bps = state.garch_forecast_vol_bps(forecast_steps)?;
+                }
             }
 
+            // Garch: score the forecast over the next hedge interval, not the trailing estimate
+            let policy_vol_bps = if state.vol_mode == VolMode::Garch as u8 && state.vol_forecast_bps > 0 {
+                state.vol_forecast_bps
+            } else {
+                state.realized_vol_bps
+            };
+
             // compute vol score
             let vol_score_bps = weighted_vol_score_bps(
-                state.realized_vol_bps,
+                policy_vol_bps,
                 state.implied_vol_bps,
                 state.vol_weight_realized_bps,
                 state.vol_weight_implied_bps,
@@ -548,7 +1445,7 @@ pub mod vol_weighted_staking {
             // hysteresis decision
             let hysteresis = state.hysteresis_bps;
             let last = state.last_vol_score_bps;
-            let delta = if vol_score_bps >= last { vol_score_bps - last } else { last - vol_score_bps };
+            let delta = vol_score_bps.abs_diff(last);
             let hysteresis_pass = delta >= hysteresis;
 
             // compute target policy if hysteresis passes (or first time)
@@ -615,9 +1512,18 @@ pub mod vol_weighted_staking {
             staked_value_usd: state.staked_value_usd()?,
             reserve_value_usd: state.reserve_value_usd()?,
             unrealized_pnl_usd: state.unrealized_pnl_usd()?,
+            realized_pnl_usd: state.hedge_realized_pnl_usd,
+            hedge_carry_accrued_usd: state.hedge_carry_accrued_usd,
             staking_accrued_usd: state.staking_accrued_usd,
             oracle_price_fp: state.oracle_price_fp,
-            oracle_ok: state.oracle_ok,
+            oracle_ok: state.oracle_ok != 0,
+        });
+        emit!(SharePriceUpdated {
+            epoch: state.epoch,
+            slot,
+            nav_usd: nav,
+            total_shares: state.total_shares,
+            share_price_fp: state.share_price_fp(nav)?,
         });
 
         emit!(EpochUpdated {
@@ -627,8 +1533,13 @@ pub mod vol_weighted_staking {
             implied_vol_bps: state.implied_vol_bps,
             vol_score_bps: state.vol_score_bps,
             realized_updated,
-            nonzero_samples: state.nonzero_samples,
-            oracle_degraded: state.oracle_degraded,
+            nonzero_samples: window_nonzero,
+            oracle_degraded: state.oracle_degraded != 0,
+            vol_forecast_bps: state.vol_forecast_bps,
+            forecast_horizon_steps: forecast_steps,
+            effective_samples: samples_ready,
+            vol_ci_low_bps: vol_ci.0,
+            vol_ci_high_bps: vol_ci.1,
         });
 
         emit!(VaultSnapshot {
@@ -643,15 +1554,15 @@ pub mod vol_weighted_staking {
             implied_vol_bps: state.implied_vol_bps,
             vol_score_bps: state.vol_score_bps,
             keeper_count: state.keeper_count,
-            paused: state.paused,
-            emergency_withdraw_enabled: state.emergency_withdraw_enabled,
+            paused: state.paused != 0,
+            emergency_withdraw_enabled: state.emergency_withdraw_enabled != 0,
             slot_now: slot,
             oracle_price_fp: state.oracle_price_fp,
             oracle_ema_price_fp: state.oracle_ema_price_fp,
             oracle_conf_fp: state.oracle_conf_fp,
             oracle_publish_slot: state.oracle_publish_slot,
-            oracle_ok: state.oracle_ok,
-            oracle_degraded: state.oracle_degraded,
+            oracle_ok: state.oracle_ok != 0,
+            oracle_degraded: state.oracle_degraded != 0,
             expected_carry_bps: state.expected_carry_bps(),
             config_version: state.config_version,
             config_hash: state.config_hash,
@@ -661,9 +1572,10 @@ pub mod vol_weighted_staking {
     }
 
     /// Permissionless: request hedge if interval met AND EMA drift exceeds band.
-    pub fn request_hedge(ctx: Context<UserWithVault>) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
+    pub fn request_hedge(ctx: Context<RequestHedge>) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.require_not_paused()?;
+        state.require_asset_book_if_initialized(ctx.accounts.asset_book.is_some())?;
 
         let slot = Clock::get()?.slot;
 
@@ -677,13 +1589,13 @@ pub mod vol_weighted_staking {
 
         require!(interval_ok, ErrorCode::HedgeTooSoon);
 
-        if state.oracle_degraded {
+        if state.oracle_degraded != 0 {
             require!(drift_bps >= state.extreme_drift_bps, ErrorCode::OracleDegradedHedgeBlocked);
         } else {
             require!(drift_ok, ErrorCode::DriftNotMet);
         }
 
-        if state.request_outstanding {
+        if state.request_outstanding != 0 {
             let since_req = slot.checked_sub(state.last_hedge_request_slot).unwrap_or(u64::MAX);
             if since_req > state.max_confirm_delay_slots {
                 state.missed_confirms = state.missed_confirms.saturating_add(1);
@@ -694,21 +1606,31 @@ pub mod vol_weighted_staking {
                     since_request_slots: since_req,
                     missed_confirms: state.missed_confirms,
                 });
-                state.request_outstanding = false;
+                state.request_outstanding = 0;
             }
         }
 
-        let sizing_price_fp = if state.oracle_ok && state.oracle_price_fp > 0 {
+        let sizing_price_fp = if state.oracle_ok != 0 && state.oracle_price_fp > 0 {
             state.oracle_price_fp
         } else {
             state.oracle_ema_price_fp
         };
 
+        // multi-LST book: inverse-vol / risk-parity targets, hedge sized on the blended beta
+        let mut beta_fp = state.lst_beta_fp;
+        let mut target_weights = [0u16; MAX_ASSETS];
+        if let Some(book) = ctx.accounts.asset_book.as_ref() {
+            if book.asset_count > 0 {
+                target_weights = book.compute_target_weights(state.realized_vol_bps)?;
+                beta_fp = book.blended_beta_fp(&target_weights);
+            }
+        }
+
         let target = compute_target_hedge_notional_usd_delta(
             state.staked_sol,
             sizing_price_fp,
             state.target_delta_bps,
-            state.lst_beta_fp,
+            beta_fp,
         )?;
 
         let delta_gap = target.checked_sub(state.hedge_notional_usd).ok_or(ErrorCode::MathOverflow)?;
@@ -720,7 +1642,7 @@ pub mod vol_weighted_staking {
 
         state.last_hedge_request_id = state.last_hedge_request_id.saturating_add(1);
         state.last_hedge_request_slot = slot;
-        state.request_outstanding = true;
+        state.request_outstanding = 1;
 
         emit!(HedgeRequested {
             epoch: state.epoch,
@@ -745,28 +1667,59 @@ pub mod vol_weighted_staking {
             oracle_price_fp: state.oracle_price_fp,
             oracle_conf_fp: state.oracle_conf_fp,
             oracle_publish_slot: state.oracle_publish_slot,
-            oracle_ok: state.oracle_ok,
-            oracle_degraded: state.oracle_degraded,
+            oracle_ok: state.oracle_ok != 0,
+            oracle_degraded: state.oracle_degraded != 0,
 
             target_delta_bps: state.target_delta_bps,
-            beta_fp: state.lst_beta_fp,
+            beta_fp,
 
             expected_carry_bps: state.expected_carry_bps(),
             config_version: state.config_version,
             config_hash: state.config_hash,
         });
 
+        if let Some(book) = ctx.accounts.asset_book.as_mut() {
+            if book.asset_count > 0 {
+                let n = (book.asset_count as usize).min(MAX_ASSETS);
+                let current_weights = book.current_weights_bps();
+                let mut mints = [Pubkey::default(); MAX_ASSETS];
+                let mut realized_vol_bps = [0u16; MAX_ASSETS];
+                let mut target_sol = [0u64; MAX_ASSETS];
+                for i in 0..n {
+                    book.assets[i].target_weight_bps = target_weights[i];
+                    mints[i] = book.assets[i].mint;
+                    realized_vol_bps[i] = book.assets[i].realized_vol_bps;
+                    target_sol[i] = ((state.staked_sol as u128) * (target_weights[i] as u128) / (BPS_DENOM as u128)) as u64;
+                }
+                book.pending_request_id = state.last_hedge_request_id;
+
+                emit!(AssetRebalanceIntent {
+                    epoch: state.epoch,
+                    slot,
+                    request_id: state.last_hedge_request_id,
+                    weighting_mode: book.weighting_mode,
+                    asset_count: book.asset_count,
+                    mints,
+                    realized_vol_bps,
+                    current_weight_bps: current_weights,
+                    target_weight_bps: target_weights,
+                    target_sol,
+                    blended_beta_fp: beta_fp,
+                });
+            }
+        }
+
         Ok(())
     }
 
     /// Keeper: confirm hedge execution (two-phase).
     pub fn confirm_hedge(
-        ctx: Context<KeeperWithVault>,
+        ctx: Context<ConfirmHedge>,
         request_id: u64,
         new_hedge_notional_usd: i64,
         fill_price_fp: i64,
     ) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.require_not_paused()?;
 
         let signer = ctx.accounts.signer.key();
@@ -774,14 +1727,18 @@ pub mod vol_weighted_staking {
         state.require_keeper_rate_limit_ok(&signer)?;
 
         require!(fill_price_fp > 0 && fill_price_fp <= MAX_PRICE_FP, ErrorCode::InvalidParams);
-        require!(state.request_outstanding, ErrorCode::NoOutstandingRequest);
+        require!(state.request_outstanding != 0, ErrorCode::NoOutstandingRequest);
         require!(request_id == state.last_hedge_request_id, ErrorCode::WrongRequestId);
+        state.require_asset_book_if_initialized(ctx.accounts.asset_book.is_some())?;
 
         let slot = Clock::get()?.slot;
 
+        // carry accrues on the notional held up to this fill
+        state.accrue_hedge_carry(Clock::get()?.unix_timestamp)?;
+        let realized_pnl_usd = state.book_hedge_fill(new_hedge_notional_usd, fill_price_fp)?;
         state.set_hedge_notional_checked(new_hedge_notional_usd)?;
 
-        let ref_price_fp = if state.oracle_ok && state.oracle_price_fp > 0 {
+        let ref_price_fp = if state.oracle_ok != 0 && state.oracle_price_fp > 0 {
             state.oracle_price_fp
         } else {
             state.oracle_ema_price_fp
@@ -793,10 +1750,31 @@ pub mod vol_weighted_staking {
 
         state.last_fill_slot = slot;
         state.hedge_fill_count = state.hedge_fill_count.saturating_add(1);
-        state.request_outstanding = false;
+        state.request_outstanding = 0;
 
         state.bump_keeper_heartbeat_and_updates(&signer, slot)?;
 
+        // the confirmed request carried an asset rebalance intent: book the new allocations
+        if let Some(book) = ctx.accounts.asset_book.as_mut() {
+            if book.asset_count > 0 && book.pending_request_id == request_id {
+                book.apply_target_allocations(state.staked_sol);
+                book.pending_request_id = 0;
+
+                let n = (book.asset_count as usize).min(MAX_ASSETS);
+                let mut allocated_sol = [0u64; MAX_ASSETS];
+                for (s, a) in allocated_sol.iter_mut().zip(&book.assets[..n]) {
+                    *s = a.allocated_sol;
+                }
+                emit!(AssetAllocationsUpdated {
+                    epoch: state.epoch,
+                    slot,
+                    request_id,
+                    allocated_sol,
+                    weight_bps: book.current_weights_bps(),
+                });
+            }
+        }
+
         emit!(HedgeConfirmed {
             epoch: state.epoch,
             slot,
@@ -807,16 +1785,109 @@ pub mod vol_weighted_staking {
             slippage_bps: slip_bps,
             avg_fill_slippage_bps: state.avg_fill_slippage_bps,
             hedge_fill_count: state.hedge_fill_count,
+            hedge_entry_price_fp: state.hedge_entry_price_fp,
+            realized_pnl_usd,
+            hedge_realized_pnl_usd: state.hedge_realized_pnl_usd,
         });
 
         Ok(())
     }
 
-    /// Keeper: (simulated) deposit bond counter (no SOL transfer)
-    pub fn deposit_keeper_bond(ctx: Context<KeeperWithVault>, amount_lamports: u64) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
-        state.require_not_paused()?;
-
+    /// Keeper/authority: simulate a slashing event of `slash_bps` of staked_sol on `validator_id`
+    /// (vote account). The loss is absorbed by reserve_sol first; any remainder is socialized by
+    /// cutting staked_sol (NAV/share falls, shares unchanged). Allowed while paused.
+    /// Auto-pauses the vault once the reserve buffer is exhausted.
+    pub fn apply_slashing_event(ctx: Context<ApplySlashing>, slash_bps: u16, validator_id: Pubkey) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        let signer = ctx.accounts.signer.key();
+        state.require_keeper_feeder(&signer)?;
+        state.require_keeper_rate_limit_ok(&signer)?;
+        require!(slash_bps > 0 && slash_bps <= BPS_DENOM, ErrorCode::InvalidParams);
+        state.sync_validator_registry(ctx.accounts.validator_registry.as_deref_mut().map(|r| &mut **r))?;
+
+        let slot = Clock::get()?.slot;
+
+        // with a delegation book, only the slashed validator's stake is at risk
+        let mut validator_idx: Option<usize> = None;
+        let mut slashable_sol = state.staked_sol;
+        if let Some(registry) = ctx.accounts.validator_registry.as_ref() {
+            if registry.validator_count > 0 {
+                let i = registry.validator_index(&validator_id).ok_or(ErrorCode::ValidatorNotFound)?;
+                slashable_sol = registry.validators[i].delegated_sol.min(state.staked_sol);
+                validator_idx = Some(i);
+            }
+        }
+
+        let loss_sol = ((slashable_sol as u128)
+            .checked_mul(slash_bps as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (BPS_DENOM as u128)) as u64;
+
+        // SOL owed to matured unstake tickets is not a first-loss buffer
+        let reserve_used_sol = loss_sol.min(state.free_reserve_sol());
+        let socialized_sol = loss_sol - reserve_used_sol;
+
+        let prev_staked = state.staked_sol;
+        let prev_hedge = state.hedge_notional_usd;
+        state.reserve_sol -= reserve_used_sol;
+        state.staked_sol = state.staked_sol.checked_sub(socialized_sol).ok_or(ErrorCode::MathOverflow)?;
+        state.total_slashed_sol = state.total_slashed_sol.checked_add(loss_sol).ok_or(ErrorCode::MathOverflow)?;
+
+        // reserve-covered loss is re-delegated from the buffer; only the socialized part leaves the book
+        if let (Some(registry), Some(i)) = (ctx.accounts.validator_registry.as_mut(), validator_idx) {
+            let v = &mut registry.validators[i];
+            v.delegated_sol = v.delegated_sol.saturating_sub(socialized_sol);
+            v.slash_count = v.slash_count.saturating_add(1);
+            v.slashed_sol = v.slashed_sol.saturating_add(loss_sol);
+            v.last_slash_epoch = state.epoch;
+            v.last_slash_bps = slash_bps;
+        }
+
+        if socialized_sol > 0 {
+            state.scale_hedge_for_unstake(prev_staked)?;
+            state.emit_hedge_scaled_down(slot, prev_hedge)?;
+        }
+
+        let reserve_ratio_ok = state.enforce_reserve_ratio().is_ok();
+        let buffer_exhausted = state.free_reserve_sol() == 0 && loss_sol > 0;
+        let auto_paused = buffer_exhausted && state.paused == 0;
+        if auto_paused {
+            state.paused = 1;
+            state.bump_config_version_and_hash();
+            emit!(PausedSet {
+                epoch: state.epoch,
+                slot,
+                paused: true,
+                config_version: state.config_version,
+                config_hash: state.config_hash,
+            });
+        }
+
+        state.bump_keeper_heartbeat_and_updates(&signer, slot)?;
+
+        emit!(SlashingApplied {
+            epoch: state.epoch,
+            slot,
+            validator_id,
+            slash_bps,
+            loss_sol,
+            reserve_used_sol,
+            socialized_sol,
+            reserve_sol: state.reserve_sol,
+            staked_sol: state.staked_sol,
+            required_reserve_sol: state.required_reserve_sol()?,
+            reserve_ratio_ok,
+            auto_paused,
+        });
+
+        Ok(())
+    }
+
+    /// Keeper: (simulated) deposit bond counter (no SOL transfer)
+    pub fn deposit_keeper_bond(ctx: Context<KeeperWithVault>, amount_lamports: u64) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.require_not_paused()?;
+
         let signer = ctx.accounts.signer.key();
         state.require_keeper_feeder(&signer)?;
         require!(amount_lamports > 0, ErrorCode::InvalidParams);
@@ -839,8 +1910,8 @@ pub mod vol_weighted_staking {
 
     /// Authority: pause/unpause
     pub fn set_paused(ctx: Context<AuthorityOnly>, paused: bool) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
-        state.paused = paused;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.paused = paused as u8;
         state.bump_config_version_and_hash();
 
         emit!(PausedSet {
@@ -855,8 +1926,8 @@ pub mod vol_weighted_staking {
 
     /// Authority: emergency mode flag
     pub fn set_emergency_withdraw_enabled(ctx: Context<AuthorityOnly>, enabled: bool) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
-        state.emergency_withdraw_enabled = enabled;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.emergency_withdraw_enabled = enabled as u8;
         state.bump_config_version_and_hash();
 
         emit!(EmergencyModeSet {
@@ -872,7 +1943,7 @@ pub mod vol_weighted_staking {
     /// Authority: two-step authority transfer (set)
     pub fn set_pending_authority(ctx: Context<AuthorityOnly>, pending: Pubkey) -> Result<()> {
         require!(pending != Pubkey::default(), ErrorCode::InvalidParams);
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.pending_authority = pending;
         state.bump_config_version_and_hash();
 
@@ -888,7 +1959,7 @@ pub mod vol_weighted_staking {
 
     /// Pending authority accepts transfer
     pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         require!(state.pending_authority != Pubkey::default(), ErrorCode::InvalidParams);
         require!(ctx.accounts.pending_authority.key() == state.pending_authority, ErrorCode::Unauthorized);
 
@@ -916,7 +1987,7 @@ pub mod vol_weighted_staking {
     /// Authority: set keeper admin delegate
     pub fn set_keeper_admin(ctx: Context<AuthorityOnly>, keeper_admin: Pubkey) -> Result<()> {
         require!(keeper_admin != Pubkey::default(), ErrorCode::InvalidParams);
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.keeper_admin = keeper_admin;
         state.bump_config_version_and_hash();
 
@@ -933,7 +2004,7 @@ pub mod vol_weighted_staking {
     /// Keeper admin: add keeper
     pub fn add_keeper(ctx: Context<KeeperAdminOnly>, keeper: Pubkey) -> Result<()> {
         require!(keeper != Pubkey::default(), ErrorCode::InvalidParams);
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
 
         state.add_keeper(keeper)?;
         state.bump_config_version_and_hash();
@@ -953,7 +2024,7 @@ pub mod vol_weighted_staking {
     /// Keeper admin: remove keeper
     pub fn remove_keeper(ctx: Context<KeeperAdminOnly>, keeper: Pubkey) -> Result<()> {
         require!(keeper != Pubkey::default(), ErrorCode::InvalidParams);
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
 
         state.remove_keeper(keeper)?;
         state.bump_config_version_and_hash();
@@ -982,7 +2053,7 @@ pub mod vol_weighted_staking {
         require!(max_band_bps <= MAX_VOL_BPS, ErrorCode::InvalidParams);
         require!(min_interval_slots <= max_interval_slots, ErrorCode::InvalidParams);
 
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.min_band_bps = min_band_bps;
         state.max_band_bps = max_band_bps;
         state.min_interval_slots = min_interval_slots;
@@ -1022,7 +2093,7 @@ pub mod vol_weighted_staking {
         require!(hysteresis_bps <= BPS_DENOM, ErrorCode::InvalidParams);
         require!(extreme_drift_bps <= BPS_DENOM, ErrorCode::InvalidParams);
 
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.policy_update_min_slots = policy_update_min_slots;
         state.max_policy_slew_bps = max_policy_slew_bps;
         state.hysteresis_bps = hysteresis_bps;
@@ -1045,27 +2116,44 @@ pub mod vol_weighted_staking {
 
     /// Authority: set vol model
     pub fn set_vol_model(
-        ctx: Context<AuthorityOnly>,
+        ctx: Context<SetVolModel>,
         vol_mode: u8,
         ewma_alpha_bps: u16,
         min_samples: u8,
         min_return_spacing_slots: u64,
+        returns_window: u16,
+        return_kind: u8,
     ) -> Result<()> {
+        require!(vol_mode <= VolMode::Garch as u8, ErrorCode::InvalidParams);
         require!(
-            vol_mode == VolMode::Stdev as u8 || vol_mode == VolMode::Ewma as u8 || vol_mode == VolMode::Mad as u8,
+            return_kind == ReturnKind::Simple as u8 || return_kind == ReturnKind::Log as u8,
             ErrorCode::InvalidParams
         );
         if vol_mode == VolMode::Ewma as u8 {
             require!(ewma_alpha_bps > 0 && ewma_alpha_bps <= BPS_DENOM, ErrorCode::InvalidParams);
         }
-        require!(min_samples > 0 && min_samples <= (N_RETURNS as u8), ErrorCode::InvalidParams);
+        require!(min_samples >= MIN_EFFECTIVE_SAMPLES, ErrorCode::InvalidParams);
         require!(min_return_spacing_slots > 0, ErrorCode::InvalidParams);
+        require!(
+            returns_window >= (min_samples as u16) && returns_window <= ctx.accounts.returns_ring.capacity,
+            ErrorCode::InvalidParams
+        );
+
+        if is_range_vol_mode(vol_mode) {
+            require!(ctx.accounts.price_bars.is_some(), ErrorCode::PriceBarsRequired);
+        }
 
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        if vol_mode == VolMode::Garch as u8 {
+            // set_garch_params first
+            require!(state.garch_params_valid(), ErrorCode::InvalidParams);
+        }
         state.vol_mode = vol_mode;
         state.ewma_alpha_bps = ewma_alpha_bps;
         state.min_samples = min_samples;
         state.min_return_spacing_slots = min_return_spacing_slots;
+        state.returns_window = returns_window;
+        state.return_kind = return_kind;
 
         state.bump_config_version_and_hash();
 
@@ -1076,6 +2164,37 @@ pub mod vol_weighted_staking {
             ewma_alpha_bps,
             min_samples,
             min_return_spacing_slots,
+            returns_window,
+            return_kind,
+            config_version: state.config_version,
+            config_hash: state.config_hash,
+        });
+        Ok(())
+    }
+
+    /// Authority: GARCH(1,1) params (omega as annualized variance fp^2; alpha + beta < 1)
+    pub fn set_garch_params(ctx: Context<AuthorityOnly>, omega_fp2: u64, alpha_bps: u16, beta_bps: u16) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.garch_omega_fp2 = omega_fp2;
+        state.garch_alpha_bps = alpha_bps;
+        state.garch_beta_bps = beta_bps;
+        require!(state.garch_params_valid(), ErrorCode::InvalidParams);
+        require!((omega_fp2 as u128) <= MAX_VAR_FP2, ErrorCode::InvalidParams);
+
+        // restart the conditional variance from the new long-run level
+        state.garch_var_fp2 = 0;
+        state.vol_forecast_bps = 0;
+
+        state.bump_config_version_and_hash();
+
+        let long_run_vol_bps = fp_to_bps(isqrt_u128(state.garch_long_run_var_fp2()?))?;
+        emit!(GarchParamsUpdated {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            omega_fp2,
+            alpha_bps,
+            beta_bps,
+            long_run_vol_bps,
             config_version: state.config_version,
             config_hash: state.config_hash,
         });
@@ -1090,17 +2209,16 @@ pub mod vol_weighted_staking {
         max_confidence_bps: u16,
         max_price_jump_bps: u16,
     ) -> Result<()> {
-        require!(
-            oracle_feed_choice == OracleFeedChoice::SolUsd as u8
-                || oracle_feed_choice == OracleFeedChoice::SolUsdc as u8
-                || oracle_feed_choice == OracleFeedChoice::AutoPreferUsdThenUsdc as u8,
-            ErrorCode::InvalidParams
-        );
+        require!(is_valid_feed_choice(oracle_feed_choice), ErrorCode::InvalidParams);
         require!(max_price_age_slots > 0, ErrorCode::InvalidParams);
         require!(max_confidence_bps <= BPS_DENOM, ErrorCode::InvalidParams);
         require!(max_price_jump_bps <= BPS_DENOM, ErrorCode::InvalidParams);
 
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        if oracle_feed_choice == OracleFeedChoice::Median as u8 {
+            // set_oracle_aggregation first
+            require!(state.oracle_feed_mask != 0, ErrorCode::InvalidParams);
+        }
         state.oracle_feed_choice = oracle_feed_choice;
         state.max_price_age_slots = max_price_age_slots;
         state.max_confidence_bps = max_confidence_bps;
@@ -1121,12 +2239,210 @@ pub mod vol_weighted_staking {
         Ok(())
     }
 
+    /// Authority: pin the oracle accounts update_oracle_price will accept.
+    /// `switchboard_feed = Pubkey::default()` leaves no Switchboard feed registered.
+    pub fn set_oracle_accounts(
+        ctx: Context<AuthorityOnly>,
+        sol_usd: Pubkey,
+        sol_usdc: Pubkey,
+        switchboard_feed: Pubkey,
+    ) -> Result<()> {
+        require!(sol_usd != Pubkey::default() && sol_usdc != Pubkey::default(), ErrorCode::InvalidParams);
+
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        let prev = (state.oracle_sol_usd, state.oracle_sol_usdc, state.oracle_switchboard_feed);
+        if prev == (sol_usd, sol_usdc, switchboard_feed) {
+            return Ok(());
+        }
+        state.oracle_sol_usd = sol_usd;
+        state.oracle_sol_usdc = sol_usdc;
+        state.oracle_switchboard_feed = switchboard_feed;
+
+        state.bump_config_version_and_hash();
+
+        emit!(OracleAccountsUpdated {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            sol_usd,
+            sol_usdc,
+            switchboard_feed,
+            prev_sol_usd: prev.0,
+            prev_sol_usdc: prev.1,
+            prev_switchboard_feed: prev.2,
+            config_version: state.config_version,
+            config_hash: state.config_hash,
+        });
+        Ok(())
+    }
+
+    /// Authority: read pyth_sol_usd / pyth_sol_usdc as Pyth pull (PriceUpdateV2) accounts.
+    /// Each must be owned by PYTH_RECEIVER_PROGRAM_ID, carry the pinned feed id and meet
+    /// the minimum verification level. A slot with a zero feed id reads as refused
+    /// (ORACLE_REASON_NO_FEED_ID) in pull mode.
+    pub fn set_pyth_pull_config(
+        ctx: Context<AuthorityOnly>,
+        enabled: bool,
+        sol_usd_feed_id: [u8; 32],
+        sol_usdc_feed_id: [u8; 32],
+        min_verification: u8,
+        min_signatures: u8,
+    ) -> Result<()> {
+        require!(
+            min_verification == PullVerification::Partial as u8 || min_verification == PullVerification::Full as u8,
+            ErrorCode::InvalidParams
+        );
+        if enabled {
+            require!(sol_usd_feed_id != [0u8; 32] || sol_usdc_feed_id != [0u8; 32], ErrorCode::InvalidParams);
+            require!(
+                min_verification == PullVerification::Full as u8 || min_signatures > 0,
+                ErrorCode::InvalidParams
+            );
+        }
+
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        // a pinned USDC/USD account is read in pull mode too, so it needs its feed id
+        require!(
+            !enabled || state.oracle_usdc_usd == Pubkey::default() || state.pyth_usdc_usd_feed_id != [0u8; 32],
+            ErrorCode::InvalidParams
+        );
+        state.pyth_pull_enabled = enabled as u8;
+        state.pyth_sol_usd_feed_id = sol_usd_feed_id;
+        state.pyth_sol_usdc_feed_id = sol_usdc_feed_id;
+        state.pyth_pull_min_verification = min_verification;
+        state.pyth_pull_min_signatures = min_signatures;
+
+        state.bump_config_version_and_hash();
+
+        emit!(PythPullConfigUpdated {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            enabled,
+            sol_usd_feed_id,
+            sol_usdc_feed_id,
+            min_verification,
+            min_signatures,
+            config_version: state.config_version,
+            config_hash: state.config_hash,
+        });
+        Ok(())
+    }
+
+    /// Authority: USDC/USD feed used to turn SOL/USDC into SOL/USD (Pubkey::default() =
+    /// take USDC 1:1), its pull feed id, and the depeg (bps from 1.0) beyond which the
+    /// SOL/USDC leg is refused
+    pub fn set_usdc_cross_rate(
+        ctx: Context<AuthorityOnly>,
+        usdc_usd: Pubkey,
+        usdc_usd_feed_id: [u8; 32],
+        max_depeg_bps: u16,
+    ) -> Result<()> {
+        require!(max_depeg_bps > 0 && max_depeg_bps <= BPS_DENOM, ErrorCode::InvalidParams);
+
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        // pull mode reads USDC/USD as a PriceUpdateV2 account, which needs its feed id
+        require!(
+            state.pyth_pull_enabled == 0 || usdc_usd == Pubkey::default() || usdc_usd_feed_id != [0u8; 32],
+            ErrorCode::InvalidParams
+        );
+        let prev_usdc_usd = state.oracle_usdc_usd;
+        state.oracle_usdc_usd = usdc_usd;
+        state.pyth_usdc_usd_feed_id = usdc_usd_feed_id;
+        state.max_usdc_depeg_bps = max_depeg_bps;
+
+        state.bump_config_version_and_hash();
+
+        emit!(UsdcCrossRateUpdated {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            usdc_usd,
+            prev_usdc_usd,
+            usdc_usd_feed_id,
+            max_depeg_bps,
+            config_version: state.config_version,
+            config_hash: state.config_hash,
+        });
+        Ok(())
+    }
+
+    /// Authority: feeds for OracleFeedChoice::Median (bits of ORACLE_FEED_BIT_*), the
+    /// quorum of surviving feeds, and the max high-low spread (bps of the median)
+    pub fn set_oracle_aggregation(
+        ctx: Context<AuthorityOnly>,
+        feed_mask: u8,
+        min_feeds: u8,
+        max_spread_bps: u16,
+    ) -> Result<()> {
+        require!(feed_mask != 0 && feed_mask & !ORACLE_FEED_MASK_ALL == 0, ErrorCode::InvalidParams);
+        require!(
+            cfg!(feature = "switchboard") || feed_mask & ORACLE_FEED_BIT_SWITCHBOARD == 0,
+            ErrorCode::InvalidParams
+        );
+        require!(min_feeds >= 1 && (min_feeds as u32) <= feed_mask.count_ones(), ErrorCode::InvalidParams);
+        require!(max_spread_bps > 0 && max_spread_bps <= BPS_DENOM, ErrorCode::InvalidParams);
+
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.oracle_feed_mask = feed_mask;
+        state.oracle_min_feeds = min_feeds;
+        state.oracle_max_spread_bps = max_spread_bps;
+
+        state.bump_config_version_and_hash();
+
+        emit!(OracleAggregationUpdated {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            feed_mask,
+            min_feeds,
+            max_spread_bps,
+            config_version: state.config_version,
+            config_hash: state.config_hash,
+        });
+        Ok(())
+    }
+
+    /// Authority: create the manual price account (OracleFeedChoice::Manual)
+    pub fn initialize_manual_price_feed(ctx: Context<InitializeManualPriceFeed>) -> Result<()> {
+        let feed = &mut ctx.accounts.manual_price;
+        feed.vault = ctx.accounts.vault_state.key();
+        feed.bump = ctx.bumps.manual_price;
+        feed.price_fp = 0;
+        feed.ema_fp = 0;
+        feed.conf_fp = 0;
+        feed.publish_time = 0;
+        Ok(())
+    }
+
+    /// Authority: publish a manual price (fp 1e6), stamped with the current unix time.
+    /// `ema_fp = 0` reuses the spot price.
+    pub fn set_manual_price(ctx: Context<SetManualPrice>, price_fp: i64, ema_fp: i64, conf_fp: i64) -> Result<()> {
+        require!(price_fp > 0 && price_fp <= MAX_PRICE_FP, ErrorCode::InvalidParams);
+        require!((0..=MAX_PRICE_FP).contains(&ema_fp), ErrorCode::InvalidParams);
+        require!((0..=MAX_PRICE_FP).contains(&conf_fp), ErrorCode::InvalidParams);
+
+        let clock = Clock::get()?;
+        let feed = &mut ctx.accounts.manual_price;
+        feed.price_fp = price_fp;
+        feed.ema_fp = if ema_fp == 0 { price_fp } else { ema_fp };
+        feed.conf_fp = conf_fp;
+        feed.publish_time = clock.unix_timestamp;
+
+        let epoch = ctx.accounts.vault_state.load()?.epoch;
+        emit!(ManualPriceSet {
+            epoch,
+            slot: clock.slot,
+            price_fp: feed.price_fp,
+            ema_fp: feed.ema_fp,
+            conf_fp: feed.conf_fp,
+            publish_time: feed.publish_time,
+        });
+        Ok(())
+    }
+
     /// Authority: hedge sizing knobs
     pub fn set_hedge_sizing(ctx: Context<AuthorityOnly>, target_delta_bps: u16, lst_beta_fp: i64) -> Result<()> {
         require!(target_delta_bps <= BPS_DENOM, ErrorCode::InvalidParams);
         require!(lst_beta_fp > 0, ErrorCode::InvalidParams);
 
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.target_delta_bps = target_delta_bps;
         state.lst_beta_fp = lst_beta_fp;
 
@@ -1156,7 +2472,7 @@ pub mod vol_weighted_staking {
         require!(max_hedge_per_sol_usd_fp > 0, ErrorCode::InvalidParams);
         require!(min_reserve_bps <= BPS_DENOM, ErrorCode::InvalidParams);
 
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.max_staked_sol = max_staked_sol;
         state.max_abs_hedge_notional_usd = max_abs_hedge_notional_usd;
         state.max_hedge_per_sol_usd_fp = max_hedge_per_sol_usd_fp;
@@ -1186,7 +2502,7 @@ pub mod vol_weighted_staking {
     pub fn set_keeper_controls(ctx: Context<AuthorityOnly>, max_updates_per_epoch: u16, keeper_bond_required_lamports: u64) -> Result<()> {
         require!(max_updates_per_epoch > 0, ErrorCode::InvalidParams);
 
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.max_updates_per_epoch = max_updates_per_epoch;
         state.keeper_bond_required_lamports = keeper_bond_required_lamports;
 
@@ -1207,7 +2523,7 @@ pub mod vol_weighted_staking {
     pub fn set_confirm_config(ctx: Context<AuthorityOnly>, max_confirm_delay_slots: u64) -> Result<()> {
         require!(max_confirm_delay_slots > 0, ErrorCode::InvalidParams);
 
-        let state = &mut ctx.accounts.vault_state;
+        let mut state = ctx.accounts.vault_state.load_mut()?;
         state.max_confirm_delay_slots = max_confirm_delay_slots;
 
         state.bump_config_version_and_hash();
@@ -1221,602 +2537,3457 @@ pub mod vol_weighted_staking {
         });
         Ok(())
     }
-}
 
-/// -------------------------------
-/// Accounts
-/// -------------------------------
+    /// Authority: create the oracle returns ring PDA with `capacity` samples (fixed once created)
+    pub fn initialize_returns_ring(ctx: Context<InitializeReturnsRing>, capacity: u16) -> Result<()> {
+        require!(
+            (MIN_RETURNS_CAPACITY..=MAX_RETURNS_CAPACITY).contains(&capacity),
+            ErrorCode::InvalidParams
+        );
+        let window = ctx.accounts.vault_state.load()?.returns_window;
+        require!(window <= capacity, ErrorCode::InvalidParams);
+
+        let ring = &mut ctx.accounts.returns_ring;
+        ring.vault = ctx.accounts.vault_state.key();
+        ring.bump = ctx.bumps.returns_ring;
+        ring.capacity = capacity;
+        ring.head = 0;
+        ring.nonzero_samples = 0;
+        ring.samples = vec![ReturnSample::default(); capacity as usize];
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct InitializeVault<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    /// Authority: create the OHLC bar PDA (`capacity` completed bars of `bar_secs` each)
+    pub fn initialize_price_bars(ctx: Context<InitializePriceBars>, capacity: u16, bar_secs: u32) -> Result<()> {
+        require!((MIN_PRICE_BARS..=MAX_PRICE_BARS).contains(&capacity), ErrorCode::InvalidParams);
+        require!(bar_secs > 0, ErrorCode::InvalidParams);
+
+        let bars = &mut ctx.accounts.price_bars;
+        bars.vault = ctx.accounts.vault_state.key();
+        bars.bump = ctx.bumps.price_bars;
+        bars.bar_secs = bar_secs;
+        bars.capacity = capacity;
+        bars.head = 0;
+        bars.bar_count = 0;
+        bars.current = OhlcBar::default();
+        bars.bars = vec![OhlcBar::default(); capacity as usize];
+        Ok(())
+    }
 
-    #[account(
-        init,
-        payer = authority,
-        space = VaultState::SPACE,
-        seeds = [b"vault", authority.key().as_ref()],
-        bump
-    )]
-    pub vault_state: Account<'info, VaultState>,
+    /// Authority: create the unstake queue PDA for this vault
+    pub fn initialize_withdraw_queue(ctx: Context<InitializeWithdrawQueue>) -> Result<()> {
+        let queue = &mut ctx.accounts.withdraw_queue;
+        queue.vault = ctx.accounts.vault_state.key();
+        queue.bump = ctx.bumps.withdraw_queue;
+        queue.next_ticket_id = 1;
+        queue.open_tickets = 0;
+        queue.fill_factor = FILL_FACTOR_ONE;
+        queue.fill_generation = 0;
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    /// Authority: create the vault's validator delegation book
+    pub fn initialize_validator_registry(
+        ctx: Context<InitializeValidatorRegistry>,
+        rebalance_drift_bps: u16,
+    ) -> Result<()> {
+        require!(rebalance_drift_bps > 0 && rebalance_drift_bps <= BPS_DENOM, ErrorCode::InvalidParams);
 
-/// Permissionless/user context
-#[derive(Accounts)]
-pub struct UserWithVault<'info> {
-    #[account(mut)]
-    pub vault_state: Account<'info, VaultState>,
-}
+        ctx.accounts.vault_state.load_mut()?.has_validator_registry = 1;
 
-/// Keeper context
-#[derive(Accounts)]
-pub struct KeeperWithVault<'info> {
-    pub signer: Signer<'info>,
-    #[account(mut)]
-    pub vault_state: Account<'info, VaultState>,
-}
+        let registry = &mut ctx.accounts.validator_registry;
+        registry.vault = ctx.accounts.vault_state.key();
+        registry.bump = ctx.bumps.validator_registry;
+        registry.validator_count = 0;
+        registry.rebalance_drift_bps = rebalance_drift_bps;
+        registry.validators = [ValidatorSlot::default(); MAX_VALIDATORS];
+        Ok(())
+    }
 
-/// Update oracle price (requires signer + two pyth accounts)
-#[derive(Accounts)]
-pub struct UpdateOraclePrice<'info> {
-    pub signer: Signer<'info>,
-    #[account(mut)]
-    pub vault_state: Account<'info, VaultState>,
+    /// Authority: register a validator (vote account) with a target weight and cap.
+    /// Sum of target weights must stay <= 10_000 bps.
+    pub fn add_validator(
+        ctx: Context<ValidatorAdmin>,
+        vote_account: Pubkey,
+        target_weight_bps: u16,
+        cap_sol: u64,
+    ) -> Result<()> {
+        require!(vote_account != Pubkey::default(), ErrorCode::InvalidParams);
+        require!(cap_sol > 0, ErrorCode::InvalidParams);
 
-    /// CHECK: Pyth SOL/USD price account
-    pub pyth_sol_usd: AccountInfo<'info>,
-    /// CHECK: Pyth SOL/USDC price account
-    pub pyth_sol_usdc: AccountInfo<'info>,
-}
+        let registry = &mut ctx.accounts.validator_registry;
+        require!(registry.validator_index(&vote_account).is_none(), ErrorCode::InvalidParams);
+        let n = registry.validator_count as usize;
+        require!(n < MAX_VALIDATORS, ErrorCode::ValidatorRegistryFull);
+        require!(
+            registry.total_weight_bps() + (target_weight_bps as u32) <= BPS_DENOM as u32,
+            ErrorCode::InvalidParams
+        );
 
-/// Authority-only
-#[derive(Accounts)]
-pub struct AuthorityOnly<'info> {
-    pub authority: Signer<'info>,
-    #[account(mut, has_one = authority)]
-    pub vault_state: Account<'info, VaultState>,
-}
+        registry.validators[n] = ValidatorSlot {
+            vote_account,
+            target_weight_bps,
+            cap_sol,
+            ..ValidatorSlot::default()
+        };
+        registry.validator_count = (n + 1) as u8;
 
-/// Keeper-admin-only
-#[derive(Accounts)]
-pub struct KeeperAdminOnly<'info> {
-    pub keeper_admin: Signer<'info>,
-    #[account(mut)]
-    pub vault_state: Account<'info, VaultState>,
-}
+        emit!(ValidatorUpdated {
+            epoch: ctx.accounts.vault_state.load()?.epoch,
+            slot: Clock::get()?.slot,
+            vote_account,
+            target_weight_bps,
+            cap_sol,
+            total_weight_bps: registry.total_weight_bps() as u16,
+            validator_count: registry.validator_count,
+        });
+        Ok(())
+    }
 
-/// Accept authority
-#[derive(Accounts)]
-pub struct AcceptAuthority<'info> {
-    pub pending_authority: Signer<'info>,
-    #[account(mut)]
-    pub vault_state: Account<'info, VaultState>,
-}
+    /// Authority: change a validator's target weight / cap (weight 0 = wind down)
+    pub fn update_validator(
+        ctx: Context<ValidatorAdmin>,
+        vote_account: Pubkey,
+        target_weight_bps: u16,
+        cap_sol: u64,
+    ) -> Result<()> {
+        require!(cap_sol > 0, ErrorCode::InvalidParams);
 
-/// -------------------------------
-/// State
-/// -------------------------------
+        let registry = &mut ctx.accounts.validator_registry;
+        let i = registry.validator_index(&vote_account).ok_or(ErrorCode::ValidatorNotFound)?;
+        let others = registry.total_weight_bps() - (registry.validators[i].target_weight_bps as u32);
+        require!(others + (target_weight_bps as u32) <= BPS_DENOM as u32, ErrorCode::InvalidParams);
 
-#[account]
-pub struct VaultState {
-    // roles
-    pub authority: Pubkey,
-    pub pending_authority: Pubkey,
-    pub keeper_admin: Pubkey,
-    pub vault_bump: u8,
+        registry.validators[i].target_weight_bps = target_weight_bps;
+        registry.validators[i].cap_sol = cap_sol;
 
-    // config identity
-    pub config_version: u64,
-    pub config_hash: [u8; 32],
+        emit!(ValidatorUpdated {
+            epoch: ctx.accounts.vault_state.load()?.epoch,
+            slot: Clock::get()?.slot,
+            vote_account,
+            target_weight_bps,
+            cap_sol,
+            total_weight_bps: registry.total_weight_bps() as u16,
+            validator_count: registry.validator_count,
+        });
+        Ok(())
+    }
 
-    // epoch + cooldown
-    pub epoch: u64,
-    pub last_policy_update_slot: u64,
+    /// Authority: remove a fully undelegated validator
+    pub fn remove_validator(ctx: Context<ValidatorAdmin>, vote_account: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.validator_registry;
+        let i = registry.validator_index(&vote_account).ok_or(ErrorCode::ValidatorNotFound)?;
+        require!(registry.validators[i].delegated_sol == 0, ErrorCode::InvalidParams);
+        registry.remove_validator_at(i);
 
-    // exposures (simulated)
-    pub staked_sol: u64,
-    pub reserve_sol: u64,
-    pub hedge_notional_usd: i64,
+        emit!(ValidatorUpdated {
+            epoch: ctx.accounts.vault_state.load()?.epoch,
+            slot: Clock::get()?.slot,
+            vote_account,
+            target_weight_bps: 0,
+            cap_sol: 0,
+            total_weight_bps: registry.total_weight_bps() as u16,
+            validator_count: registry.validator_count,
+        });
+        Ok(())
+    }
 
-    // caps / guardrails
-    pub max_staked_sol: u64,
-    pub max_abs_hedge_notional_usd: i64,
-    pub max_hedge_per_sol_usd_fp: i64, // USD per SOL fp 1e6
-    pub min_reserve_bps: u16,
+    /// Keeper: per-validator APY input (bps/year)
+    pub fn update_validator_apy(ctx: Context<KeeperWithRegistry>, vote_account: Pubkey, apy_bps: u16) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.require_not_paused()?;
 
-    // oracle-driven returns buffer
-    pub returns_ring: [i32; N_RETURNS],
-    pub returns_idx: u8,
-    pub nonzero_samples: u16,
-    pub last_return_slot: u64,
-    pub min_samples: u8,
-    pub min_return_spacing_slots: u64,
+        let signer = ctx.accounts.signer.key();
+        state.require_keeper_feeder(&signer)?;
+        state.require_keeper_rate_limit_ok(&signer)?;
+        require!(apy_bps <= BPS_DENOM, ErrorCode::InvalidParams);
 
-    // realized vol model
-    pub vol_mode: u8,
-    pub ewma_alpha_bps: u16,
-    pub ewma_var_fp2: u128,
+        let registry = &mut ctx.accounts.validator_registry;
+        let i = registry.validator_index(&vote_account).ok_or(ErrorCode::ValidatorNotFound)?;
+        registry.validators[i].apy_bps = apy_bps;
 
-    // volatility outputs
-    pub realized_vol_bps: u16,
-    pub implied_vol_bps: u16,
+        let slot = Clock::get()?.slot;
+        state.bump_keeper_heartbeat_and_updates(&signer, slot)?;
+
+        emit!(ValidatorApyUpdated {
+            epoch: state.epoch,
+            slot,
+            vote_account,
+            apy_bps,
+            weighted_apy_bps: registry.weighted_apy_bps(),
+        });
+        Ok(())
+    }
+
+    /// Keeper: when any validator's actual weight drifts from target by >= rebalance_drift_bps,
+    /// emit a move plan (largest overweight -> largest underweight) and apply it to the
+    /// simulated delegation book.
+    pub fn rebalance_delegations(ctx: Context<KeeperWithRegistry>) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.require_not_paused()?;
+
+        let signer = ctx.accounts.signer.key();
+        state.require_keeper_feeder(&signer)?;
+        state.require_keeper_rate_limit_ok(&signer)?;
+
+        let slot = Clock::get()?.slot;
+        let registry = &mut ctx.accounts.validator_registry;
+        let total = registry.total_delegated_sol();
+        let max_drift_bps = registry.max_drift_bps(total);
+
+        let mut move_count: u8 = 0;
+        let mut moved_sol: u64 = 0;
+        if max_drift_bps >= registry.rebalance_drift_bps {
+            for _ in 0..MAX_VALIDATORS {
+                let Some((from, to, amount_sol)) = registry.next_move(total) else {
+                    break;
+                };
+                registry.validators[from].delegated_sol -= amount_sol;
+                registry.validators[to].delegated_sol += amount_sol;
+                move_count += 1;
+                moved_sol = moved_sol.saturating_add(amount_sol);
+
+                emit!(DelegationMove {
+                    epoch: state.epoch,
+                    slot,
+                    from_vote_account: registry.validators[from].vote_account,
+                    to_vote_account: registry.validators[to].vote_account,
+                    amount_sol,
+                });
+            }
+        }
+
+        state.bump_keeper_heartbeat_and_updates(&signer, slot)?;
+
+        emit!(DelegationsRebalanced {
+            epoch: state.epoch,
+            slot,
+            max_drift_bps,
+            rebalance_drift_bps: registry.rebalance_drift_bps,
+            move_count,
+            moved_sol,
+            total_delegated_sol: total,
+            staked_sol: state.staked_sol,
+        });
+        Ok(())
+    }
+
+    /// Authority: create the vault's multi-LST asset book
+    pub fn initialize_asset_book(ctx: Context<InitializeAssetBook>, weighting_mode: u8) -> Result<()> {
+        require!(weighting_mode <= WeightingMode::RiskParity as u8, ErrorCode::InvalidParams);
+        ctx.accounts.vault_state.load_mut()?.has_asset_book = 1;
+
+        let book = &mut ctx.accounts.asset_book;
+        book.vault = ctx.accounts.vault_state.key();
+        book.bump = ctx.bumps.asset_book;
+        book.asset_count = 0;
+        book.weighting_mode = weighting_mode;
+        book.pending_request_id = 0;
+        book.assets = [AssetSlot::default(); MAX_ASSETS];
+        Ok(())
+    }
+
+    /// Authority: register an LST with its own Pyth feed and beta (fp 1e6)
+    pub fn add_asset(ctx: Context<AssetAdmin>, mint: Pubkey, price_feed: Pubkey, lst_beta_fp: i64) -> Result<()> {
+        require!(mint != Pubkey::default() && price_feed != Pubkey::default(), ErrorCode::InvalidParams);
+        require!(lst_beta_fp > 0, ErrorCode::InvalidParams);
+
+        let book = &mut ctx.accounts.asset_book;
+        let n = book.asset_count as usize;
+        require!(n < MAX_ASSETS, ErrorCode::AssetBookFull);
+        require!(book.asset_index(&mint).is_none(), ErrorCode::InvalidParams);
+
+        book.assets[n] = AssetSlot {
+            mint,
+            price_feed,
+            lst_beta_fp,
+            ..AssetSlot::default()
+        };
+        book.asset_count = (n + 1) as u8;
+
+        emit!(AssetAdded {
+            epoch: ctx.accounts.vault_state.load()?.epoch,
+            slot: Clock::get()?.slot,
+            asset_index: n as u8,
+            mint,
+            price_feed,
+            lst_beta_fp,
+        });
+        Ok(())
+    }
+
+    /// Authority: inverse-vol (0) or risk-parity (1) target weights
+    pub fn set_asset_weighting_mode(ctx: Context<AssetAdmin>, weighting_mode: u8) -> Result<()> {
+        require!(weighting_mode <= WeightingMode::RiskParity as u8, ErrorCode::InvalidParams);
+        let book = &mut ctx.accounts.asset_book;
+        book.weighting_mode = weighting_mode;
+
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.bump_config_version_and_hash();
+
+        emit!(AssetWeightingModeSet {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            weighting_mode,
+            config_version: state.config_version,
+            config_hash: state.config_hash,
+        });
+        Ok(())
+    }
+
+    /// Keeper: refresh one asset's price from its Pyth feed (vault oracle gates) and
+    /// update its return ring + realized vol.
+    pub fn update_asset_price(ctx: Context<UpdateAssetPrice>, asset_index: u8) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.require_not_paused()?;
+
+        let signer = ctx.accounts.signer.key();
+        state.require_keeper_feeder(&signer)?;
+        state.require_keeper_rate_limit_ok(&signer)?;
+
+        let book = &mut ctx.accounts.asset_book;
+        let i = asset_index as usize;
+        require!(i < (book.asset_count as usize).min(MAX_ASSETS), ErrorCode::InvalidParams);
+        require_keys_eq!(ctx.accounts.price_feed.key(), book.assets[i].price_feed, ErrorCode::InvalidParams);
+
+        let clock = Clock::get()?;
+        let slot = clock.slot;
+        let (price_fp, _ema_fp, _conf_fp, publish_time, ok, reason) = read_oracle_checked(
+            &PythSource,
+            &ctx.accounts.price_feed,
+            slot,
+            clock.unix_timestamp,
+            state.max_price_age_slots, // interpreted as max_age_seconds here
+            state.max_confidence_bps,
+            state.max_price_jump_bps,
+            book.assets[i].last_price_fp,
+        )?;
+
+        let asset = &mut book.assets[i];
+        asset.price_fp = price_fp;
+        asset.last_publish_time = publish_time;
+        asset.price_ok = ok;
+        if ok {
+            asset.try_record_return(slot, price_fp, publish_time as i64, state.min_return_spacing_slots, state.return_kind)?;
+            if effective_sample_count(&asset.returns_ring) >= (state.min_samples.max(MIN_EFFECTIVE_SAMPLES) as u16) {
+                // per-asset rings carry no EWMA state: Ewma falls back to stdev
+                let mode = if state.vol_mode == VolMode::Mad as u8 { VolMode::Mad as u8 } else { VolMode::Stdev as u8 };
+                asset.realized_vol_bps = compute_realized_vol_bps_mode(mode, &asset.returns_ring, None, 0, publish_time as i64)?;
+            }
+        }
+
+        state.bump_keeper_heartbeat_and_updates(&signer, slot)?;
+
+        emit!(AssetPriceUpdated {
+            epoch: state.epoch,
+            slot,
+            asset_index,
+            mint: asset.mint,
+            price_fp,
+            publish_time,
+            price_ok: ok,
+            reason_code: reason,
+            realized_vol_bps: asset.realized_vol_bps,
+            nonzero_samples: asset.nonzero_samples,
+        });
+        Ok(())
+    }
+
+    /// Authority: fee rates and recipient. Accrues fees at the old rates first.
+    pub fn set_fees(
+        ctx: Context<AuthorityOnly>,
+        management_fee_bps: u16,
+        performance_fee_bps: u16,
+        fee_recipient: Pubkey,
+    ) -> Result<()> {
+        require!(management_fee_bps <= MAX_MANAGEMENT_FEE_BPS, ErrorCode::InvalidParams);
+        require!(performance_fee_bps <= MAX_PERFORMANCE_FEE_BPS, ErrorCode::InvalidParams);
+        require!(fee_recipient != Pubkey::default(), ErrorCode::InvalidParams);
+
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        let clock = Clock::get()?;
+        // settle at the old rates; time without a fresh mark is not charged at the new ones
+        state.accrue_to_now()?;
+        state.last_fee_ts = clock.unix_timestamp;
+
+        state.management_fee_bps = management_fee_bps;
+        state.performance_fee_bps = performance_fee_bps;
+        state.fee_recipient = fee_recipient;
+
+        state.bump_config_version_and_hash();
+
+        emit!(FeesUpdated {
+            epoch: state.epoch,
+            slot: clock.slot,
+            management_fee_bps,
+            performance_fee_bps,
+            fee_recipient,
+            config_version: state.config_version,
+            config_hash: state.config_hash,
+        });
+        Ok(())
+    }
+
+    /// Fee recipient: move accrued fee shares into the recipient's own position.
+    pub fn claim_fee_shares(ctx: Context<UserDeposit>) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        require_keys_eq!(ctx.accounts.owner.key(), state.fee_recipient, ErrorCode::Unauthorized);
+        // fees accrued since the last flow belong in this claim
+        state.accrue_to_now()?;
+
+        let shares = state.fee_shares_unclaimed;
+        require!(shares > 0, ErrorCode::NothingToClaim);
+        state.fee_shares_unclaimed = 0;
+
+        let position = &mut ctx.accounts.user_position;
+        position.shares = position.shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+
+        #[cfg(feature = "token-custody")]
+        share_mint_to(
+            &ctx.accounts.share_mint,
+            &ctx.accounts.user_share_account,
+            &ctx.accounts.custody_authority,
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault_state.key(),
+            state.custody_authority_bump,
+            shares,
+        )?;
+
+        emit!(FeeSharesClaimed {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            fee_recipient: state.fee_recipient,
+            shares,
+            position_shares: position.shares,
+        });
+        Ok(())
+    }
+
+    /// Authority: compound staking yield into staked_sol instead of staking_accrued_usd
+    pub fn set_staking_compound(ctx: Context<AuthorityOnly>, compound_staking_yield: bool) -> Result<()> {
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        // settle what accrued under the old mode first
+        state.accrue_staking_yield_now()?;
+        state.compound_staking_yield = compound_staking_yield as u8;
+
+        state.bump_config_version_and_hash();
+
+        emit!(StakingCompoundUpdated {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            compound_staking_yield,
+            config_version: state.config_version,
+            config_hash: state.config_hash,
+        });
+        Ok(())
+    }
+
+    /// Authority: unstake cooldown (epochs before a ticket can be filled)
+    pub fn set_unstake_cooldown(ctx: Context<AuthorityOnly>, unstake_cooldown_epochs: u64) -> Result<()> {
+        require!(unstake_cooldown_epochs <= MAX_UNSTAKE_COOLDOWN_EPOCHS, ErrorCode::InvalidParams);
+
+        let mut state = ctx.accounts.vault_state.load_mut()?;
+        state.unstake_cooldown_epochs = unstake_cooldown_epochs;
+
+        state.bump_config_version_and_hash();
+
+        emit!(UnstakeCooldownUpdated {
+            epoch: state.epoch,
+            slot: Clock::get()?.slot,
+            unstake_cooldown_epochs,
+            config_version: state.config_version,
+            config_hash: state.config_hash,
+        });
+        Ok(())
+    }
+
+    /// Authority: pin the custody mint, create the vault-owned custody token account and the
+    /// vault share mint (same decimals as the custody mint).
+    /// Only available with the `token-custody` feature; one-time, before any deposits.
+    pub fn initialize_custody(ctx: Context<InitializeCustody>) -> Result<()> {
+        #[cfg(feature = "token-custody")]
+        {
+            let mut state = ctx.accounts.vault_state.load_mut()?;
+            require!(state.custody_mint == Pubkey::default(), ErrorCode::InvalidParams);
+            require!(
+                state.staked_sol == 0 && state.reserve_sol == 0 && state.deactivating_sol == 0,
+                ErrorCode::InvalidParams
+            );
+
+            state.custody_mint = ctx.accounts.custody_mint.key();
+            state.custody_authority_bump = ctx.bumps.custody_authority;
+            state.share_mint = ctx.accounts.share_mint.key();
+            state.bump_config_version_and_hash();
+
+            emit!(CustodyInitialized {
+                epoch: state.epoch,
+                slot: Clock::get()?.slot,
+                custody_mint: state.custody_mint,
+                custody_token_account: ctx.accounts.custody_token_account.key(),
+                share_mint: state.share_mint,
+                config_version: state.config_version,
+                config_hash: state.config_hash,
+            });
+            Ok(())
+        }
+        #[cfg(not(feature = "token-custody"))]
+        {
+            let _ = ctx;
+            err!(ErrorCode::CustodyDisabled)
+        }
+    }
+}
+
+// -------------------------------
+// Accounts
+// -------------------------------
+
+#[derive(Accounts)]
+#[instruction(params: InitializeParams)]
+pub struct InitializeVault<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = VaultState::SPACE,
+        seeds = [b"vault", authority.key().as_ref(), params.vault_index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    /// Optional: list the new vault in the global registry
+    #[account(mut, seeds = [b"vault_registry"], bump = vault_registry.bump)]
+    pub vault_registry: Option<Box<Account<'info, VaultRegistry>>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Create the global vault registry (permissionless, once)
+#[derive(Accounts)]
+pub struct InitializeVaultRegistry<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(init, payer = payer, space = VaultRegistry::SPACE, seeds = [b"vault_registry"], bump)]
+    pub vault_registry: Box<Account<'info, VaultRegistry>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Upsert a vault's entry (vault authority only, so slots can't be filled by third parties)
+#[derive(Accounts)]
+pub struct SyncVaultRegistry<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(mut, seeds = [b"vault_registry"], bump = vault_registry.bump)]
+    pub vault_registry: Box<Account<'info, VaultRegistry>>,
+}
+
+/// Vault authority: remove its vault's entry
+#[derive(Accounts)]
+pub struct DeregisterVault<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(mut, seeds = [b"vault_registry"], bump = vault_registry.bump)]
+    pub vault_registry: Box<Account<'info, VaultRegistry>>,
+}
+
+/// Upgrade a vault account to the current layout (authority pays any extra rent)
+#[derive(Accounts)]
+pub struct MigrateVault<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: may predate the current layout (too short for AccountLoader::load);
+    /// discriminator + authority are checked from the raw header in the handler.
+    #[account(mut, owner = crate::ID)]
+    pub vault_state: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Permissionless hedge request (asset book required once created)
+#[derive(Accounts)]
+pub struct RequestHedge<'info> {
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"asset_book", vault_state.key().as_ref()],
+        bump = asset_book.bump
+    )]
+    pub asset_book: Option<Box<Account<'info, AssetBook>>>,
+}
+
+/// Open a per-depositor position PDA
+#[derive(Accounts)]
+pub struct OpenUserPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = UserPosition::SPACE,
+        seeds = [b"position", vault_state.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Depositor context (vault + owner's position)
+#[cfg(not(feature = "token-custody"))]
+#[derive(Accounts)]
+pub struct UserDeposit<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"position", vault_state.key().as_ref(), owner.key().as_ref()],
+        bump = user_position.bump,
+        has_one = owner
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    #[account(
+        mut,
+        seeds = [b"validator_registry", vault_state.key().as_ref()],
+        bump = validator_registry.bump
+    )]
+    pub validator_registry: Option<Box<Account<'info, ValidatorRegistry>>>,
+}
+
+/// Depositor context (vault + owner's position + token custody)
+#[cfg(feature = "token-custody")]
+#[derive(Accounts)]
+pub struct UserDeposit<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"position", vault_state.key().as_ref(), owner.key().as_ref()],
+        bump = user_position.bump,
+        has_one = owner
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    #[account(
+        mut,
+        seeds = [b"validator_registry", vault_state.key().as_ref()],
+        bump = validator_registry.bump
+    )]
+    pub validator_registry: Option<Box<Account<'info, ValidatorRegistry>>>,
+
+    #[account(mut, token::mint = vault_state.load()?.custody_mint, token::authority = owner)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut, address = vault_state.load()?.share_mint)]
+    pub share_mint: Account<'info, Mint>,
+
+    #[account(mut, token::mint = vault_state.load()?.share_mint, token::authority = owner)]
+    pub user_share_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"custody", vault_state.key().as_ref()],
+        bump,
+        token::mint = vault_state.load()?.custody_mint
+    )]
+    pub custody_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: PDA signer for the custody token account
+    #[account(seeds = [b"custody_authority", vault_state.key().as_ref()], bump = vault_state.load()?.custody_authority_bump)]
+    pub custody_authority: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Reserve deposit (simulated: permissionless counter bump)
+#[cfg(not(feature = "token-custody"))]
+#[derive(Accounts)]
+pub struct ReserveDeposit<'info> {
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+}
+
+/// Reserve deposit (token custody: depositor funds the reserve)
+#[cfg(feature = "token-custody")]
+#[derive(Accounts)]
+pub struct ReserveDeposit<'info> {
+    pub depositor: Signer<'info>,
+
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(mut, token::mint = vault_state.load()?.custody_mint, token::authority = depositor)]
+    pub depositor_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"custody", vault_state.key().as_ref()],
+        bump,
+        token::mint = vault_state.load()?.custody_mint
+    )]
+    pub custody_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Reserve withdrawal (authority-only)
+#[cfg(not(feature = "token-custody"))]
+#[derive(Accounts)]
+pub struct ReserveWithdraw<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+}
+
+/// Reserve withdrawal (authority-only, token custody)
+#[cfg(feature = "token-custody")]
+#[derive(Accounts)]
+pub struct ReserveWithdraw<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(mut, token::mint = vault_state.load()?.custody_mint)]
+    pub destination_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"custody", vault_state.key().as_ref()],
+        bump,
+        token::mint = vault_state.load()?.custody_mint
+    )]
+    pub custody_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: PDA signer for the custody token account
+    #[account(seeds = [b"custody_authority", vault_state.key().as_ref()], bump = vault_state.load()?.custody_authority_bump)]
+    pub custody_authority: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Authority: set up token custody (no-op context in the simulated build)
+#[cfg(not(feature = "token-custody"))]
+#[derive(Accounts)]
+pub struct InitializeCustody<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+}
+
+/// Authority: set up token custody (pins mint, creates custody token account PDA)
+#[cfg(feature = "token-custody")]
+#[derive(Accounts)]
+pub struct InitializeCustody<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    pub custody_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"custody", vault_state.key().as_ref()],
+        bump,
+        token::mint = custody_mint,
+        token::authority = custody_authority
+    )]
+    pub custody_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"share_mint", vault_state.key().as_ref()],
+        bump,
+        mint::decimals = custody_mint.decimals,
+        mint::authority = custody_authority
+    )]
+    pub share_mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: PDA signer for the custody token account
+    #[account(seeds = [b"custody_authority", vault_state.key().as_ref()], bump)]
+    pub custody_authority: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Authority: create the vault's returns ring (space depends on capacity)
+#[derive(Accounts)]
+#[instruction(capacity: u16)]
+pub struct InitializeReturnsRing<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = ReturnsRing::space(capacity),
+        seeds = [b"returns_ring", vault_state.key().as_ref()],
+        bump
+    )]
+    pub returns_ring: Box<Account<'info, ReturnsRing>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Authority: create the vault's manual price account
+#[derive(Accounts)]
+pub struct InitializeManualPriceFeed<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = ManualPriceFeed::SPACE,
+        seeds = [b"manual_price", vault_state.key().as_ref()],
+        bump
+    )]
+    pub manual_price: Box<Account<'info, ManualPriceFeed>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Authority: publish a manual price
+#[derive(Accounts)]
+pub struct SetManualPrice<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(mut, seeds = [b"manual_price", vault_state.key().as_ref()], bump = manual_price.bump)]
+    pub manual_price: Box<Account<'info, ManualPriceFeed>>,
+}
+
+/// Authority: create the vault's OHLC bar account (space depends on capacity)
+#[derive(Accounts)]
+#[instruction(capacity: u16)]
+pub struct InitializePriceBars<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = PriceBars::space(capacity),
+        seeds = [b"price_bars", vault_state.key().as_ref()],
+        bump
+    )]
+    pub price_bars: Box<Account<'info, PriceBars>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Authority: create the vault's unstake queue
+#[derive(Accounts)]
+pub struct InitializeWithdrawQueue<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = WithdrawQueue::SPACE,
+        seeds = [b"withdraw_queue", vault_state.key().as_ref()],
+        bump
+    )]
+    pub withdraw_queue: Box<Account<'info, WithdrawQueue>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Depositor unstake (position + queue)
+#[cfg(not(feature = "token-custody"))]
+#[derive(Accounts)]
+pub struct UserUnstake<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"position", vault_state.key().as_ref(), owner.key().as_ref()],
+        bump = user_position.bump,
+        has_one = owner
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    #[account(
+        mut,
+        seeds = [b"validator_registry", vault_state.key().as_ref()],
+        bump = validator_registry.bump
+    )]
+    pub validator_registry: Option<Box<Account<'info, ValidatorRegistry>>>,
+
+    #[account(
+        mut,
+        seeds = [b"withdraw_queue", vault_state.key().as_ref()],
+        bump = withdraw_queue.bump
+    )]
+    pub withdraw_queue: Box<Account<'info, WithdrawQueue>>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = UnstakeTicket::SPACE,
+        seeds = [b"unstake_ticket", vault_state.key().as_ref(), &withdraw_queue.next_ticket_id.to_le_bytes()],
+        bump
+    )]
+    pub ticket: Box<Account<'info, UnstakeTicket>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Depositor unstake (position + queue + share token burn)
+#[cfg(feature = "token-custody")]
+#[derive(Accounts)]
+pub struct UserUnstake<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"position", vault_state.key().as_ref(), owner.key().as_ref()],
+        bump = user_position.bump,
+        has_one = owner
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    #[account(
+        mut,
+        seeds = [b"validator_registry", vault_state.key().as_ref()],
+        bump = validator_registry.bump
+    )]
+    pub validator_registry: Option<Box<Account<'info, ValidatorRegistry>>>,
+
+    #[account(
+        mut,
+        seeds = [b"withdraw_queue", vault_state.key().as_ref()],
+        bump = withdraw_queue.bump
+    )]
+    pub withdraw_queue: Box<Account<'info, WithdrawQueue>>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = UnstakeTicket::SPACE,
+        seeds = [b"unstake_ticket", vault_state.key().as_ref(), &withdraw_queue.next_ticket_id.to_le_bytes()],
+        bump
+    )]
+    pub ticket: Box<Account<'info, UnstakeTicket>>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, address = vault_state.load()?.share_mint)]
+    pub share_mint: Account<'info, Mint>,
+
+    #[account(mut, token::mint = vault_state.load()?.share_mint, token::authority = owner)]
+    pub user_share_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Permissionless: mature one ticket
+#[derive(Accounts)]
+pub struct MatureUnstakeTicket<'info> {
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(seeds = [b"withdraw_queue", vault_state.key().as_ref()], bump = withdraw_queue.bump)]
+    pub withdraw_queue: Box<Account<'info, WithdrawQueue>>,
+
+    #[account(
+        mut,
+        seeds = [b"unstake_ticket", vault_state.key().as_ref(), &ticket.id.to_le_bytes()],
+        bump = ticket.bump
+    )]
+    pub ticket: Box<Account<'info, UnstakeTicket>>,
+}
+
+/// Permissionless queue crank
+#[derive(Accounts)]
+pub struct WithdrawQueueCrank<'info> {
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"withdraw_queue", vault_state.key().as_ref()],
+        bump = withdraw_queue.bump
+    )]
+    pub withdraw_queue: Box<Account<'info, WithdrawQueue>>,
+}
+
+/// Ticket owner claims filled SOL
+#[cfg(not(feature = "token-custody"))]
+#[derive(Accounts)]
+pub struct ClaimUnstake<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"withdraw_queue", vault_state.key().as_ref()],
+        bump = withdraw_queue.bump
+    )]
+    pub withdraw_queue: Box<Account<'info, WithdrawQueue>>,
+
+    #[account(
+        mut,
+        seeds = [b"unstake_ticket", vault_state.key().as_ref(), &ticket.id.to_le_bytes()],
+        bump = ticket.bump,
+        has_one = owner
+    )]
+    pub ticket: Box<Account<'info, UnstakeTicket>>,
+}
+
+/// Ticket owner claims filled SOL (token custody)
+#[cfg(feature = "token-custody")]
+#[derive(Accounts)]
+pub struct ClaimUnstake<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"withdraw_queue", vault_state.key().as_ref()],
+        bump = withdraw_queue.bump
+    )]
+    pub withdraw_queue: Box<Account<'info, WithdrawQueue>>,
+
+    #[account(
+        mut,
+        seeds = [b"unstake_ticket", vault_state.key().as_ref(), &ticket.id.to_le_bytes()],
+        bump = ticket.bump,
+        has_one = owner
+    )]
+    pub ticket: Box<Account<'info, UnstakeTicket>>,
+
+    #[account(mut, token::mint = vault_state.load()?.custody_mint, token::authority = owner)]
+    pub owner_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"custody", vault_state.key().as_ref()],
+        bump,
+        token::mint = vault_state.load()?.custody_mint
+    )]
+    pub custody_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: PDA signer for the custody token account
+    #[account(seeds = [b"custody_authority", vault_state.key().as_ref()], bump = vault_state.load()?.custody_authority_bump)]
+    pub custody_authority: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Authority: create the validator delegation book
+#[derive(Accounts)]
+pub struct InitializeValidatorRegistry<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut, has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = ValidatorRegistry::SPACE,
+        seeds = [b"validator_registry", vault_state.key().as_ref()],
+        bump
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Authority: manage validators
+#[derive(Accounts)]
+pub struct ValidatorAdmin<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"validator_registry", vault_state.key().as_ref()],
+        bump = validator_registry.bump
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+}
+
+/// Keeper + validator registry
+#[derive(Accounts)]
+pub struct KeeperWithRegistry<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"validator_registry", vault_state.key().as_ref()],
+        bump = validator_registry.bump
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+}
+
+/// Keeper hedge confirm (asset book required once created: books the rebalance intent of the request)
+#[derive(Accounts)]
+pub struct ConfirmHedge<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"asset_book", vault_state.key().as_ref()],
+        bump = asset_book.bump
+    )]
+    pub asset_book: Option<Box<Account<'info, AssetBook>>>,
+}
+
+/// Authority: create the multi-LST asset book
+#[derive(Accounts)]
+pub struct InitializeAssetBook<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut, has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = AssetBook::SPACE,
+        seeds = [b"asset_book", vault_state.key().as_ref()],
+        bump
+    )]
+    pub asset_book: Box<Account<'info, AssetBook>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Authority: manage assets
+#[derive(Accounts)]
+pub struct AssetAdmin<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut, has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"asset_book", vault_state.key().as_ref()],
+        bump = asset_book.bump
+    )]
+    pub asset_book: Box<Account<'info, AssetBook>>,
+}
+
+/// Keeper: per-asset price update
+#[derive(Accounts)]
+pub struct UpdateAssetPrice<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"asset_book", vault_state.key().as_ref()],
+        bump = asset_book.bump
+    )]
+    pub asset_book: Box<Account<'info, AssetBook>>,
+
+    /// CHECK: Pyth price account; must match the asset slot's price_feed
+    pub price_feed: AccountInfo<'info>,
+}
+
+/// Keeper/authority slashing (registry attributes the loss to one validator; required once created)
+#[derive(Accounts)]
+pub struct ApplySlashing<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"validator_registry", vault_state.key().as_ref()],
+        bump = validator_registry.bump
+    )]
+    pub validator_registry: Option<Box<Account<'info, ValidatorRegistry>>>,
+}
+
+/// Keeper context
+#[derive(Accounts)]
+pub struct KeeperWithVault<'info> {
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+}
+
+/// Keeper epoch/policy update (reads the returns ring)
+#[derive(Accounts)]
+pub struct UpdateEpochAndPolicy<'info> {
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(seeds = [b"returns_ring", vault_state.key().as_ref()], bump = returns_ring.bump)]
+    pub returns_ring: Box<Account<'info, ReturnsRing>>,
+
+    /// Optional (required by range-based vol modes)
+    #[account(seeds = [b"price_bars", vault_state.key().as_ref()], bump = price_bars.bump)]
+    pub price_bars: Option<Box<Account<'info, PriceBars>>>,
+}
+
+/// Update oracle price (requires signer + two pyth accounts)
+#[derive(Accounts)]
+pub struct UpdateOraclePrice<'info> {
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(mut, seeds = [b"returns_ring", vault_state.key().as_ref()], bump = returns_ring.bump)]
+    pub returns_ring: Box<Account<'info, ReturnsRing>>,
+
+    /// Fold the accepted price into the open OHLC bar (required in range vol modes)
+    #[account(mut, seeds = [b"price_bars", vault_state.key().as_ref()], bump = price_bars.bump)]
+    pub price_bars: Option<Box<Account<'info, PriceBars>>>,
+
+    /// CHECK: Pyth SOL/USD price account, pinned by set_oracle_accounts
+    #[account(address = vault_state.load()?.oracle_sol_usd)]
+    pub pyth_sol_usd: AccountInfo<'info>,
+    /// CHECK: Pyth SOL/USDC price account, pinned by set_oracle_accounts
+    #[account(address = vault_state.load()?.oracle_sol_usdc)]
+    pub pyth_sol_usdc: AccountInfo<'info>,
+
+    /// CHECK: Switchboard pull feed, pinned by set_oracle_accounts (owner + layout checked by SwitchboardSource)
+    #[account(address = vault_state.load()?.oracle_switchboard_feed)]
+    pub switchboard_feed: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Pyth USDC/USD price account, pinned by set_usdc_cross_rate; required while one is pinned
+    #[account(address = vault_state.load()?.oracle_usdc_usd)]
+    pub pyth_usdc_usd: Option<UncheckedAccount<'info>>,
+
+    /// Optional: authority-set price, read when oracle_feed_choice = Manual
+    #[account(seeds = [b"manual_price", vault_state.key().as_ref()], bump = manual_price.bump)]
+    pub manual_price: Option<Box<Account<'info, ManualPriceFeed>>>,
+}
+
+/// Authority-only
+#[derive(Accounts)]
+pub struct AuthorityOnly<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+}
+
+/// Authority: vol model (window is checked against the ring capacity)
+#[derive(Accounts)]
+pub struct SetVolModel<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+
+    #[account(seeds = [b"returns_ring", vault_state.key().as_ref()], bump = returns_ring.bump)]
+    pub returns_ring: Box<Account<'info, ReturnsRing>>,
+
+    /// Required to select a range-based mode (initialize_price_bars first)
+    #[account(seeds = [b"price_bars", vault_state.key().as_ref()], bump = price_bars.bump)]
+    pub price_bars: Option<Box<Account<'info, PriceBars>>>,
+}
+
+/// Keeper-admin-only
+#[derive(Accounts)]
+pub struct KeeperAdminOnly<'info> {
+    pub keeper_admin: Signer<'info>,
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+}
+
+/// Accept authority
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    pub pending_authority: Signer<'info>,
+    #[account(mut)]
+    pub vault_state: AccountLoader<'info, VaultState>,
+}
+
+// -------------------------------
+// State
+// -------------------------------
+
+/// Zero-copy vault account. Layout is `repr(C)` with explicit padding so every
+/// group starts 8-byte aligned; new fields are carved out of `_reserved` (or
+/// appended with a `layout_version` bump and a `migrate_vault` step).
+/// `authority` and `layout_version` never move: `migrate_vault` reads them
+/// straight from the account data before the account is resized.
+#[account(zero_copy)]
+pub struct VaultState {
+    // roles + layout header (fixed offsets)
+    pub authority: Pubkey,
+    pub layout_version: u8,
+    pub vault_bump: u8,
+    pub vault_index: u16,
+    pub custody_authority_bump: u8,
+
+    // safety toggles (0/1)
+    pub paused: u8,
+    pub emergency_withdraw_enabled: u8,
+    pub _pad0: [u8; 1],
+
+    pub pending_authority: Pubkey,
+    pub keeper_admin: Pubkey,
+
+    // vault identity (label; not part of config_hash so variants compare by config)
+    pub name: [u8; VAULT_NAME_LEN],
+
+    // config identity
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+
+    // epoch + cooldown
+    pub epoch: u64,
+    pub last_policy_update_slot: u64,
+
+    // exposures (simulated)
+    pub staked_sol: u64,
+    pub reserve_sol: u64,
+    pub hedge_notional_usd: i64,
+
+    // depositor shares (sum of UserPosition.shares)
+    pub total_shares: u64,
+
+    // unstake queue (SOL owed to tickets; outside NAV)
+    pub unstake_cooldown_epochs: u64,
+    pub deactivating_sol: u64,
+    pub withdraw_owed_sol: u64,
+
+    // slashing (simulated)
+    pub total_slashed_sol: u64,
+
+    // token custody (token-custody feature; default Pubkey when simulated)
+    pub custody_mint: Pubkey,
+    pub share_mint: Pubkey,
+
+    // caps / guardrails
+    pub max_staked_sol: u64,
+    pub max_abs_hedge_notional_usd: i64,
+    pub max_hedge_per_sol_usd_fp: i64, // USD per SOL fp 1e6
+    pub min_reserve_bps: u16,
+    pub _pad1: [u8; 6],
+
+    // oracle-driven returns buffer
+    pub last_return_slot: u64,
+    pub min_return_spacing_slots: u64,
+    pub _retired_returns: [u8; 4 * N_RETURNS + 3], // v1 in-account ring; samples now live in ReturnsRing
+    pub min_samples: u8,
+    pub _pad2: [u8; 4],
+
+    // realized vol model
+    pub ewma_var_fp2: u64, // annualized variance (fp^2), clamped to MAX_VAR_FP2
+    pub ewma_alpha_bps: u16,
+    pub vol_mode: u8,
+    pub return_kind: u8, // ReturnKind
+
+    // volatility outputs
+    pub realized_vol_bps: u16,
+    pub implied_vol_bps: u16,
+    pub vol_score_bps: u16,
+    pub last_vol_score_bps: u16,
+
+    // score weights
+    pub vol_weight_realized_bps: u16,
+    pub vol_weight_implied_bps: u16,
+
+    // policy bounds
+    pub min_interval_slots: u64,
+    pub max_interval_slots: u64,
+    pub min_band_bps: u16,
+    pub max_band_bps: u16,
+
+    // policy outputs
+    pub band_bps: u16,
+    pub _pad4: [u8; 2],
+    pub min_hedge_interval_slots: u64,
+
+    // stability knobs
+    pub policy_update_min_slots: u64,
+    pub max_policy_slew_bps: u16,
+    pub hysteresis_bps: u16,
+    pub _pad5: [u8; 4],
+
+    // oracle config (NOTE: max_price_age_slots interpreted as seconds in this impl)
+    pub max_price_age_slots: u64,
+    pub max_confidence_bps: u16,
+    pub max_price_jump_bps: u16,
+    pub oracle_feed_choice: u8,
+    pub _pad6: [u8; 3],
+
+    // oracle last observation
+    pub oracle_price_fp: i64,
+    pub oracle_ema_price_fp: i64,
+    pub oracle_conf_fp: i64,
+    pub oracle_publish_slot: u64, // actually publish_time seconds (unix) in this impl
+    pub last_oracle_price_fp: i64,
+    pub last_oracle_ema_price_fp: i64,
+    pub oracle_ok: u8,
+
+    // circuit breaker
+    pub oracle_degraded: u8,
+    pub extreme_drift_bps: u16,
+
+    // hedge sizing knobs
+    pub target_delta_bps: u16,
+    pub _pad7: [u8; 2],
+    pub lst_beta_fp: i64,
+
+    // carry inputs (bps/day)
+    pub funding_bps_per_day: i32,
+    pub borrow_bps_per_day: i32,
+    pub staking_bps_per_day: i32,
+
+    // staking accrual (simulated)
+    pub compound_staking_yield: u8, // 1: yield grows staked_sol instead of staking_accrued_usd
+    pub _pad8: [u8; 3],
+    pub staking_accrued_usd: i64,
+    pub last_accrual_ts: i64,
+
+    // hedge carry accrual (funding on the signed notional, borrow on |hedge_notional_usd|)
+    pub hedge_carry_accrued_usd: i64,
+    pub carry_accrued_epoch_usd: i64, // reset each epoch after CarryAccrued
+    pub last_carry_accrual_ts: i64,
+
+    // fees (paid as newly minted shares, held until the recipient claims)
+    pub fee_recipient: Pubkey,
+    pub high_water_mark_fp: i64, // NAV per share, fp 1e6
+    pub fee_shares_unclaimed: u64,
+    pub last_fee_ts: i64,
+    pub management_fee_bps: u16,  // per year, on NAV
+    pub performance_fee_bps: u16, // on NAV/share gains above high_water_mark_fp
+    pub _pad9: [u8; 4],
+
+    // hedge timing + anchors
+    pub last_hedge_slot: u64,
+    pub last_hedge_ema_price_fp: i64,
+
+    // hedge request/confirm
+    pub last_hedge_request_slot: u64,
+    pub last_hedge_request_id: u64,
+    pub last_fill_slot: u64,
+    pub hedge_fill_count: u64,
+    pub hedge_entry_price_fp: i64,   // avg entry of the open hedge (fp 1e6); 0 when flat
+    pub hedge_realized_pnl_usd: i64, // cumulative PnL booked on reduces/flips
+    pub max_confirm_delay_slots: u64,
+    pub missed_confirms: u32,
+    pub avg_fill_slippage_bps: u16,
+    pub request_outstanding: u8,
+    pub _pad10: [u8; 1],
+
+    // keepers
+    pub keepers: [Pubkey; MAX_KEEPERS],
+    pub keeper_heartbeat_slot: [u64; MAX_KEEPERS],
+    pub keeper_miss_count: [u32; MAX_KEEPERS],
+
+    // keeper controls
+    pub keeper_bond_required_lamports: u64,
+    pub keeper_bond_deposited_lamports: [u64; MAX_KEEPERS],
+    pub keeper_updates_this_epoch: [u16; MAX_KEEPERS],
+    pub max_updates_per_epoch: u16,
+    pub keeper_count: u8,
+    pub _pad11: [u8; 5],
+
+    // returns window (newest samples of ReturnsRing fed to the estimators)
+    pub returns_window: u16,
+    pub _pad12: [u8; 6],
+
+    // anchor of the next return: price + publish time of the last recorded sample
+    // (0 after migrate: the next accepted price only re-anchors)
+    pub last_return_price_fp: i64,
+    pub last_return_ts: i64,
+
+    // GARCH(1,1): h' = omega + alpha * r^2/dt + beta * h (annualized variance, fp^2)
+    pub garch_omega_fp2: u64,
+    pub garch_var_fp2: u64, // conditional variance, clamped to MAX_VAR_FP2
+    pub garch_alpha_bps: u16,
+    pub garch_beta_bps: u16,
+    pub vol_forecast_bps: u16, // h-step forecast (Garch mode only)
+    pub _pad13: [u8; 2],
+
+    // median oracle aggregation (OracleFeedChoice::Median)
+    pub oracle_feed_mask: u8, // ORACLE_FEED_BIT_*
+    pub oracle_min_feeds: u8, // quorum of feeds passing the gates
+    pub oracle_max_spread_bps: u16,
+    pub _pad14: [u8; 4],
+
+    // Pyth pull oracle: pinned feed ids for the two Pyth slots
+    pub pyth_sol_usd_feed_id: [u8; 32],
+    pub pyth_sol_usdc_feed_id: [u8; 32],
+    pub pyth_pull_enabled: u8,
+    pub pyth_pull_min_verification: u8, // PullVerification
+    pub pyth_pull_min_signatures: u8,   // Partial only
+    pub _pad15: [u8; 5],
+
+    // pinned oracle accounts (UpdateOraclePrice `address =` constraints); unset = no updates
+    pub oracle_sol_usd: Pubkey,
+    pub oracle_sol_usdc: Pubkey,
+    pub oracle_switchboard_feed: Pubkey,
+
+    // USDC/USD cross-rate for the SOL/USDC leg; unset oracle_usdc_usd = 1:1
+    pub oracle_usdc_usd: Pubkey,
+    pub pyth_usdc_usd_feed_id: [u8; 32], // pull mode
+    pub max_usdc_depeg_bps: u16,
+    pub _pad16: [u8; 6],
+
+    // shares minted against NAV that predated the first deposit (locked like DEAD_SHARES)
+    pub locked_shares: u64,
+
+    // validator registry: once created it must be passed to every flow that moves stake
+    pub has_validator_registry: u8,
+    pub has_asset_book: u8, // same rule for the multi-LST asset book and hedge requests
+    pub _pad17: [u8; 6],
+    pub registry_pending_yield_sol: u64, // compounded yield not yet credited to validators
+
+    // room for later layout versions
+    pub _reserved: [u8; VAULT_RESERVED_LEN],
+}
+
+// A field added without updating SPACE (or that introduces implicit padding) fails the build here.
+const _: () = assert!(VaultState::SPACE == 8 + core::mem::size_of::<VaultState>());
+const _: () = assert!(core::mem::size_of::<VaultState>() & 7 == 0);
+
+/// Pre-zero-copy (Borsh) `VaultState`, frozen as deployed before layout versioning.
+/// Only `migrate_vault` reads it: the account has the same discriminator but no
+/// `layout_version` (byte 40 is `pending_authority[0]`), so it is told apart by length.
+#[derive(AnchorDeserialize)]
+pub struct LegacyVaultState {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub keeper_admin: Pubkey,
+    pub vault_bump: u8,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+    pub epoch: u64,
+    pub last_policy_update_slot: u64,
+    pub staked_sol: u64,
+    pub reserve_sol: u64,
+    pub hedge_notional_usd: i64,
+    pub max_staked_sol: u64,
+    pub max_abs_hedge_notional_usd: i64,
+    pub max_hedge_per_sol_usd_fp: i64,
+    pub min_reserve_bps: u16,
+    pub returns_ring: [i32; LEGACY_N_RETURNS],
+    pub returns_idx: u8,
+    pub nonzero_samples: u16,
+    pub last_return_slot: u64,
+    pub min_samples: u8,
+    pub min_return_spacing_slots: u64,
+    pub vol_mode: u8,
+    pub ewma_alpha_bps: u16,
+    pub ewma_var_fp2: u128,
+    pub realized_vol_bps: u16,
+    pub implied_vol_bps: u16,
     pub vol_score_bps: u16,
     pub last_vol_score_bps: u16,
+    pub vol_weight_realized_bps: u16,
+    pub vol_weight_implied_bps: u16,
+    pub min_band_bps: u16,
+    pub max_band_bps: u16,
+    pub min_interval_slots: u64,
+    pub max_interval_slots: u64,
+    pub band_bps: u16,
+    pub min_hedge_interval_slots: u64,
+    pub policy_update_min_slots: u64,
+    pub max_policy_slew_bps: u16,
+    pub hysteresis_bps: u16,
+    pub oracle_feed_choice: u8,
+    pub max_price_age_slots: u64,
+    pub max_confidence_bps: u16,
+    pub max_price_jump_bps: u16,
+    pub oracle_price_fp: i64,
+    pub oracle_ema_price_fp: i64,
+    pub oracle_conf_fp: i64,
+    pub oracle_publish_slot: u64,
+    pub oracle_ok: bool,
+    pub last_oracle_price_fp: i64,
+    pub last_oracle_ema_price_fp: i64,
+    pub oracle_degraded: bool,
+    pub extreme_drift_bps: u16,
+    pub target_delta_bps: u16,
+    pub lst_beta_fp: i64,
+    pub funding_bps_per_day: i32,
+    pub borrow_bps_per_day: i32,
+    pub staking_bps_per_day: i32,
+    pub staking_accrued_usd: i64,
+    pub last_hedge_slot: u64,
+    pub last_hedge_ema_price_fp: i64,
+    pub last_hedge_request_slot: u64,
+    pub last_hedge_request_id: u64,
+    pub request_outstanding: bool,
+    pub last_fill_slot: u64,
+    pub hedge_fill_count: u64,
+    pub avg_fill_slippage_bps: u16,
+    pub missed_confirms: u32,
+    pub max_confirm_delay_slots: u64,
+    pub paused: bool,
+    pub emergency_withdraw_enabled: bool,
+    pub keepers: [Pubkey; MAX_KEEPERS],
+    pub keeper_count: u8,
+    pub keeper_heartbeat_slot: [u64; MAX_KEEPERS],
+    pub keeper_miss_count: [u32; MAX_KEEPERS],
+    pub max_updates_per_epoch: u16,
+    pub keeper_updates_this_epoch: [u16; MAX_KEEPERS],
+    pub keeper_bond_required_lamports: u64,
+    pub keeper_bond_deposited_lamports: [u64; MAX_KEEPERS],
+}
+
+impl LegacyVaultState {
+    /// Account length as allocated by the legacy `initialize_vault` (discriminator + Borsh).
+    pub const SPACE: usize = 8
+        + (32 * 3 + 1) // roles, vault_bump
+        + (8 + 32) // config identity
+        + (8 + 8) // epoch, last_policy_update_slot
+        + (8 + 8 + 8) // exposures
+        + (8 + 8 + 8 + 2) // caps
+        + (4 * LEGACY_N_RETURNS + 1 + 2 + 8 + 1 + 8) // in-account returns ring
+        + (1 + 2 + 16) // vol model
+        + (2 * 4) // vol outputs
+        + (2 * 2) // score weights
+        + (2 + 2 + 8 + 8) // policy bounds
+        + (2 + 8) // policy outputs
+        + (8 + 2 + 2) // stability knobs
+        + (1 + 8 + 2 + 2) // oracle config
+        + (8 * 4 + 1 + 8 + 8) // oracle observation
+        + (1 + 2) // circuit breaker
+        + (2 + 8) // hedge sizing
+        + (4 * 3) // carry inputs
+        + 8 // staking_accrued_usd
+        + (8 + 8) // hedge anchors
+        + (8 + 8 + 1 + 8 + 8 + 2 + 4 + 8) // hedge request/confirm
+        + (1 + 1) // safety toggles
+        + (32 * MAX_KEEPERS + 1 + 8 * MAX_KEEPERS + 4 * MAX_KEEPERS) // keepers
+        + (2 + 2 * MAX_KEEPERS + 8 + 8 * MAX_KEEPERS); // keeper controls
+
+    /// Copy every field that survived into the zero-copy layout; `state` must be zeroed.
+    /// The in-account ring and its EWMA variance are dropped: those samples are 1e6-scaled
+    /// returns, while ReturnsRing (and the asset-book rings) only ever hold RET_FP_SCALE
+    /// (1e9) samples, so nothing at the old scale survives the migration.
+    pub fn upgrade_into(&self, state: &mut VaultState, now_ts: i64) {
+        state.authority = self.authority;
+        state.pending_authority = self.pending_authority;
+        state.keeper_admin = self.keeper_admin;
+        state.vault_bump = self.vault_bump;
+
+        state.config_version = self.config_version;
+        state.config_hash = self.config_hash;
+        state.epoch = self.epoch;
+        state.last_policy_update_slot = self.last_policy_update_slot;
+
+        state.staked_sol = self.staked_sol;
+        state.reserve_sol = self.reserve_sol;
+        state.hedge_notional_usd = self.hedge_notional_usd;
+
+        state.max_staked_sol = self.max_staked_sol;
+        state.max_abs_hedge_notional_usd = self.max_abs_hedge_notional_usd;
+        state.max_hedge_per_sol_usd_fp = self.max_hedge_per_sol_usd_fp;
+        state.min_reserve_bps = self.min_reserve_bps;
+
+        state.last_return_slot = self.last_return_slot;
+        state.min_samples = self.min_samples;
+        state.min_return_spacing_slots = self.min_return_spacing_slots;
+
+        state.vol_mode = self.vol_mode;
+        state.ewma_alpha_bps = self.ewma_alpha_bps;
+        state.return_kind = ReturnKind::Simple as u8;
+        state.realized_vol_bps = self.realized_vol_bps;
+        state.implied_vol_bps = self.implied_vol_bps;
+        state.vol_score_bps = self.vol_score_bps;
+        state.last_vol_score_bps = self.last_vol_score_bps;
+        state.vol_weight_realized_bps = self.vol_weight_realized_bps;
+        state.vol_weight_implied_bps = self.vol_weight_implied_bps;
+
+        state.min_band_bps = self.min_band_bps;
+        state.max_band_bps = self.max_band_bps;
+        state.min_interval_slots = self.min_interval_slots;
+        state.max_interval_slots = self.max_interval_slots;
+        state.band_bps = self.band_bps;
+        state.min_hedge_interval_slots = self.min_hedge_interval_slots;
+
+        state.policy_update_min_slots = self.policy_update_min_slots;
+        state.max_policy_slew_bps = self.max_policy_slew_bps;
+        state.hysteresis_bps = self.hysteresis_bps;
+
+        state.oracle_feed_choice = self.oracle_feed_choice;
+        state.max_price_age_slots = self.max_price_age_slots;
+        state.max_confidence_bps = self.max_confidence_bps;
+        state.max_price_jump_bps = self.max_price_jump_bps;
+        state.oracle_price_fp = self.oracle_price_fp;
+        state.oracle_ema_price_fp = self.oracle_ema_price_fp;
+        state.oracle_conf_fp = self.oracle_conf_fp;
+        state.oracle_publish_slot = self.oracle_publish_slot;
+        state.oracle_ok = self.oracle_ok as u8;
+        state.last_oracle_price_fp = self.last_oracle_price_fp;
+        state.last_oracle_ema_price_fp = self.last_oracle_ema_price_fp;
+        state.oracle_degraded = self.oracle_degraded as u8;
+        state.extreme_drift_bps = self.extreme_drift_bps;
+
+        state.target_delta_bps = self.target_delta_bps;
+        state.lst_beta_fp = self.lst_beta_fp;
+        state.funding_bps_per_day = self.funding_bps_per_day;
+        state.borrow_bps_per_day = self.borrow_bps_per_day;
+        state.staking_bps_per_day = self.staking_bps_per_day;
+        state.staking_accrued_usd = self.staking_accrued_usd;
+
+        // accrual clocks start at the migration (nothing accrued in the legacy layout)
+        state.last_accrual_ts = now_ts;
+        state.last_carry_accrual_ts = now_ts;
+        state.last_fee_ts = now_ts;
+        state.fee_recipient = self.authority;
+
+        state.last_hedge_slot = self.last_hedge_slot;
+        state.last_hedge_ema_price_fp = self.last_hedge_ema_price_fp;
+        state.last_hedge_request_slot = self.last_hedge_request_slot;
+        state.last_hedge_request_id = self.last_hedge_request_id;
+        state.request_outstanding = self.request_outstanding as u8;
+        state.last_fill_slot = self.last_fill_slot;
+        state.hedge_fill_count = self.hedge_fill_count;
+        state.avg_fill_slippage_bps = self.avg_fill_slippage_bps;
+        state.missed_confirms = self.missed_confirms;
+        state.max_confirm_delay_slots = self.max_confirm_delay_slots;
+
+        state.paused = self.paused as u8;
+        state.emergency_withdraw_enabled = self.emergency_withdraw_enabled as u8;
+
+        state.keepers = self.keepers;
+        state.keeper_count = self.keeper_count;
+        state.keeper_heartbeat_slot = self.keeper_heartbeat_slot;
+        state.keeper_miss_count = self.keeper_miss_count;
+        state.max_updates_per_epoch = self.max_updates_per_epoch;
+        state.keeper_updates_this_epoch = self.keeper_updates_this_epoch;
+        state.keeper_bond_required_lamports = self.keeper_bond_required_lamports;
+        state.keeper_bond_deposited_lamports = self.keeper_bond_deposited_lamports;
+    }
+}
+
+/// Layout version of a vault account of any layout, plus the decoded state when it is
+/// the legacy Borsh one (v0). Checks the discriminator; the caller checks authority.
+pub fn read_vault_header(data: &[u8]) -> Result<(u8, Option<Box<LegacyVaultState>>)> {
+    require!(data.len() > VAULT_LAYOUT_VERSION_OFFSET, ErrorCode::VaultLayoutInvalid);
+    require!(data[..8] == <VaultState as Discriminator>::DISCRIMINATOR, ErrorCode::VaultLayoutInvalid);
+    if data.len() != LegacyVaultState::SPACE {
+        return Ok((data[VAULT_LAYOUT_VERSION_OFFSET], None));
+    }
+    let legacy = LegacyVaultState::deserialize(&mut &data[8..]).map_err(|_| error!(ErrorCode::VaultLayoutInvalid))?;
+    Ok((0, Some(Box::new(legacy))))
+}
+
+/// Run the per-version upgrade steps on an account already resized to `VaultState::SPACE`
+/// and stamp `VAULT_LAYOUT_VERSION`.
+pub fn upgrade_vault_data(data: &mut [u8], legacy: Option<Box<LegacyVaultState>>, now_ts: i64) -> &mut VaultState {
+    if legacy.is_some() {
+        // the Borsh bytes don't line up with any zero-copy field
+        data[8..VaultState::SPACE].fill(0);
+    }
+    let state: &mut VaultState = bytemuck::from_bytes_mut(&mut data[8..VaultState::SPACE]);
+
+    // per-version upgrade steps, oldest first (add one per VAULT_LAYOUT_VERSION bump)
+    if let Some(legacy) = legacy {
+        // legacy Borsh -> v1: rewrite the surviving fields into the zero-copy layout
+        legacy.upgrade_into(state, now_ts);
+        // the ring moved to ReturnsRing: keep the old 32-sample window
+        state.returns_window = N_RETURNS as u16;
+        // no USDC/USD feed pinned, so SOL/USDC stays 1:1
+        state.max_usdc_depeg_bps = DEFAULT_MAX_USDC_DEPEG_BPS;
+        state.bump_config_version_and_hash();
+    }
+    state.layout_version = VAULT_LAYOUT_VERSION;
+    state
+}
+
+impl VaultState {
+    /// Discriminator + struct, summed per group above. Checked against
+    /// `size_of::<VaultState>()` at compile time (see the assert below).
+    pub const SPACE: usize = 8
+        + (32 + 1 + 1 + 2 + 1) // authority, layout_version, vault_bump, vault_index, custody_authority_bump
+        + (1 + 1 + 1) // paused, emergency_withdraw_enabled, pad
+        + (32 + 32) // pending_authority, keeper_admin
+        + VAULT_NAME_LEN
+        + (8 + 32) // config identity
+        + (8 + 8) // epoch + cooldown
+        + (8 + 8 + 8 + 8) // exposures, total_shares
+        + (8 + 8 + 8) // unstake queue
+        + 8 // total_slashed_sol
+        + (32 + 32) // custody_mint, share_mint
+        + (8 + 8 + 8 + 2 + 6) // caps
+        + (8 + 8 + (4 * N_RETURNS) + 2 + 1 + 1 + 4) // returns buffer
+        + (8 + 2 + 1 + 1) // vol model, return_kind
+        + (2 * 4) // vol outputs
+        + (2 * 2) // score weights
+        + (8 + 8 + 2 + 2) // policy bounds
+        + (2 + 2 + 8) // policy outputs
+        + (8 + 2 + 2 + 4) // stability
+        + (8 + 2 + 2 + 1 + 3) // oracle config
+        + (8 * 6 + 1) // oracle last observation
+        + (1 + 2) // circuit breaker
+        + (2 + 2 + 8) // hedge sizing
+        + (4 * 3) // carry inputs
+        + (1 + 3 + 8 + 8) // staking accrual
+        + (8 * 3) // hedge carry
+        + (32 + 8 + 8 + 8 + 2 + 2 + 4) // fees
+        + (8 + 8) // hedge timing
+        + (8 * 7 + 4 + 2 + 1 + 1) // hedge request/confirm
+        + ((32 * MAX_KEEPERS) + (8 * MAX_KEEPERS) + (4 * MAX_KEEPERS)) // keepers
+        + (8 + (8 * MAX_KEEPERS) + (2 * MAX_KEEPERS) + 2 + 1 + 5) // keeper controls
+        + (2 + 6) // returns window
+        + (8 + 8) // return anchor
+        + (8 + 8 + 2 + 2 + 2 + 2) // garch
+        + (1 + 1 + 2 + 4) // oracle aggregation
+        + (32 + 32 + 1 + 1 + 1 + 5) // pyth pull
+        + (32 * 3) // pinned oracle accounts
+        + (32 + 32 + 2 + 6) // usdc cross-rate
+        + 8 // locked_shares
+        + (1 + 1 + 6 + 8) // validator registry / asset book flags + pending yield
+        + VAULT_RESERVED_LEN;
+
+    pub fn require_not_paused(&self) -> Result<()> {
+        require!(self.paused == 0, ErrorCode::Paused);
+        Ok(())
+    }
+
+    /// Vaults migrated from before pinning carry no oracle accounts until set_oracle_accounts
+    pub fn require_oracle_accounts_set(&self) -> Result<()> {
+        require!(
+            self.oracle_sol_usd != Pubkey::default() && self.oracle_sol_usdc != Pubkey::default(),
+            ErrorCode::OracleAccountsNotSet
+        );
+        Ok(())
+    }
+
+    pub fn pyth_pull_source(&self, feed_id: [u8; 32]) -> PythPullSource {
+        PythPullSource {
+            feed_id,
+            min_verification: self.pyth_pull_min_verification,
+            min_signatures: self.pyth_pull_min_signatures,
+        }
+    }
+
+    /// Stationary GARCH params: omega > 0 and alpha + beta < 1
+    pub fn garch_params_valid(&self) -> bool {
+        self.garch_omega_fp2 > 0 && (self.garch_alpha_bps as u32) + (self.garch_beta_bps as u32) < BPS_DENOM as u32
+    }
+
+    /// Long-run variance omega / (1 - alpha - beta), fp^2 (clamped)
+    pub fn garch_long_run_var_fp2(&self) -> Result<u128> {
+        let persistence = (self.garch_alpha_bps as u128) + (self.garch_beta_bps as u128);
+        let gap = (BPS_DENOM as u128).checked_sub(persistence).filter(|g| *g > 0).ok_or(ErrorCode::InvalidParams)?;
+        let v = (self.garch_omega_fp2 as u128).checked_mul(BPS_DENOM as u128).ok_or(ErrorCode::MathOverflow)? / gap;
+        Ok(v.min(MAX_VAR_FP2))
+    }
+
+    /// One GARCH step on an annualized variance sample. An unseeded model
+    /// starts from the long-run variance.
+    fn garch_update(&self, var_sample_fp2: u128) -> Result<u128> {
+        let prev = if self.garch_var_fp2 == 0 {
+            self.garch_long_run_var_fp2()?
+        } else {
+            self.garch_var_fp2 as u128
+        };
+        let a = var_sample_fp2.checked_mul(self.garch_alpha_bps as u128).ok_or(ErrorCode::MathOverflow)? / (BPS_DENOM as u128);
+        let b = prev.checked_mul(self.garch_beta_bps as u128).ok_or(ErrorCode::MathOverflow)? / (BPS_DENOM as u128);
+        let h = (self.garch_omega_fp2 as u128)
+            .checked_add(a)
+            .and_then(|x| x.checked_add(b))
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(h.min(MAX_VAR_FP2))
+    }
+
+    /// Samples between hedges: min_hedge_interval_slots over the return spacing
+    pub fn garch_horizon_steps(&self) -> u64 {
+        let spacing = self.min_return_spacing_slots.max(1);
+        (self.min_hedge_interval_slots / spacing).clamp(1, MAX_GARCH_HORIZON_STEPS)
+    }
+
+    /// h-step variance forecast: V_L + (alpha + beta)^h * (h_t - V_L), as annualized bps
+    pub fn garch_forecast_vol_bps(&self, steps: u64) -> Result<u16> {
+        let long_run = self.garch_long_run_var_fp2()? as i128;
+        let persistence = (self.garch_alpha_bps as u32 + self.garch_beta_bps as u32) as u16;
+        let decay = pow_bps(persistence, steps) as i128;
+        let current = self.garch_var_fp2 as i128;
+        let var = long_run + (current - long_run) * decay / (BPS_DENOM as i128);
+        fp_to_bps(isqrt_u128((var.max(0) as u128).min(MAX_VAR_FP2)))
+    }
+
+    pub fn bump_config_version_and_hash(&mut self) {
+        self.config_version = self.config_version.saturating_add(1);
+        self.recompute_config_hash();
+    }
+
+    pub fn recompute_config_hash(&mut self) {
+        let mut bytes = Vec::<u8>::with_capacity(256);
+
+        bytes.extend_from_slice(self.authority.as_ref());
+        bytes.extend_from_slice(self.keeper_admin.as_ref());
+
+        bytes.extend_from_slice(&self.min_band_bps.to_le_bytes());
+        bytes.extend_from_slice(&self.max_band_bps.to_le_bytes());
+        bytes.extend_from_slice(&self.min_interval_slots.to_le_bytes());
+        bytes.extend_from_slice(&self.max_interval_slots.to_le_bytes());
+
+        bytes.extend_from_slice(&self.vol_weight_realized_bps.to_le_bytes());
+        bytes.extend_from_slice(&self.vol_weight_implied_bps.to_le_bytes());
+
+        bytes.push(self.vol_mode);
+        bytes.push(self.return_kind);
+        bytes.extend_from_slice(&self.ewma_alpha_bps.to_le_bytes());
+
+        bytes.extend_from_slice(&self.min_samples.to_le_bytes());
+        bytes.extend_from_slice(&self.min_return_spacing_slots.to_le_bytes());
+        bytes.extend_from_slice(&self.returns_window.to_le_bytes());
+
+        bytes.extend_from_slice(&self.policy_update_min_slots.to_le_bytes());
+        bytes.extend_from_slice(&self.max_policy_slew_bps.to_le_bytes());
+        bytes.extend_from_slice(&self.hysteresis_bps.to_le_bytes());
+
+        bytes.push(self.oracle_feed_choice);
+        bytes.extend_from_slice(&self.max_price_age_slots.to_le_bytes());
+        bytes.extend_from_slice(&self.max_confidence_bps.to_le_bytes());
+        bytes.extend_from_slice(&self.max_price_jump_bps.to_le_bytes());
+        bytes.push(self.oracle_feed_mask);
+        bytes.push(self.oracle_min_feeds);
+        bytes.extend_from_slice(&self.oracle_max_spread_bps.to_le_bytes());
+        bytes.push(self.pyth_pull_enabled);
+        bytes.extend_from_slice(&self.pyth_sol_usd_feed_id);
+        bytes.extend_from_slice(&self.pyth_sol_usdc_feed_id);
+        bytes.push(self.pyth_pull_min_verification);
+        bytes.push(self.pyth_pull_min_signatures);
+        bytes.extend_from_slice(self.oracle_sol_usd.as_ref());
+        bytes.extend_from_slice(self.oracle_sol_usdc.as_ref());
+        bytes.extend_from_slice(self.oracle_switchboard_feed.as_ref());
+        bytes.extend_from_slice(self.oracle_usdc_usd.as_ref());
+        bytes.extend_from_slice(&self.pyth_usdc_usd_feed_id);
+        bytes.extend_from_slice(&self.max_usdc_depeg_bps.to_le_bytes());
+
+        bytes.extend_from_slice(&self.target_delta_bps.to_le_bytes());
+        bytes.extend_from_slice(&self.lst_beta_fp.to_le_bytes());
+
+        bytes.extend_from_slice(&self.max_staked_sol.to_le_bytes());
+        bytes.extend_from_slice(&self.max_abs_hedge_notional_usd.to_le_bytes());
+        bytes.extend_from_slice(&self.max_hedge_per_sol_usd_fp.to_le_bytes());
+        bytes.extend_from_slice(&self.min_reserve_bps.to_le_bytes());
+
+        bytes.extend_from_slice(&self.max_confirm_delay_slots.to_le_bytes());
+        bytes.extend_from_slice(&self.extreme_drift_bps.to_le_bytes());
+
+        bytes.extend_from_slice(&self.max_updates_per_epoch.to_le_bytes());
+        bytes.extend_from_slice(&self.keeper_bond_required_lamports.to_le_bytes());
+
+        bytes.extend_from_slice(&self.unstake_cooldown_epochs.to_le_bytes());
+        bytes.push(self.compound_staking_yield);
+
+        bytes.extend_from_slice(self.fee_recipient.as_ref());
+        bytes.extend_from_slice(&self.management_fee_bps.to_le_bytes());
+        bytes.extend_from_slice(&self.performance_fee_bps.to_le_bytes());
+
+        bytes.extend_from_slice(self.custody_mint.as_ref());
+        bytes.extend_from_slice(self.share_mint.as_ref());
+
+        bytes.extend_from_slice(&self.garch_omega_fp2.to_le_bytes());
+        bytes.extend_from_slice(&self.garch_alpha_bps.to_le_bytes());
+        bytes.extend_from_slice(&self.garch_beta_bps.to_le_bytes());
+
+        let h = hashv(&[b"vwsa-config-v1", &bytes]);
+        self.config_hash = h.to_bytes();
+    }
+
+    pub fn is_keeper(&self, k: &Pubkey) -> bool {
+        let n = (self.keeper_count as usize).min(MAX_KEEPERS);
+        for i in 0..n {
+            if self.keepers[i] == *k {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn keeper_index(&self, k: &Pubkey) -> Option<usize> {
+        let n = (self.keeper_count as usize).min(MAX_KEEPERS);
+        self.keepers[..n].iter().position(|x| x == k)
+    }
+
+    pub fn require_keeper_feeder(&self, k: &Pubkey) -> Result<()> {
+        require!(
+            self.is_keeper(k) || *k == self.keeper_admin || *k == self.authority,
+            ErrorCode::Unauthorized
+        );
+        Ok(())
+    }
+
+    pub fn require_keeper_rate_limit_ok(&self, k: &Pubkey) -> Result<()> {
+        if let Some(i) = self.keeper_index(k) {
+            if self.keeper_bond_required_lamports > 0 {
+                require!(
+                    self.keeper_bond_deposited_lamports[i] >= self.keeper_bond_required_lamports,
+                    ErrorCode::KeeperBondInsufficient
+                );
+            }
+            require!(
+                self.keeper_updates_this_epoch[i] < self.max_updates_per_epoch,
+                ErrorCode::KeeperRateLimited
+            );
+        }
+        Ok(())
+    }
+
+    pub fn bump_keeper_heartbeat_and_updates(&mut self, keeper: &Pubkey, slot: u64) -> Result<()> {
+        if let Some(i) = self.keeper_index(keeper) {
+            self.keeper_heartbeat_slot[i] = slot;
+            self.keeper_updates_this_epoch[i] = self.keeper_updates_this_epoch[i].saturating_add(1);
+        }
+        Ok(())
+    }
 
-    // score weights
-    pub vol_weight_realized_bps: u16,
-    pub vol_weight_implied_bps: u16,
+    pub fn add_keeper(&mut self, keeper: Pubkey) -> Result<()> {
+        if self.is_keeper(&keeper) {
+            return Ok(());
+        }
+        let n = self.keeper_count as usize;
+        require!(n < MAX_KEEPERS, ErrorCode::InvalidParams);
+        self.keepers[n] = keeper;
+        self.keeper_heartbeat_slot[n] = 0;
+        self.keeper_miss_count[n] = 0;
+        self.keeper_updates_this_epoch[n] = 0;
+        self.keeper_bond_deposited_lamports[n] = 0;
+        self.keeper_count = (n + 1) as u8;
+        Ok(())
+    }
 
-    // policy bounds
-    pub min_band_bps: u16,
-    pub max_band_bps: u16,
-    pub min_interval_slots: u64,
-    pub max_interval_slots: u64,
+    pub fn remove_keeper(&mut self, keeper: Pubkey) -> Result<()> {
+        let n = self.keeper_count as usize;
+        if n == 0 {
+            return Ok(());
+        }
+        let mut idx: Option<usize> = None;
+        for i in 0..n.min(MAX_KEEPERS) {
+            if self.keepers[i] == keeper {
+                idx = Some(i);
+                break;
+            }
+        }
+        if idx.is_none() {
+            return Ok(());
+        }
+        let i = idx.unwrap();
+        let last = n - 1;
 
-    // policy outputs
-    pub band_bps: u16,
-    pub min_hedge_interval_slots: u64,
+        self.keepers[i] = self.keepers[last];
+        self.keeper_heartbeat_slot[i] = self.keeper_heartbeat_slot[last];
+        self.keeper_miss_count[i] = self.keeper_miss_count[last];
+        self.keeper_updates_this_epoch[i] = self.keeper_updates_this_epoch[last];
+        self.keeper_bond_deposited_lamports[i] = self.keeper_bond_deposited_lamports[last];
+
+        self.keepers[last] = Pubkey::default();
+        self.keeper_heartbeat_slot[last] = 0;
+        self.keeper_miss_count[last] = 0;
+        self.keeper_updates_this_epoch[last] = 0;
+        self.keeper_bond_deposited_lamports[last] = 0;
+
+        self.keeper_count = last as u8;
+        Ok(())
+    }
+
+    pub fn set_hedge_notional_checked(&mut self, hedge: i64) -> Result<()> {
+        let abs = abs_i64(hedge);
+        require!(abs <= self.max_abs_hedge_notional_usd, ErrorCode::CapExceeded);
+        self.hedge_notional_usd = hedge;
+        self.enforce_leverage_guardrail()?;
+        Ok(())
+    }
+
+    pub fn enforce_leverage_guardrail(&self) -> Result<()> {
+        if self.staked_sol == 0 {
+            require!(self.hedge_notional_usd == 0, ErrorCode::LeverageExceeded);
+            return Ok(());
+        }
+        let max_per_sol = self.max_hedge_per_sol_usd_fp as i128;
+        require!(max_per_sol > 0, ErrorCode::InvalidParams);
+
+        let lhs = abs_i64(self.hedge_notional_usd) as i128;
+        let rhs = (self.staked_sol as i128)
+            .checked_mul(max_per_sol)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (PRICE_FP_SCALE as i128);
+
+        require!(lhs <= rhs, ErrorCode::LeverageExceeded);
+        Ok(())
+    }
+
+    pub fn required_reserve_sol(&self) -> Result<u64> {
+        let req = (self.staked_sol as u128)
+            .checked_mul(self.min_reserve_bps as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (BPS_DENOM as u128);
+        Ok(req as u64)
+    }
+
+    pub fn enforce_reserve_ratio(&self) -> Result<()> {
+        let req = self.required_reserve_sol()?;
+        require!(self.reserve_sol >= req, ErrorCode::ReserveTooLow);
+        Ok(())
+    }
+
+    /// Accrue staking yield on staked_sol at staking_bps_per_day for the seconds since
+    /// last_accrual_ts. Compounds into staked_sol or books USD into staking_accrued_usd.
+    /// Sub-unit results leave last_accrual_ts untouched so small books still accrue.
+    pub fn accrue_staking_yield(&mut self, now_ts: i64, slot: u64) -> Result<()> {
+        let elapsed = now_ts.saturating_sub(self.last_accrual_ts);
+        if elapsed <= 0 {
+            return Ok(());
+        }
+        if self.staked_sol == 0 || self.staking_bps_per_day <= 0 {
+            self.last_accrual_ts = now_ts;
+            return Ok(());
+        }
+        // USD booking needs a price; wait for the oracle rather than dropping the yield
+        if self.compound_staking_yield == 0 && self.oracle_price_fp <= 0 {
+            return Ok(());
+        }
+
+        let yield_sol = (self.staked_sol as u128)
+            .checked_mul(self.staking_bps_per_day as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_mul(elapsed as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / ((BPS_DENOM as u128) * (SECONDS_PER_DAY as u128));
+        if yield_sol == 0 {
+            return Ok(());
+        }
+        let yield_sol = yield_sol.min(u64::MAX as u128) as u64;
+
+        let yield_usd = if self.compound_staking_yield != 0 {
+            self.staked_sol = self.staked_sol.saturating_add(yield_sol);
+            if self.has_validator_registry != 0 {
+                self.registry_pending_yield_sol = self.registry_pending_yield_sol.saturating_add(yield_sol);
+            }
+            0
+        } else {
+            let v = self.sol_value_usd(yield_sol)?;
+            self.staking_accrued_usd = self.staking_accrued_usd.checked_add(v).ok_or(ErrorCode::MathOverflow)?;
+            v
+        };
+        self.last_accrual_ts = now_ts;
+
+        emit!(StakingYieldAccrued {
+            epoch: self.epoch,
+            slot,
+            elapsed_secs: elapsed,
+            staking_bps_per_day: self.staking_bps_per_day,
+            yield_sol,
+            yield_usd,
+            compounded: self.compound_staking_yield != 0,
+            staked_sol: self.staked_sol,
+            staking_accrued_usd: self.staking_accrued_usd,
+        });
+        Ok(())
+    }
+
+    pub fn accrue_staking_yield_now(&mut self) -> Result<()> {
+        let clock = Clock::get()?;
+        self.accrue_staking_yield(clock.unix_timestamp, clock.slot)
+    }
+
+    /// Accrue perp carry since last_carry_accrual_ts: funding on the signed hedge notional
+    /// (positive funding is paid by longs to shorts, so a short hedge earns it) minus borrow
+    /// on |hedge_notional_usd|.
+    pub fn accrue_hedge_carry(&mut self, now_ts: i64) -> Result<i64> {
+        let elapsed = now_ts.saturating_sub(self.last_carry_accrual_ts);
+        if elapsed <= 0 {
+            return Ok(0);
+        }
+        if self.hedge_notional_usd == 0 || (self.funding_bps_per_day == 0 && self.borrow_bps_per_day == 0) {
+            self.last_carry_accrual_ts = now_ts;
+            return Ok(0);
+        }
+
+        // funding follows the side (longs pay shorts when positive); borrow is paid on size
+        let notional = self.hedge_notional_usd as i128;
+        let funding = notional
+            .checked_neg()
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_mul(self.funding_bps_per_day as i128)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let borrow = notional
+            .abs()
+            .checked_mul(self.borrow_bps_per_day as i128)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let carry = funding
+            .checked_sub(borrow)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_mul(elapsed as i128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / ((BPS_DENOM as i128) * (SECONDS_PER_DAY as i128));
+        if carry == 0 {
+            // sub-unit: keep the clock so the remainder keeps accruing
+            return Ok(0);
+        }
+        let carry = carry.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+
+        self.hedge_carry_accrued_usd = self.hedge_carry_accrued_usd.checked_add(carry).ok_or(ErrorCode::MathOverflow)?;
+        self.carry_accrued_epoch_usd = self.carry_accrued_epoch_usd.checked_add(carry).ok_or(ErrorCode::MathOverflow)?;
+        self.last_carry_accrual_ts = now_ts;
+        Ok(carry)
+    }
+
+    /// Accrue management (time-based, on NAV) and performance (NAV/share above the
+    /// high-water mark) fees by minting fee shares held for fee_recipient.
+    pub fn accrue_fees(&mut self, now_ts: i64, slot: u64) -> Result<()> {
+        let elapsed = now_ts.saturating_sub(self.last_fee_ts).max(0);
+        if self.total_shares == 0 {
+            self.last_fee_ts = now_ts;
+            self.high_water_mark_fp = 0;
+            return Ok(());
+        }
+        // no NAV to charge against: keep last_fee_ts so the period is charged once there is
+        let nav = self.compute_nav_usd()?;
+        if nav <= 0 {
+            return Ok(());
+        }
+        self.last_fee_ts = now_ts;
+
+        let mgmt_fee_usd = ((nav as i128)
+            .checked_mul(self.management_fee_bps as i128)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_mul(elapsed as i128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / ((BPS_DENOM as i128) * (SECONDS_PER_YEAR as i128))) as i64;
+
+        // performance fee on the gain above the HWM, after the management fee
+        let price_after_mgmt_fp = self.share_price_fp(nav - mgmt_fee_usd)?;
+        let mut perf_fee_usd: i64 = 0;
+        if self.high_water_mark_fp == 0 {
+            self.high_water_mark_fp = price_after_mgmt_fp;
+        } else if price_after_mgmt_fp > self.high_water_mark_fp {
+            let gain_usd = ((price_after_mgmt_fp - self.high_water_mark_fp) as i128)
+                .checked_mul(self.total_shares as i128)
+                .ok_or(ErrorCode::MathOverflow)?
+                / (PRICE_FP_SCALE as i128);
+            perf_fee_usd = (gain_usd * (self.performance_fee_bps as i128) / (BPS_DENOM as i128)) as i64;
+        }
+
+        let fee_usd = mgmt_fee_usd.checked_add(perf_fee_usd).ok_or(ErrorCode::MathOverflow)?;
+        let mut fee_shares: u64 = 0;
+        if fee_usd > 0 && fee_usd < nav {
+            // dilute so the new shares are worth fee_usd at post-fee NAV/share
+            fee_shares = ((fee_usd as i128)
+                .checked_mul(self.total_shares as i128)
+                .ok_or(ErrorCode::MathOverflow)?
+                / ((nav - fee_usd) as i128))
+                .min(u64::MAX as i128) as u64;
+            self.total_shares = self.total_shares.checked_add(fee_shares).ok_or(ErrorCode::MathOverflow)?;
+            self.fee_shares_unclaimed = self.fee_shares_unclaimed.checked_add(fee_shares).ok_or(ErrorCode::MathOverflow)?;
+        }
+        if perf_fee_usd > 0 || self.performance_fee_bps == 0 {
+            self.high_water_mark_fp = self.high_water_mark_fp.max(self.share_price_fp(nav)?);
+        }
+
+        if fee_shares > 0 {
+            emit!(FeesAccrued {
+                epoch: self.epoch,
+                slot,
+                elapsed_secs: elapsed,
+                nav_usd: nav,
+                management_fee_usd: mgmt_fee_usd,
+                performance_fee_usd: perf_fee_usd,
+                fee_shares,
+                share_price_fp: self.share_price_fp(nav)?,
+                high_water_mark_fp: self.high_water_mark_fp,
+                fee_shares_unclaimed: self.fee_shares_unclaimed,
+                fee_recipient: self.fee_recipient,
+            });
+        }
+        Ok(())
+    }
+
+    /// Staking yield + hedge carry + fees up to the current clock (before NAV-priced flows).
+    /// Fees need NAV, so they are only charged off a fresh mark and a positive NAV; otherwise
+    /// last_fee_ts stays put and the elapsed time is charged on the next accrual with both.
+    pub fn accrue_to_now(&mut self) -> Result<()> {
+        let clock = Clock::get()?;
+        self.accrue_staking_yield(clock.unix_timestamp, clock.slot)?;
+        self.accrue_hedge_carry(clock.unix_timestamp)?;
+        if self.mark_is_fresh(clock.unix_timestamp) {
+            self.accrue_fees(clock.unix_timestamp, clock.slot)?;
+        }
+        Ok(())
+    }
+
+    pub fn expected_carry_bps(&self) -> i32 {
+        self.staking_bps_per_day
+            .saturating_add(self.funding_bps_per_day)
+            .saturating_sub(self.borrow_bps_per_day)
+    }
+
+    /// Record the return since the last recorded sample together with the
+    /// publish time it spans, so estimators can normalize variance per second.
+    pub fn try_record_oracle_return(&mut self, ring: &mut ReturnsRing, slot: u64, price_fp: i64, publish_ts: i64) -> Result<()> {
+        if self.last_return_slot != 0 {
+            let elapsed = slot.saturating_sub(self.last_return_slot);
+            if elapsed < self.min_return_spacing_slots {
+                return Ok(());
+            }
+        }
+
+        if self.last_return_price_fp <= 0 || self.last_return_ts <= 0 {
+            self.last_return_price_fp = price_fp;
+            self.last_return_ts = publish_ts;
+            self.last_return_slot = slot;
+            return Ok(());
+        }
+
+        // same publish as the last sample: no new information
+        let elapsed_secs = publish_ts.checked_sub(self.last_return_ts).ok_or(ErrorCode::MathOverflow)?;
+        if elapsed_secs <= 0 {
+            return Ok(());
+        }
+        let elapsed_secs = elapsed_secs.min(u32::MAX as i64) as u32;
+
+        let ret_i32 = compute_return_fp(price_fp, self.last_return_price_fp, self.return_kind)?;
+
+        let sample = ReturnSample { ret_fp: ret_i32, elapsed_secs };
+        let idx = ring.push(sample)?;
+
+        if self.vol_mode == VolMode::Ewma as u8 {
+            // EWMA of annualized variance samples (r^2 / dt * year)
+            let var_ann = annualized_var_sample_fp2(sample)?;
+            self.ewma_var_fp2 = ewma_update_u128(self.ewma_var_fp2 as u128, var_ann, self.ewma_alpha_bps)? as u64;
+        } else if self.vol_mode == VolMode::Garch as u8 {
+            let var_ann = annualized_var_sample_fp2(sample)?;
+            self.garch_var_fp2 = self.garch_update(var_ann)? as u64;
+        }
+
+        self.last_return_slot = slot;
+        self.last_return_price_fp = price_fp;
+        self.last_return_ts = publish_ts;
+
+        emit!(OracleReturnRecorded {
+            epoch: self.epoch,
+            slot,
+            idx,
+            return_fp: ret_i32,
+            elapsed_secs,
+            nonzero_samples: ring.nonzero_samples,
+            oracle_price_fp: price_fp,
+        });
+
+        Ok(())
+    }
+
+    /// The mark is usable for minting/burning shares: last read passed the gates, the
+    /// breaker is clear and it was published within max_price_age_slots (seconds).
+    pub fn mark_is_fresh(&self, now_ts: i64) -> bool {
+        let now = now_ts.max(0) as u64;
+        self.oracle_ok != 0
+            && self.oracle_degraded == 0
+            && self.oracle_price_fp > 0
+            && self.oracle_publish_slot <= now
+            && now - self.oracle_publish_slot <= self.max_price_age_slots
+    }
+
+    pub fn require_fresh_mark(&self, now_ts: i64) -> Result<()> {
+        require!(self.mark_is_fresh(now_ts), ErrorCode::OraclePriceUnsafe);
+        Ok(())
+    }
+
+    /// Hedge flows: once the vault has an asset book it must be passed, so requests can't
+    /// skip the multi-LST targets and blended beta.
+    pub fn require_asset_book_if_initialized(&mut self, has_book: bool) -> Result<()> {
+        if has_book {
+            // books created before the flag existed
+            self.has_asset_book = 1;
+        } else {
+            require!(self.has_asset_book == 0, ErrorCode::AssetBookRequired);
+        }
+        Ok(())
+    }
+
+    /// Stake-moving flows: once the vault has a validator registry it must be passed, and
+    /// yield compounded since the last flow is credited to the validators that earned it.
+    pub fn sync_validator_registry(&mut self, registry: Option<&mut ValidatorRegistry>) -> Result<()> {
+        let Some(registry) = registry else {
+            require!(self.has_validator_registry == 0, ErrorCode::ValidatorRegistryRequired);
+            return Ok(());
+        };
+        // registries created before the flag existed
+        self.has_validator_registry = 1;
+        registry.credit_yield(self.registry_pending_yield_sol);
+        self.registry_pending_yield_sol = 0;
+        Ok(())
+    }
+
+    pub fn sol_value_usd(&self, amount_sol: u64) -> Result<i64> {
+        if amount_sol == 0 {
+            return Ok(0);
+        }
+        let p = self.oracle_price_fp;
+        require!(p > 0, ErrorCode::OracleNotReady);
+        let v = (amount_sol as i128)
+            .checked_mul(p as i128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (PRICE_FP_SCALE as i128);
+        Ok(v.min(i64::MAX as i128) as i64)
+    }
+
+    pub fn staked_value_usd(&self) -> Result<i64> {
+        self.sol_value_usd(self.staked_sol)
+    }
+
+    pub fn reserve_value_usd(&self) -> Result<i64> {
+        self.sol_value_usd(self.reserve_sol)
+    }
+
+    /// Shares to mint for a deposit worth `deposit_value_usd`, priced at pre-deposit NAV.
+    /// First deposit (no shares outstanding) mints 1 share per USD unit.
+    pub fn shares_for_deposit_usd(&self, deposit_value_usd: i64, nav_before_usd: i64) -> Result<u64> {
+        if deposit_value_usd <= 0 {
+            return Ok(0);
+        }
+        if self.total_shares == 0 {
+            // first mint at 1 share per NAV unit, covering any NAV already in the vault
+            let s = (deposit_value_usd as u64)
+                .checked_add(nav_before_usd.max(0) as u64)
+                .ok_or(ErrorCode::MathOverflow)?;
+            return Ok(s);
+        }
+        require!(nav_before_usd > 0, ErrorCode::NavNotPositive);
+
+        let s = (deposit_value_usd as u128)
+            .checked_mul(self.total_shares as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (nav_before_usd as u128);
+        Ok(s.min(u64::MAX as u128) as u64)
+    }
+
+    /// Convert a USD value back to SOL at the current oracle price (rounds down).
+    pub fn usd_to_sol(&self, value_usd: i64) -> Result<u64> {
+        if value_usd <= 0 {
+            return Ok(0);
+        }
+        let p = self.oracle_price_fp;
+        require!(p > 0, ErrorCode::OracleNotReady);
+        let v = (value_usd as i128)
+            .checked_mul(PRICE_FP_SCALE as i128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (p as i128);
+        Ok(v.min(u64::MAX as i128) as u64)
+    }
+
+    /// Scale the hedge by staked_sol / prev_staked_sol so exits shrink it with the book.
+    /// The reduction is booked as a fill at the last oracle mark.
+    pub fn scale_hedge_for_unstake(&mut self, prev_staked_sol: u64) -> Result<()> {
+        if prev_staked_sol == 0 || self.hedge_notional_usd == 0 {
+            return Ok(());
+        }
+        let scaled = (self.hedge_notional_usd as i128)
+            .checked_mul(self.staked_sol as i128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (prev_staked_sol as i128);
+        self.book_hedge_fill(scaled as i64, self.oracle_price_fp)?;
+        self.hedge_notional_usd = scaled as i64;
+        Ok(())
+    }
+
+    pub fn emit_hedge_scaled_down(&self, slot: u64, prev_hedge_notional_usd: i64) -> Result<()> {
+        if prev_hedge_notional_usd == self.hedge_notional_usd {
+            return Ok(());
+        }
+        let sizing_price_fp = if self.oracle_ok != 0 && self.oracle_price_fp > 0 {
+            self.oracle_price_fp
+        } else {
+            self.oracle_ema_price_fp
+        };
+        let target = compute_target_hedge_notional_usd_delta(
+            self.staked_sol,
+            sizing_price_fp,
+            self.target_delta_bps,
+            self.lst_beta_fp,
+        )?;
+        emit!(HedgeScaledDown {
+            epoch: self.epoch,
+            slot,
+            prev_hedge_notional_usd,
+            hedge_notional_usd: self.hedge_notional_usd,
+            target_hedge_notional_usd: target,
+            staked_sol: self.staked_sol,
+        });
+        Ok(())
+    }
 
-    // stability knobs
-    pub policy_update_min_slots: u64,
-    pub max_policy_slew_bps: u16,
-    pub hysteresis_bps: u16,
+    /// NAV per share (fp 1e6); 1.0 before any shares exist.
+    pub fn share_price_fp(&self, nav_usd: i64) -> Result<i64> {
+        if self.total_shares == 0 {
+            return Ok(PRICE_FP_SCALE);
+        }
+        let p = (nav_usd as i128)
+            .checked_mul(PRICE_FP_SCALE as i128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (self.total_shares as i128);
+        Ok(p.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+    }
 
-    // oracle config (NOTE: max_price_age_slots interpreted as seconds in this impl)
-    pub oracle_feed_choice: u8,
-    pub max_price_age_slots: u64,
-    pub max_confidence_bps: u16,
-    pub max_price_jump_bps: u16,
+    /// Shares depositors can redeem: all but the locked (dead + first-mint NAV) shares and
+    /// unclaimed fee shares.
+    pub fn redeemable_shares(&self) -> u64 {
+        self.total_shares
+            .saturating_sub(DEAD_SHARES)
+            .saturating_sub(self.locked_shares)
+            .saturating_sub(self.fee_shares_unclaimed)
+    }
 
-    // oracle last observation
-    pub oracle_price_fp: i64,
-    pub oracle_ema_price_fp: i64,
-    pub oracle_conf_fp: i64,
-    pub oracle_publish_slot: u64, // actually publish_time seconds (unix) in this impl
-    pub oracle_ok: bool,
+    /// Reserve not owed to matured unstake tickets.
+    pub fn free_reserve_sol(&self) -> u64 {
+        self.reserve_sol.saturating_sub(self.withdraw_owed_sol)
+    }
 
-    pub last_oracle_price_fp: i64,
-    pub last_oracle_ema_price_fp: i64,
+    /// Pro-rata NAV attributable to `shares`.
+    pub fn shares_value_usd(&self, shares: u64) -> Result<i64> {
+        if shares == 0 || self.total_shares == 0 {
+            return Ok(0);
+        }
+        let nav = self.compute_nav_usd()?;
+        let v = (nav as i128)
+            .checked_mul(shares as i128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (self.total_shares as i128);
+        Ok(v.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+    }
 
-    // circuit breaker
-    pub oracle_degraded: bool,
-    pub extreme_drift_bps: u16,
+    /// Mark-to-market PnL of the open hedge vs its avg entry, at oracle_price_fp.
+    pub fn unrealized_pnl_usd(&self) -> Result<i64> {
+        hedge_pnl_usd(self.hedge_notional_usd, self.hedge_entry_price_fp, self.oracle_price_fp)
+    }
 
-    // hedge sizing knobs
-    pub target_delta_bps: u16,
-    pub lst_beta_fp: i64,
+    /// Book a fill moving the hedge to `new_notional_usd` at `fill_price_fp`:
+    /// increases blend the avg entry, reduces/flips realize PnL on the closed part.
+    /// Returns the PnL realized by this fill. Does not write hedge_notional_usd.
+    pub fn book_hedge_fill(&mut self, new_notional_usd: i64, fill_price_fp: i64) -> Result<i64> {
+        let prev = self.hedge_notional_usd;
+        if fill_price_fp <= 0 || new_notional_usd == prev {
+            return Ok(0);
+        }
 
-    // carry inputs (bps/day)
-    pub funding_bps_per_day: i32,
-    pub borrow_bps_per_day: i32,
-    pub staking_bps_per_day: i32,
+        let same_side = (prev > 0 && new_notional_usd > 0) || (prev < 0 && new_notional_usd < 0);
+        let mut realized: i64 = 0;
 
-    // staking accrual (simulated)
-    pub staking_accrued_usd: i64,
+        if prev == 0 || (same_side && abs_i64(new_notional_usd) > abs_i64(prev)) {
+            // open / increase
+            self.hedge_entry_price_fp = blend_entry_price_fp(
+                prev,
+                self.hedge_entry_price_fp,
+                new_notional_usd.checked_sub(prev).ok_or(ErrorCode::MathOverflow)?,
+                fill_price_fp,
+            )?;
+        } else if same_side {
+            // partial reduce: entry unchanged
+            let closed = prev.checked_sub(new_notional_usd).ok_or(ErrorCode::MathOverflow)?;
+            realized = hedge_pnl_usd(closed, self.hedge_entry_price_fp, fill_price_fp)?;
+        } else {
+            // close or flip: realize all of prev, remainder opens at the fill
+            realized = hedge_pnl_usd(prev, self.hedge_entry_price_fp, fill_price_fp)?;
+            self.hedge_entry_price_fp = if new_notional_usd == 0 { 0 } else { fill_price_fp };
+        }
 
-    // hedge timing + anchors
-    pub last_hedge_slot: u64,
-    pub last_hedge_ema_price_fp: i64,
+        self.hedge_realized_pnl_usd = self.hedge_realized_pnl_usd.checked_add(realized).ok_or(ErrorCode::MathOverflow)?;
+        Ok(realized)
+    }
 
-    // hedge request/confirm
-    pub last_hedge_request_slot: u64,
-    pub last_hedge_request_id: u64,
-    pub request_outstanding: bool,
+    pub fn compute_nav_usd(&self) -> Result<i64> {
+        let st = self.staked_value_usd()?;
+        let rs = self.reserve_value_usd()?;
+        let pnl = self.unrealized_pnl_usd()?;
+        // matured-but-unfilled unstake tickets are a liability against the reserve
+        let owed = self.sol_value_usd(self.withdraw_owed_sol)?;
+        Ok(st
+            .checked_add(rs)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_add(pnl)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_add(self.hedge_realized_pnl_usd)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_add(self.hedge_carry_accrued_usd)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_add(self.staking_accrued_usd)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_sub(owed)
+            .ok_or(ErrorCode::MathOverflow)?)
+    }
+}
 
-    pub last_fill_slot: u64,
-    pub hedge_fill_count: u64,
-    pub avg_fill_slippage_bps: u16,
-    pub missed_confirms: u32,
-    pub max_confirm_delay_slots: u64,
+/// Per-depositor share accounting (PDA: ["position", vault, owner])
+#[account]
+pub struct UserPosition {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub bump: u8,
 
-    // safety toggles
-    pub paused: bool,
-    pub emergency_withdraw_enabled: bool,
+    // shares of VaultState.total_shares
+    pub shares: u64,
 
-    // keepers
-    pub keepers: [Pubkey; MAX_KEEPERS],
-    pub keeper_count: u8,
-    pub keeper_heartbeat_slot: [u64; MAX_KEEPERS],
-    pub keeper_miss_count: [u32; MAX_KEEPERS],
+    // cost basis
+    pub deposited_sol: u64,
+    pub cost_basis_usd: i64,
 
-    // keeper controls
-    pub max_updates_per_epoch: u16,
-    pub keeper_updates_this_epoch: [u16; MAX_KEEPERS],
-    pub keeper_bond_required_lamports: u64,
-    pub keeper_bond_deposited_lamports: [u64; MAX_KEEPERS],
+    pub last_deposit_slot: u64,
+    pub last_deposit_epoch: u64,
 }
 
-impl VaultState {
+impl UserPosition {
     pub const SPACE: usize = 8
         + 32
         + 32
-        + 32
-        + 1
-        + 8
-        + 32
-        + 8
-        + 8
-        + 8
-        + 8
-        + 8
-        + 8
-        + 2
-        + (4 * N_RETURNS)
-        + 1
-        + 2
-        + 8
-        + 1
-        + 8
-        + 1
-        + 2
-        + 16
-        + 2
-        + 2
-        + 2
-        + 2
-        + 2
-        + 2
-        + 2
-        + 2
-        + 8
-        + 8
-        + 2
-        + 8
-        + 8
-        + 2
-        + 2
-        + 1
-        + 8
-        + 2
-        + 2
-        + 8
-        + 8
-        + 8
-        + 8
-        + 1
-        + 8
-        + 8
-        + 1
-        + 2
-        + 2
-        + 8
-        + 4
-        + 4
-        + 4
-        + 8
-        + 8
-        + 8
-        + 8
         + 1
         + 8
         + 8
         + 8
-        + 2
-        + 4
         + 8
+        + 8;
+
+    /// Burn shares and release cost basis pro-rata.
+    pub fn burn_shares(&mut self, shares: u64) -> Result<()> {
+        require!(shares <= self.shares, ErrorCode::InsufficientShares);
+        if shares == self.shares {
+            self.shares = 0;
+            self.deposited_sol = 0;
+            self.cost_basis_usd = 0;
+            return Ok(());
+        }
+
+        let sol_released = (self.deposited_sol as u128)
+            .checked_mul(shares as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (self.shares as u128);
+        let basis_released = (self.cost_basis_usd as i128)
+            .checked_mul(shares as i128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (self.shares as i128);
+
+        self.deposited_sol -= sol_released as u64;
+        self.cost_basis_usd -= basis_released as i64;
+        self.shares -= shares;
+        Ok(())
+    }
+}
+
+/// Global list of vaults (PDA: ["vault_registry"]) with their config identity, so parameter
+/// variants can be enumerated and compared on-chain. Entries are refreshed by sync_vault_registry
+/// (vault authority) and dropped by deregister_vault (admin or vault authority).
+#[account]
+pub struct VaultRegistry {
+    pub bump: u8,
+    pub vault_count: u16,
+    pub entries: [VaultEntry; MAX_REGISTERED_VAULTS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct VaultEntry {
+    pub vault: Pubkey,
+    pub authority: Pubkey,
+    pub vault_index: u16,
+    pub name: [u8; VAULT_NAME_LEN],
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+impl VaultEntry {
+    pub const SPACE: usize = 32 + 32 + 2 + VAULT_NAME_LEN + 8 + 32;
+}
+
+impl VaultRegistry {
+    pub const SPACE: usize = 8
         + 1
-        + 1
-        + (32 * MAX_KEEPERS)
-        + 1
-        + (8 * MAX_KEEPERS)
-        + (4 * MAX_KEEPERS)
         + 2
-        + (2 * MAX_KEEPERS)
-        + 8
-        + (8 * MAX_KEEPERS);
+        + (VaultEntry::SPACE * MAX_REGISTERED_VAULTS);
 
-    pub fn require_not_paused(&self) -> Result<()> {
-        require!(!self.paused, ErrorCode::Paused);
-        Ok(())
+    pub fn entry_index(&self, vault: &Pubkey) -> Option<usize> {
+        let n = (self.vault_count as usize).min(MAX_REGISTERED_VAULTS);
+        self.entries[..n].iter().position(|e| e.vault == *vault)
     }
 
-    pub fn bump_config_version_and_hash(&mut self) {
-        self.config_version = self.config_version.saturating_add(1);
-        self.recompute_config_hash();
+    /// Insert or refresh the entry for `vault`; returns its index.
+    pub fn upsert(&mut self, vault: Pubkey, state: &VaultState) -> Result<usize> {
+        let n = (self.vault_count as usize).min(MAX_REGISTERED_VAULTS);
+        let i = match self.entry_index(&vault) {
+            Some(i) => i,
+            None => {
+                require!(n < MAX_REGISTERED_VAULTS, ErrorCode::VaultRegistryFull);
+                self.vault_count = (n + 1) as u16;
+                n
+            }
+        };
+        self.entries[i] = VaultEntry {
+            vault,
+            authority: state.authority,
+            vault_index: state.vault_index,
+            name: state.name,
+            config_version: state.config_version,
+            config_hash: state.config_hash,
+        };
+        Ok(i)
     }
 
-    pub fn recompute_config_hash(&mut self) {
-        let mut bytes = Vec::<u8>::with_capacity(256);
+    /// Remove entry `i` (the last entry takes its slot).
+    pub fn remove(&mut self, i: usize) {
+        let last = (self.vault_count as usize).min(MAX_REGISTERED_VAULTS) - 1;
+        self.entries[i] = self.entries[last];
+        self.entries[last] = VaultEntry::default();
+        self.vault_count = last as u16;
+    }
+}
 
-        bytes.extend_from_slice(self.authority.as_ref());
-        bytes.extend_from_slice(self.keeper_admin.as_ref());
+fn emit_vault_registered(registry: &VaultRegistry, entry_index: usize) -> Result<()> {
+    let e = &registry.entries[entry_index];
+    emit!(VaultRegistered {
+        slot: Clock::get()?.slot,
+        vault: e.vault,
+        authority: e.authority,
+        vault_index: e.vault_index,
+        name: e.name,
+        config_version: e.config_version,
+        config_hash: e.config_hash,
+        entry_index: entry_index as u16,
+        vault_count: registry.vault_count,
+    });
+    Ok(())
+}
 
-        bytes.extend_from_slice(&self.min_band_bps.to_le_bytes());
-        bytes.extend_from_slice(&self.max_band_bps.to_le_bytes());
-        bytes.extend_from_slice(&self.min_interval_slots.to_le_bytes());
-        bytes.extend_from_slice(&self.max_interval_slots.to_le_bytes());
+/// Validator delegation book (PDA: ["validator_registry", vault]).
+/// delegated_sol tracks principal routed by deposits/exits; targets are weight / total weight
+/// of the book, clamped by each validator's cap.
+#[account]
+pub struct ValidatorRegistry {
+    pub vault: Pubkey,
+    pub bump: u8,
+    pub validator_count: u8,
+    pub rebalance_drift_bps: u16,
+    pub validators: [ValidatorSlot; MAX_VALIDATORS],
+}
 
-        bytes.extend_from_slice(&self.vol_weight_realized_bps.to_le_bytes());
-        bytes.extend_from_slice(&self.vol_weight_implied_bps.to_le_bytes());
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct ValidatorSlot {
+    pub vote_account: Pubkey,
+    pub target_weight_bps: u16,
+    pub cap_sol: u64,
+    pub delegated_sol: u64,
+    pub apy_bps: u16,
+
+    // slashing history
+    pub slash_count: u32,
+    pub slashed_sol: u64,
+    pub last_slash_epoch: u64,
+    pub last_slash_bps: u16,
+}
 
-        bytes.push(self.vol_mode);
-        bytes.extend_from_slice(&self.ewma_alpha_bps.to_le_bytes());
+impl ValidatorSlot {
+    pub const SPACE: usize = 32 + 2 + 8 + 8 + 2 + 4 + 8 + 8 + 2;
 
-        bytes.extend_from_slice(&self.min_samples.to_le_bytes());
-        bytes.extend_from_slice(&self.min_return_spacing_slots.to_le_bytes());
+    pub fn headroom_sol(&self) -> u64 {
+        self.cap_sol.saturating_sub(self.delegated_sol)
+    }
+}
 
-        bytes.extend_from_slice(&self.policy_update_min_slots.to_le_bytes());
-        bytes.extend_from_slice(&self.max_policy_slew_bps.to_le_bytes());
-        bytes.extend_from_slice(&self.hysteresis_bps.to_le_bytes());
+impl ValidatorRegistry {
+    pub const SPACE: usize = 8
+        + 32
+        + 1
+        + 1
+        + 2
+        + (ValidatorSlot::SPACE * MAX_VALIDATORS);
 
-        bytes.push(self.oracle_feed_choice);
-        bytes.extend_from_slice(&self.max_price_age_slots.to_le_bytes());
-        bytes.extend_from_slice(&self.max_confidence_bps.to_le_bytes());
-        bytes.extend_from_slice(&self.max_price_jump_bps.to_le_bytes());
+    fn len(&self) -> usize {
+        (self.validator_count as usize).min(MAX_VALIDATORS)
+    }
 
-        bytes.extend_from_slice(&self.target_delta_bps.to_le_bytes());
-        bytes.extend_from_slice(&self.lst_beta_fp.to_le_bytes());
+    pub fn validator_index(&self, vote_account: &Pubkey) -> Option<usize> {
+        (0..self.len()).find(|&i| self.validators[i].vote_account == *vote_account)
+    }
 
-        bytes.extend_from_slice(&self.max_staked_sol.to_le_bytes());
-        bytes.extend_from_slice(&self.max_abs_hedge_notional_usd.to_le_bytes());
-        bytes.extend_from_slice(&self.max_hedge_per_sol_usd_fp.to_le_bytes());
-        bytes.extend_from_slice(&self.min_reserve_bps.to_le_bytes());
+    pub fn total_weight_bps(&self) -> u32 {
+        (0..self.len()).map(|i| self.validators[i].target_weight_bps as u32).sum()
+    }
 
-        bytes.extend_from_slice(&self.max_confirm_delay_slots.to_le_bytes());
-        bytes.extend_from_slice(&self.extreme_drift_bps.to_le_bytes());
+    pub fn total_delegated_sol(&self) -> u64 {
+        (0..self.len()).fold(0u64, |acc, i| acc.saturating_add(self.validators[i].delegated_sol))
+    }
 
-        bytes.extend_from_slice(&self.max_updates_per_epoch.to_le_bytes());
-        bytes.extend_from_slice(&self.keeper_bond_required_lamports.to_le_bytes());
+    /// Target stake for validator i at book size `total_sol` (capped).
+    pub fn target_sol(&self, i: usize, total_sol: u64) -> u64 {
+        let w = self.total_weight_bps();
+        if w == 0 {
+            return 0;
+        }
+        let t = (total_sol as u128) * (self.validators[i].target_weight_bps as u128) / (w as u128);
+        (t as u64).min(self.validators[i].cap_sol)
+    }
 
-        let h = hashv(&[b"vwsa-config-v1", &bytes]);
-        self.config_hash = h.to_bytes();
+    /// Delegation-weighted APY across the book.
+    pub fn weighted_apy_bps(&self) -> u16 {
+        let total = self.total_delegated_sol();
+        if total == 0 {
+            return 0;
+        }
+        let num: u128 = (0..self.len())
+            .map(|i| (self.validators[i].delegated_sol as u128) * (self.validators[i].apy_bps as u128))
+            .sum();
+        (num / (total as u128)).min(u16::MAX as u128) as u16
     }
 
-    pub fn is_keeper(&self, k: &Pubkey) -> bool {
-        let n = (self.keeper_count as usize).min(MAX_KEEPERS);
-        for i in 0..n {
-            if self.keepers[i] == *k {
-                return true;
+    /// Credit compounded staking yield to the validators that earned it: pro-rata to
+    /// delegated_sol x apy_bps (delegated_sol alone until APYs are fed). Rewards stay in
+    /// the validator's stake account, so caps don't apply.
+    pub fn credit_yield(&mut self, yield_sol: u64) {
+        let n = self.len();
+        if n == 0 || yield_sol == 0 {
+            return;
+        }
+        let book = &mut self.validators[..n];
+        let any_apy = book.iter().any(|v| v.apy_bps > 0 && v.delegated_sol > 0);
+        let earned = |v: &ValidatorSlot| -> u128 {
+            if any_apy {
+                (v.delegated_sol as u128) * (v.apy_bps as u128)
+            } else {
+                v.delegated_sol as u128
             }
+        };
+        let total: u128 = book.iter().map(earned).sum();
+        if total == 0 {
+            return;
         }
-        false
+
+        let mut credited = 0u64;
+        let mut top = (0usize, 0u128);
+        for (i, v) in book.iter_mut().enumerate() {
+            let w = earned(v);
+            if w > top.1 {
+                top = (i, w);
+            }
+            let share = ((yield_sol as u128) * w / total) as u64;
+            v.delegated_sol = v.delegated_sol.saturating_add(share);
+            credited += share;
+        }
+        let top = top.0;
+        // rounding dust to the largest earner
+        self.validators[top].delegated_sol = self.validators[top].delegated_sol.saturating_add(yield_sol - credited);
     }
 
-    pub fn keeper_index(&self, k: &Pubkey) -> Option<usize> {
-        let n = (self.keeper_count as usize).min(MAX_KEEPERS);
-        for i in 0..n {
-            if self.keepers[i] == *k {
-                return Some(i);
+    /// Largest |actual - target| across validators, in bps of the book.
+    pub fn max_drift_bps(&self, total_sol: u64) -> u16 {
+        if total_sol == 0 {
+            return 0;
+        }
+        let mut max = 0u64;
+        for i in 0..self.len() {
+            let d = self.validators[i].delegated_sol;
+            let t = self.target_sol(i, total_sol);
+            max = max.max(d.abs_diff(t));
+        }
+        ((max as u128) * (BPS_DENOM as u128) / (total_sol as u128)).min(BPS_DENOM as u128) as u16
+    }
+
+    /// Route a deposit: largest shortfall vs target first, then weighted validators with cap headroom.
+    pub fn allocate(&mut self, amount_sol: u64) -> Result<()> {
+        let n = self.len();
+        if n == 0 || amount_sol == 0 {
+            return Ok(());
+        }
+        let total_after = self.total_delegated_sol().checked_add(amount_sol).ok_or(ErrorCode::MathOverflow)?;
+        let mut remaining = amount_sol;
+
+        for _ in 0..(2 * MAX_VALIDATORS) {
+            if remaining == 0 {
+                break;
+            }
+            let mut best: Option<(usize, u64)> = None;
+            for i in 0..n {
+                let v = &self.validators[i];
+                let gap = self.target_sol(i, total_after).saturating_sub(v.delegated_sol).min(v.headroom_sol());
+                if gap > best.map_or(0, |(_, g)| g) {
+                    best = Some((i, gap));
+                }
             }
+            if best.is_none() {
+                for i in 0..n {
+                    let v = &self.validators[i];
+                    let h = v.headroom_sol();
+                    if v.target_weight_bps > 0 && h > best.map_or(0, |(_, b)| b) {
+                        best = Some((i, h));
+                    }
+                }
+            }
+            let Some((i, room)) = best else {
+                break;
+            };
+            let take = room.min(remaining);
+            self.validators[i].delegated_sol += take;
+            remaining -= take;
         }
-        None
-    }
 
-    pub fn require_keeper_feeder(&self, k: &Pubkey) -> Result<()> {
-        require!(
-            self.is_keeper(k) || *k == self.keeper_admin || *k == self.authority,
-            ErrorCode::Unauthorized
-        );
+        require!(remaining == 0, ErrorCode::ValidatorCapExceeded);
         Ok(())
     }
 
-    pub fn require_keeper_rate_limit_ok(&self, k: &Pubkey) -> Result<()> {
-        if let Some(i) = self.keeper_index(k) {
-            if self.keeper_bond_required_lamports > 0 {
-                require!(
-                    self.keeper_bond_deposited_lamports[i] >= self.keeper_bond_required_lamports,
-                    ErrorCode::KeeperBondInsufficient
-                );
+    /// Route an exit: most overweight validators first, then the largest delegations.
+    pub fn deallocate(&mut self, amount_sol: u64) {
+        let n = self.len();
+        let total = self.total_delegated_sol();
+        let mut remaining = amount_sol.min(total);
+        let total_after = total - remaining;
+
+        for _ in 0..(2 * MAX_VALIDATORS) {
+            if remaining == 0 {
+                break;
             }
-            require!(
-                self.keeper_updates_this_epoch[i] < self.max_updates_per_epoch,
-                ErrorCode::KeeperRateLimited
-            );
+            let mut best: Option<(usize, u64)> = None;
+            for i in 0..n {
+                let excess = self.validators[i].delegated_sol.saturating_sub(self.target_sol(i, total_after));
+                if excess > best.map_or(0, |(_, e)| e) {
+                    best = Some((i, excess));
+                }
+            }
+            if best.is_none() {
+                for i in 0..n {
+                    let d = self.validators[i].delegated_sol;
+                    if d > best.map_or(0, |(_, b)| b) {
+                        best = Some((i, d));
+                    }
+                }
+            }
+            let Some((i, avail)) = best else {
+                break;
+            };
+            let take = avail.min(remaining);
+            self.validators[i].delegated_sol -= take;
+            remaining -= take;
         }
-        Ok(())
     }
 
-    pub fn bump_keeper_heartbeat_and_updates(&mut self, keeper: &Pubkey, slot: u64) -> Result<()> {
-        if let Some(i) = self.keeper_index(keeper) {
-            self.keeper_heartbeat_slot[i] = slot;
-            self.keeper_updates_this_epoch[i] = self.keeper_updates_this_epoch[i].saturating_add(1);
+    /// Next rebalance move: (most overweight, most underweight with headroom, amount).
+    pub fn next_move(&self, total_sol: u64) -> Option<(usize, usize, u64)> {
+        let mut over: Option<(usize, u64)> = None;
+        let mut under: Option<(usize, u64)> = None;
+        for i in 0..self.len() {
+            let v = &self.validators[i];
+            let t = self.target_sol(i, total_sol);
+            if v.delegated_sol > t {
+                let e = v.delegated_sol - t;
+                if e > over.map_or(0, |(_, x)| x) {
+                    over = Some((i, e));
+                }
+            } else {
+                let d = (t - v.delegated_sol).min(v.headroom_sol());
+                if d > under.map_or(0, |(_, x)| x) {
+                    under = Some((i, d));
+                }
+            }
+        }
+        match (over, under) {
+            (Some((from, e)), Some((to, d))) => Some((from, to, e.min(d))),
+            _ => None,
         }
-        Ok(())
     }
 
-    pub fn add_keeper(&mut self, keeper: Pubkey) -> Result<()> {
-        if self.is_keeper(&keeper) {
-            return Ok(());
+    /// Order-preserving remove
+    pub fn remove_validator_at(&mut self, idx: usize) {
+        let n = self.len();
+        if idx >= n {
+            return;
         }
-        let n = self.keeper_count as usize;
-        require!(n < MAX_KEEPERS, ErrorCode::InvalidParams);
-        self.keepers[n] = keeper;
-        self.keeper_heartbeat_slot[n] = 0;
-        self.keeper_miss_count[n] = 0;
-        self.keeper_updates_this_epoch[n] = 0;
-        self.keeper_bond_deposited_lamports[n] = 0;
-        self.keeper_count = (n + 1) as u8;
-        Ok(())
+        for i in idx..(n - 1) {
+            self.validators[i] = self.validators[i + 1];
+        }
+        self.validators[n - 1] = ValidatorSlot::default();
+        self.validator_count = (n - 1) as u8;
     }
+}
 
-    pub fn remove_keeper(&mut self, keeper: Pubkey) -> Result<()> {
-        let n = self.keeper_count as usize;
-        if n == 0 {
+/// Multi-LST asset book (PDA: ["asset_book", vault]). Each LST has its own Pyth feed, beta
+/// and return ring; target weights come from per-asset realized vol.
+#[account]
+pub struct AssetBook {
+    pub vault: Pubkey,
+    pub bump: u8,
+    pub asset_count: u8,
+    pub weighting_mode: u8,
+    pub pending_request_id: u64, // hedge request whose rebalance intent awaits confirm
+    pub assets: [AssetSlot; MAX_ASSETS],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct AssetSlot {
+    pub mint: Pubkey,
+    pub price_feed: Pubkey,
+    pub lst_beta_fp: i64,
+
+    // oracle
+    pub price_fp: i64,
+    pub last_price_fp: i64,
+    pub last_publish_time: u64,
+    pub price_ok: bool,
+
+    // returns
+    pub returns_ring: [ReturnSample; N_RETURNS],
+    pub returns_idx: u8,
+    pub nonzero_samples: u16,
+    pub last_return_slot: u64,
+    pub last_return_ts: i64,
+    pub realized_vol_bps: u16,
+
+    // allocation
+    pub target_weight_bps: u16,
+    pub allocated_sol: u64,
+}
+
+impl AssetSlot {
+    pub const SPACE: usize = 32 + 32 + 8 + 8 + 8 + 8 + 1 + (ReturnSample::SPACE * N_RETURNS) + 1 + 2 + 8 + 8 + 2 + 2 + 8;
+
+    /// Same return definition, spacing gate and elapsed-time stamp as the vault's SOL ring.
+    pub fn try_record_return(
+        &mut self,
+        slot: u64,
+        price_fp: i64,
+        publish_ts: i64,
+        min_spacing_slots: u64,
+        return_kind: u8,
+    ) -> Result<()> {
+        if self.last_return_slot != 0 && slot.saturating_sub(self.last_return_slot) < min_spacing_slots {
             return Ok(());
         }
-        let mut idx: Option<usize> = None;
-        for i in 0..n.min(MAX_KEEPERS) {
-            if self.keepers[i] == keeper {
-                idx = Some(i);
-                break;
-            }
+        if self.last_price_fp <= 0 || self.last_return_ts <= 0 {
+            self.last_price_fp = price_fp;
+            self.last_return_ts = publish_ts;
+            self.last_return_slot = slot;
+            return Ok(());
         }
-        if idx.is_none() {
+        let elapsed_secs = publish_ts.saturating_sub(self.last_return_ts);
+        if elapsed_secs <= 0 {
             return Ok(());
         }
-        let i = idx.unwrap();
-        let last = n - 1;
 
-        self.keepers[i] = self.keepers[last];
-        self.keeper_heartbeat_slot[i] = self.keeper_heartbeat_slot[last];
-        self.keeper_miss_count[i] = self.keeper_miss_count[last];
-        self.keeper_updates_this_epoch[i] = self.keeper_updates_this_epoch[last];
-        self.keeper_bond_deposited_lamports[i] = self.keeper_bond_deposited_lamports[last];
+        let ret_i32 = compute_return_fp(price_fp, self.last_price_fp, return_kind)?;
 
-        self.keepers[last] = Pubkey::default();
-        self.keeper_heartbeat_slot[last] = 0;
-        self.keeper_miss_count[last] = 0;
-        self.keeper_updates_this_epoch[last] = 0;
-        self.keeper_bond_deposited_lamports[last] = 0;
+        let idx = (self.returns_idx as usize) % N_RETURNS;
+        let prev = self.returns_ring[idx].ret_fp;
+        self.returns_ring[idx] = ReturnSample {
+            ret_fp: ret_i32,
+            elapsed_secs: elapsed_secs.min(u32::MAX as i64) as u32,
+        };
+        self.returns_idx = self.returns_idx.wrapping_add(1);
+        if prev == 0 && ret_i32 != 0 {
+            self.nonzero_samples = self.nonzero_samples.saturating_add(1);
+        } else if prev != 0 && ret_i32 == 0 {
+            self.nonzero_samples = self.nonzero_samples.saturating_sub(1);
+        }
 
-        self.keeper_count = last as u8;
+        self.last_price_fp = price_fp;
+        self.last_return_ts = publish_ts;
+        self.last_return_slot = slot;
         Ok(())
     }
+}
 
-    pub fn set_hedge_notional_checked(&mut self, hedge: i64) -> Result<()> {
-        let abs = abs_i64(hedge);
-        require!(abs <= self.max_abs_hedge_notional_usd, ErrorCode::CapExceeded);
-        self.hedge_notional_usd = hedge;
-        self.enforce_leverage_guardrail()?;
-        Ok(())
+impl AssetBook {
+    pub const SPACE: usize = 8
+        + 32
+        + 1
+        + 1
+        + 1
+        + 8
+        + (AssetSlot::SPACE * MAX_ASSETS);
+
+    fn len(&self) -> usize {
+        (self.asset_count as usize).min(MAX_ASSETS)
     }
 
-    pub fn enforce_leverage_guardrail(&self) -> Result<()> {
-        if self.staked_sol == 0 {
-            require!(self.hedge_notional_usd == 0, ErrorCode::LeverageExceeded);
-            return Ok(());
+    pub fn asset_index(&self, mint: &Pubkey) -> Option<usize> {
+        (0..self.len()).find(|&i| self.assets[i].mint == *mint)
+    }
+
+    /// Vol used for weighting: the asset's own realized vol, else beta x SOL vol until its
+    /// ring has min_samples.
+    fn weighting_vol_bps(&self, i: usize, sol_vol_bps: u16) -> u128 {
+        let a = &self.assets[i];
+        if a.realized_vol_bps > 0 {
+            return a.realized_vol_bps as u128;
         }
-        let max_per_sol = self.max_hedge_per_sol_usd_fp as i128;
-        require!(max_per_sol > 0, ErrorCode::InvalidParams);
+        let proxy = (sol_vol_bps as u128) * (a.lst_beta_fp.max(0) as u128) / (PRICE_FP_SCALE as u128);
+        proxy.max(1)
+    }
 
-        let lhs = abs_i64(self.hedge_notional_usd) as i128;
-        let rhs = (self.staked_sol as i128)
-            .checked_mul(max_per_sol)
-            .ok_or(ErrorCode::MathOverflow)?
-            / (PRICE_FP_SCALE as i128);
+    /// Target weights (bps, sum 10_000) by inverse volatility or risk parity.
+    /// Risk parity uses a one-factor (SOL) covariance: cov_ij = b_i b_j s_m^2 + idio_i^2 [i == j].
+    pub fn compute_target_weights(&self, sol_vol_bps: u16) -> Result<[u16; MAX_ASSETS]> {
+        let n = self.len();
+        let mut vols = [0u128; MAX_ASSETS];
+        for (i, v) in vols.iter_mut().enumerate().take(n) {
+            *v = self.weighting_vol_bps(i, sol_vol_bps);
+        }
 
-        require!(lhs <= rhs, ErrorCode::LeverageExceeded);
-        Ok(())
+        // inverse vol (also the risk-parity starting point)
+        let mut w = [0u128; MAX_ASSETS];
+        for i in 0..n {
+            w[i] = WEIGHT_FP_SCALE * WEIGHT_FP_SCALE / vols[i];
+        }
+        normalize_weights(&mut w, n);
+
+        if self.weighting_mode == WeightingMode::RiskParity as u8 {
+            let mut sys = [0u128; MAX_ASSETS];
+            let mut idio_var = [0u128; MAX_ASSETS];
+            for i in 0..n {
+                sys[i] = (self.assets[i].lst_beta_fp.max(0) as u128) * (sol_vol_bps as u128) / (PRICE_FP_SCALE as u128);
+                idio_var[i] = (vols[i] * vols[i]).saturating_sub(sys[i] * sys[i]);
+            }
+            // fixed point of w_i = sqrt(w_i / (Cov w)_i): equal risk contributions
+            for _ in 0..RISK_PARITY_ITERS {
+                let mut next = [0u128; MAX_ASSETS];
+                for i in 0..n {
+                    let mut cw: u128 = 0;
+                    for j in 0..n {
+                        let cov = sys[i] * sys[j] + if i == j { idio_var[i] } else { 0 };
+                        cw = cw.checked_add(cov.checked_mul(w[j]).ok_or(ErrorCode::MathOverflow)?).ok_or(ErrorCode::MathOverflow)?;
+                    }
+                    let scaled = w[i].checked_mul(1_000_000_000_000_000_000).ok_or(ErrorCode::MathOverflow)?;
+                    next[i] = scaled.checked_div(cw).map_or(w[i], isqrt_u128);
+                }
+                w = next;
+                normalize_weights(&mut w, n);
+            }
+        }
+
+        let mut out = [0u16; MAX_ASSETS];
+        let mut sum: u32 = 0;
+        let mut largest = 0usize;
+        for i in 0..n {
+            out[i] = (w[i] * (BPS_DENOM as u128) / WEIGHT_FP_SCALE) as u16;
+            sum += out[i] as u32;
+            if out[i] > out[largest] {
+                largest = i;
+            }
+        }
+        if n > 0 {
+            // rounding dust to the largest weight so targets sum to exactly 10_000
+            out[largest] += (BPS_DENOM as u32).saturating_sub(sum) as u16;
+        }
+        Ok(out)
     }
 
-    pub fn enforce_reserve_ratio(&self) -> Result<()> {
-        let req = (self.staked_sol as u128)
-            .checked_mul(self.min_reserve_bps as u128)
-            .ok_or(ErrorCode::MathOverflow)?
-            / (BPS_DENOM as u128);
-        require!((self.reserve_sol as u128) >= req, ErrorCode::ReserveTooLow);
-        Ok(())
+    /// Weight-averaged beta (fp 1e6).
+    pub fn blended_beta_fp(&self, weights_bps: &[u16; MAX_ASSETS]) -> i64 {
+        let mut acc: i128 = 0;
+        for (a, &w) in self.assets[..self.len()].iter().zip(weights_bps) {
+            acc += (a.lst_beta_fp as i128) * (w as i128);
+        }
+        (acc / (BPS_DENOM as i128)) as i64
     }
 
-    pub fn expected_carry_bps(&self) -> i32 {
-        self.staking_bps_per_day
-            .saturating_add(self.funding_bps_per_day)
-            .saturating_sub(self.borrow_bps_per_day)
+    /// Current allocation weights (bps of allocated SOL).
+    pub fn current_weights_bps(&self) -> [u16; MAX_ASSETS] {
+        let mut out = [0u16; MAX_ASSETS];
+        let total: u128 = (0..self.len()).map(|i| self.assets[i].allocated_sol as u128).sum();
+        if total == 0 {
+            return out;
+        }
+        for (o, a) in out.iter_mut().zip(&self.assets[..self.len()]) {
+            *o = ((a.allocated_sol as u128) * (BPS_DENOM as u128) / total) as u16;
+        }
+        out
     }
 
-    pub fn try_record_oracle_return(&mut self, slot: u64, price_fp: i64) -> Result<()> {
-        if self.last_return_slot != 0 {
-            let elapsed = slot.checked_sub(self.last_return_slot).unwrap_or(0);
-            if elapsed < self.min_return_spacing_slots {
-                return Ok(());
-            }
+    /// Book staked_sol across assets at target_weight_bps (dust to the last asset).
+    pub fn apply_target_allocations(&mut self, staked_sol: u64) {
+        let n = self.len();
+        let mut assigned: u64 = 0;
+        for i in 0..n {
+            let a = if i + 1 == n {
+                staked_sol - assigned
+            } else {
+                ((staked_sol as u128) * (self.assets[i].target_weight_bps as u128) / (BPS_DENOM as u128)) as u64
+            };
+            self.assets[i].allocated_sol = a;
+            assigned += a;
         }
+    }
+}
 
-        if self.last_oracle_price_fp <= 0 {
-            self.last_oracle_price_fp = price_fp;
-            self.last_return_slot = slot;
-            return Ok(());
+/// Scale the first n weights to sum to WEIGHT_FP_SCALE.
+fn normalize_weights(w: &mut [u128; MAX_ASSETS], n: usize) {
+    let sum: u128 = w[..n].iter().sum();
+    if sum == 0 {
+        for x in w[..n].iter_mut() {
+            *x = WEIGHT_FP_SCALE / (n as u128);
         }
+        return;
+    }
+    for x in w[..n].iter_mut() {
+        *x = *x * WEIGHT_FP_SCALE / sum;
+    }
+}
 
-        let p = price_fp as i128;
-        let p0 = self.last_oracle_price_fp as i128;
-        let diff = p.checked_sub(p0).ok_or(ErrorCode::MathOverflow)?;
+/// One oracle return and the publish-time gap it spans (`elapsed_secs == 0`: unfilled slot)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct ReturnSample {
+    pub ret_fp: i32, // RET_FP_SCALE
+    pub elapsed_secs: u32,
+}
 
-        let mut ret = diff
-            .checked_mul(RET_FP_SCALE as i128)
-            .ok_or(ErrorCode::MathOverflow)?
-            / p0.max(1);
+impl ReturnSample {
+    pub const SPACE: usize = 4 + 4;
+}
 
-        if ret > (MAX_RETURN_ABS_FP as i128) {
-            ret = MAX_RETURN_ABS_FP as i128;
-        } else if ret < -(MAX_RETURN_ABS_FP as i128) {
-            ret = -(MAX_RETURN_ABS_FP as i128);
-        }
-        let ret_i32 = ret as i32;
+/// Oracle returns ring (PDA: ["returns_ring", vault]); `samples.len() == capacity`.
+/// Estimators read the newest `VaultState.returns_window` samples.
+#[account]
+pub struct ReturnsRing {
+    pub vault: Pubkey,
+    pub bump: u8,
+    pub capacity: u16,
+    pub head: u16,            // next write index
+    pub nonzero_samples: u16, // across the whole ring
+    pub samples: Vec<ReturnSample>,
+}
 
-        let idx = (self.returns_idx as usize) % N_RETURNS;
-        let prev = self.returns_ring[idx];
-        self.returns_ring[idx] = ret_i32;
-        self.returns_idx = self.returns_idx.wrapping_add(1);
+impl ReturnsRing {
+    pub fn space(capacity: u16) -> usize {
+        8 + 32 + 1 + 2 + 2 + 2 + (4 + ReturnSample::SPACE * capacity as usize)
+    }
 
-        if prev == 0 && ret_i32 != 0 {
+    /// Overwrite the oldest sample; returns the index written
+    pub fn push(&mut self, sample: ReturnSample) -> Result<u16> {
+        let idx = self.head;
+        let prev = self.samples[idx as usize].ret_fp;
+        self.samples[idx as usize] = sample;
+        self.head = (idx + 1) % self.capacity;
+
+        if prev == 0 && sample.ret_fp != 0 {
             self.nonzero_samples = self.nonzero_samples.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
-        } else if prev != 0 && ret_i32 == 0 {
+        } else if prev != 0 && sample.ret_fp == 0 {
             self.nonzero_samples = self.nonzero_samples.checked_sub(1).ok_or(ErrorCode::MathOverflow)?;
         }
+        Ok(idx)
+    }
 
-        if self.vol_mode == VolMode::Ewma as u8 {
-            let r_abs: i64 = if ret_i32 < 0 { -(ret_i32 as i64) } else { ret_i32 as i64 };
-            let r2: u128 = (r_abs as u128).checked_mul(r_abs as u128).ok_or(ErrorCode::MathOverflow)?;
-            let r2_clamped = r2.min(MAX_VAR_FP2);
-            self.ewma_var_fp2 = ewma_update_u128(self.ewma_var_fp2, r2_clamped, self.ewma_alpha_bps)?;
-        }
+    /// Newest `window` samples (capped at capacity), oldest first
+    pub fn window(&self, window: u16) -> Vec<ReturnSample> {
+        let cap = self.capacity as usize;
+        let n = (window as usize).min(cap);
+        let start = self.head as usize + cap - n;
+        (0..n).map(|k| self.samples[(start + k) % cap]).collect()
+    }
+}
 
-        self.last_return_slot = slot;
-        self.last_oracle_price_fp = price_fp;
+/// Authority-set price (PDA: ["manual_price", vault]); fp 1e6, publish_time in unix seconds
+#[account]
+pub struct ManualPriceFeed {
+    pub vault: Pubkey,
+    pub bump: u8,
+    pub price_fp: i64,
+    pub ema_fp: i64,
+    pub conf_fp: i64,
+    pub publish_time: i64,
+}
 
-        emit!(OracleReturnRecorded {
-            epoch: self.epoch,
-            slot,
-            idx: idx as u8,
-            return_fp: ret_i32,
-            nonzero_samples: self.nonzero_samples,
-            oracle_price_fp: price_fp,
-        });
+impl ManualPriceFeed {
+    pub const SPACE: usize = 8 + 32 + 1 + 8 + 8 + 8 + 8;
+}
 
-        Ok(())
+/// One OHLC bar of accepted oracle prices (fp 1e6)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct OhlcBar {
+    pub start_ts: i64, // interval start (aligned to bar_secs)
+    pub open_fp: i64,
+    pub high_fp: i64,
+    pub low_fp: i64,
+    pub close_fp: i64,
+    pub obs_count: u16,
+}
+
+impl OhlcBar {
+    pub const SPACE: usize = 8 + 8 + 8 + 8 + 8 + 2;
+}
+
+/// OHLC bars (PDA: ["price_bars", vault]); `current` is the open bar, completed
+/// bars go to the ring (`bars.len() == capacity`).
+#[account]
+pub struct PriceBars {
+    pub vault: Pubkey,
+    pub bump: u8,
+    pub bar_secs: u32,
+    pub capacity: u16,
+    pub head: u16,      // next write index
+    pub bar_count: u16, // completed bars stored (<= capacity)
+    pub current: OhlcBar,
+    pub bars: Vec<OhlcBar>,
+}
+
+impl PriceBars {
+    pub fn space(capacity: u16) -> usize {
+        8 + 32 + 1 + 4 + 2 + 2 + 2 + OhlcBar::SPACE + (4 + OhlcBar::SPACE * capacity as usize)
     }
 
-    pub fn staked_value_usd(&self) -> Result<i64> {
-        if self.staked_sol == 0 {
-            return Ok(0);
+    /// Fold an accepted price into the open bar. Returns the bar it closed, if
+    /// `publish_ts` falls past the open bar's interval. Out-of-order prices are ignored.
+    pub fn observe(&mut self, price_fp: i64, publish_ts: i64) -> Option<OhlcBar> {
+        let secs = self.bar_secs as i64;
+        let start_ts = publish_ts - publish_ts.rem_euclid(secs);
+        let mut closed = None;
+
+        if self.current.obs_count > 0 {
+            if start_ts < self.current.start_ts {
+                return None;
+            }
+            if start_ts == self.current.start_ts {
+                let bar = &mut self.current;
+                bar.high_fp = bar.high_fp.max(price_fp);
+                bar.low_fp = bar.low_fp.min(price_fp);
+                bar.close_fp = price_fp;
+                bar.obs_count = bar.obs_count.saturating_add(1);
+                return None;
+            }
+
+            let idx = self.head as usize;
+            self.bars[idx] = self.current;
+            self.head = (self.head + 1) % self.capacity;
+            self.bar_count = (self.bar_count + 1).min(self.capacity);
+            closed = Some(self.current);
         }
-        let p = self.oracle_price_fp;
-        require!(p > 0, ErrorCode::OracleNotReady);
-        let v = (self.staked_sol as i128)
-            .checked_mul(p as i128)
-            .ok_or(ErrorCode::MathOverflow)?
-            / (PRICE_FP_SCALE as i128);
-        Ok(v.min(i64::MAX as i128) as i64)
+
+        self.current = OhlcBar {
+            start_ts,
+            open_fp: price_fp,
+            high_fp: price_fp,
+            low_fp: price_fp,
+            close_fp: price_fp,
+            obs_count: 1,
+        };
+        closed
     }
 
-    pub fn reserve_value_usd(&self) -> Result<i64> {
-        if self.reserve_sol == 0 {
-            return Ok(0);
-        }
-        let p = self.oracle_price_fp;
-        require!(p > 0, ErrorCode::OracleNotReady);
-        let v = (self.reserve_sol as i128)
-            .checked_mul(p as i128)
-            .ok_or(ErrorCode::MathOverflow)?
-            / (PRICE_FP_SCALE as i128);
-        Ok(v.min(i64::MAX as i128) as i64)
+    /// Completed bars with a range (single-observation bars carry none) that started
+    /// within the last `capacity` bar intervals, so bars left over a feed gap age out.
+    pub fn usable_bars(&self, now_ts: i64) -> impl Iterator<Item = &OhlcBar> {
+        let oldest_ts = now_ts.saturating_sub((self.capacity as i64) * (self.bar_secs as i64));
+        self.bars.iter().filter(move |b| b.obs_count >= 2 && b.start_ts >= oldest_ts)
     }
 
-    pub fn unrealized_pnl_usd(&self) -> Result<i64> {
-        Ok(0)
+    pub fn usable_bar_count(&self, now_ts: i64) -> u16 {
+        self.usable_bars(now_ts).count() as u16
     }
+}
 
-    pub fn compute_nav_usd(&self) -> Result<i64> {
-        let st = self.staked_value_usd()?;
-        let rs = self.reserve_value_usd()?;
-        let pnl = self.unrealized_pnl_usd()?;
-        Ok(st
-            .checked_add(rs)
-            .ok_or(ErrorCode::MathOverflow)?
-            .checked_add(pnl)
-            .ok_or(ErrorCode::MathOverflow)?
-            .checked_add(self.staking_accrued_usd)
-            .ok_or(ErrorCode::MathOverflow)?)
+/// Unstake pool (PDA: ["withdraw_queue", vault]). Tickets are their own PDAs; matured
+/// tickets share one pool that fills pro-rata, so a ticket's filled part follows from the
+/// pool's fill factor since it matured.
+#[account]
+pub struct WithdrawQueue {
+    pub vault: Pubkey,
+    pub bump: u8,
+    pub next_ticket_id: u64,
+    pub open_tickets: u64,
+    pub fill_factor: u128,    // unfilled fraction of the matured pool (FILL_FACTOR_ONE = none filled)
+    pub fill_generation: u64, // bumped when the pool is filled in full (factor restarts)
+}
+
+impl WithdrawQueue {
+    pub const SPACE: usize = 8 + 32 + 1 + 8 + 8 + 16 + 8;
+}
+
+/// Unstake ticket (PDA: ["unstake_ticket", vault, id (u64 LE)])
+#[account]
+pub struct UnstakeTicket {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub id: u64,
+    pub bump: u8,
+    pub amount_sol: u64,
+    pub claimed_sol: u64,
+    pub request_epoch: u64,
+    pub claimable_epoch: u64,
+    pub matured: bool,
+    pub matured_fill_factor: u128,
+    pub matured_generation: u64,
+}
+
+impl UnstakeTicket {
+    pub const SPACE: usize = 8 + 32 + 32 + 8 + 1 + 8 + 8 + 8 + 8 + 1 + 16 + 8;
+
+    /// Filled part of the ticket, from the pool's fill since it matured. The unfilled
+    /// remainder rounds up, so the sum over tickets never exceeds what the pool paid.
+    pub fn filled_sol(&self, queue: &WithdrawQueue) -> u64 {
+        if !self.matured {
+            return 0;
+        }
+        if queue.fill_generation != self.matured_generation || self.matured_fill_factor == 0 {
+            return self.amount_sol;
+        }
+        let remaining = (self.amount_sol as u128)
+            .saturating_mul(queue.fill_factor)
+            .div_ceil(self.matured_fill_factor)
+            .min(self.amount_sol as u128) as u64;
+        self.amount_sol - remaining
     }
 }
 
-/// -------------------------------
-/// Initialize Params
-/// -------------------------------
+// -------------------------------
+// Initialize Params
+// -------------------------------
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct InitializeParams {
@@ -1854,6 +6025,10 @@ pub struct InitializeParams {
     pub max_price_age_slots: u64, // interpreted as max_age_seconds in this impl
     pub max_confidence_bps: u16,
     pub max_price_jump_bps: u16,
+    // accounts update_oracle_price accepts (see set_oracle_accounts)
+    pub oracle_sol_usd: Pubkey,
+    pub oracle_sol_usdc: Pubkey,
+    pub oracle_switchboard_feed: Pubkey, // Pubkey::default() = none
 
     // hedge sizing
     pub target_delta_bps: u16,
@@ -1865,14 +6040,53 @@ pub struct InitializeParams {
     // circuit breaker extreme drift
     pub extreme_drift_bps: u16,
 
-    // keeper controls
-    pub max_updates_per_epoch: u16,
-    pub keeper_bond_required_lamports: u64,
+    // keeper controls
+    pub max_updates_per_epoch: u16,
+    pub keeper_bond_required_lamports: u64,
+
+    // unstake queue
+    pub unstake_cooldown_epochs: u64,
+
+    // identity: vault PDA = ["vault", authority, vault_index (u16 LE)]
+    pub vault_index: u16,
+    pub name: [u8; VAULT_NAME_LEN],
+}
+
+// -------------------------------
+// Events
+// -------------------------------
+
+#[event]
+pub struct VaultRegistered {
+    pub slot: u64,
+    pub vault: Pubkey,
+    pub authority: Pubkey,
+    pub vault_index: u16,
+    pub name: [u8; VAULT_NAME_LEN],
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+    pub entry_index: u16,
+    pub vault_count: u16,
+}
+
+#[event]
+pub struct VaultDeregistered {
+    pub slot: u64,
+    pub vault: Pubkey,
+    pub authority: Pubkey,
+    pub vault_count: u16,
 }
 
-/// -------------------------------
-/// Events
-/// -------------------------------
+#[event]
+pub struct VaultMigrated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub vault: Pubkey,
+    pub old_version: u8,
+    pub new_version: u8,
+    pub old_len: u32,
+    pub new_len: u32,
+}
 
 #[event]
 pub struct VaultInitialized {
@@ -1918,6 +6132,10 @@ pub struct VaultInitialized {
 
     pub max_updates_per_epoch: u16,
     pub keeper_bond_required_lamports: u64,
+
+    pub unstake_cooldown_epochs: u64,
+    pub vault_index: u16,
+    pub name: [u8; VAULT_NAME_LEN],
 }
 
 #[event]
@@ -1929,6 +6147,149 @@ pub struct StakeAllocated {
     pub reserve_sol: u64,
 }
 
+#[event]
+pub struct ValidatorUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub vote_account: Pubkey,
+    pub target_weight_bps: u16,
+    pub cap_sol: u64, // 0 when removed
+    pub total_weight_bps: u16,
+    pub validator_count: u8,
+}
+
+#[event]
+pub struct ValidatorApyUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub vote_account: Pubkey,
+    pub apy_bps: u16,
+    pub weighted_apy_bps: u16,
+}
+
+#[event]
+pub struct DelegationMove {
+    pub epoch: u64,
+    pub slot: u64,
+    pub from_vote_account: Pubkey,
+    pub to_vote_account: Pubkey,
+    pub amount_sol: u64,
+}
+
+#[event]
+pub struct DelegationsRebalanced {
+    pub epoch: u64,
+    pub slot: u64,
+    pub max_drift_bps: u16,
+    pub rebalance_drift_bps: u16,
+    pub move_count: u8,
+    pub moved_sol: u64,
+    pub total_delegated_sol: u64,
+    pub staked_sol: u64,
+}
+
+#[event]
+pub struct UserPositionOpened {
+    pub epoch: u64,
+    pub slot: u64,
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+}
+
+#[event]
+pub struct SharesMinted {
+    pub epoch: u64,
+    pub slot: u64,
+    pub owner: Pubkey,
+    pub amount_sol: u64,
+    pub deposit_value_usd: i64,
+    pub nav_before_usd: i64,
+    pub shares_minted: u64,
+    pub dead_shares: u64,
+    pub nav_locked_shares: u64, // first mint only: shares backing NAV that predated it
+    pub position_shares: u64,
+    pub total_shares: u64,
+    pub position_cost_basis_usd: i64,
+}
+
+#[event]
+pub struct UnstakeEnqueued {
+    pub epoch: u64,
+    pub slot: u64,
+    pub owner: Pubkey,
+    pub ticket_id: u64,
+    pub shares_burned: u64,
+    pub amount_sol: u64,
+    pub from_reserve_sol: u64, // part of amount_sol taken from the reserve (staked_sol short)
+    pub value_usd: i64,
+    pub claimable_epoch: u64,
+    pub new_staked_sol: u64,
+    pub deactivating_sol: u64,
+    pub position_shares: u64,
+    pub total_shares: u64,
+    pub open_tickets: u64,
+}
+
+#[event]
+pub struct UnstakeTicketMatured {
+    pub epoch: u64,
+    pub slot: u64,
+    pub owner: Pubkey,
+    pub ticket_id: u64,
+    pub amount_sol: u64,
+    pub withdraw_owed_sol: u64,
+    pub deactivating_sol: u64,
+}
+
+#[event]
+pub struct UnstakeFulfilled {
+    pub epoch: u64,
+    pub slot: u64,
+    pub due_sol: u64,
+    pub filled_sol: u64,
+    pub fill_ratio_bps: u16,
+    pub fill_factor: u128,
+    pub fill_generation: u64,
+    pub reserve_sol: u64,
+    pub withdraw_owed_sol: u64,
+    pub deactivating_sol: u64,
+}
+
+#[event]
+pub struct UnstakeClaimed {
+    pub epoch: u64,
+    pub slot: u64,
+    pub owner: Pubkey,
+    pub ticket_id: u64,
+    pub amount_sol: u64,
+    pub fully_claimed: bool,
+    pub open_tickets: u64,
+}
+
+#[event]
+pub struct EmergencyWithdrawn {
+    pub epoch: u64,
+    pub slot: u64,
+    pub owner: Pubkey,
+    pub shares_burned: u64,
+    pub staked_out_sol: u64,
+    pub reserve_out_sol: u64,
+    pub new_staked_sol: u64,
+    pub reserve_sol: u64,
+    pub position_shares: u64,
+    pub total_shares: u64,
+}
+
+#[event]
+pub struct HedgeScaledDown {
+    pub epoch: u64,
+    pub slot: u64,
+    pub prev_hedge_notional_usd: i64,
+    pub hedge_notional_usd: i64,
+    pub target_hedge_notional_usd: i64,
+    pub staked_sol: u64,
+}
+
 #[event]
 pub struct ReserveUpdated {
     pub epoch: u64,
@@ -1954,6 +6315,64 @@ pub struct CarryInputsUpdated {
     pub expected_carry_bps: i32,
 }
 
+#[event]
+pub struct AssetAdded {
+    pub epoch: u64,
+    pub slot: u64,
+    pub asset_index: u8,
+    pub mint: Pubkey,
+    pub price_feed: Pubkey,
+    pub lst_beta_fp: i64,
+}
+
+#[event]
+pub struct AssetWeightingModeSet {
+    pub epoch: u64,
+    pub slot: u64,
+    pub weighting_mode: u8,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct AssetPriceUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub asset_index: u8,
+    pub mint: Pubkey,
+    pub price_fp: i64,
+    pub publish_time: u64,
+    pub price_ok: bool,
+    pub reason_code: u8,
+    pub realized_vol_bps: u16,
+    pub nonzero_samples: u16,
+}
+
+/// Emitted right after HedgeRequested when the asset book is passed
+#[event]
+pub struct AssetRebalanceIntent {
+    pub epoch: u64,
+    pub slot: u64,
+    pub request_id: u64,
+    pub weighting_mode: u8,
+    pub asset_count: u8,
+    pub mints: [Pubkey; MAX_ASSETS],
+    pub realized_vol_bps: [u16; MAX_ASSETS],
+    pub current_weight_bps: [u16; MAX_ASSETS],
+    pub target_weight_bps: [u16; MAX_ASSETS],
+    pub target_sol: [u64; MAX_ASSETS],
+    pub blended_beta_fp: i64,
+}
+
+#[event]
+pub struct AssetAllocationsUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub request_id: u64,
+    pub allocated_sol: [u64; MAX_ASSETS],
+    pub weight_bps: [u16; MAX_ASSETS],
+}
+
 #[event]
 pub struct OraclePriceUpdated {
     pub epoch: u64,
@@ -1965,14 +6384,104 @@ pub struct OraclePriceUpdated {
     pub oracle_publish_slot: u64, // publish_time seconds in this impl
     pub oracle_ok: bool,
     pub oracle_degraded: bool,
+    // USDC/USD applied to the SOL/USDC leg (1e6 = 1:1, also when no feed is pinned);
+    // USDC_USD_NOT_READ when a feed is pinned but this update didn't read the SOL/USDC leg
+    pub implied_usdc_usd_fp: i64,
+}
+
+#[event]
+pub struct UsdcCrossRateUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub usdc_usd: Pubkey,
+    pub prev_usdc_usd: Pubkey,
+    pub usdc_usd_feed_id: [u8; 32],
+    pub max_depeg_bps: u16,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct OracleAccountsUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub sol_usd: Pubkey,
+    pub sol_usdc: Pubkey,
+    pub switchboard_feed: Pubkey,
+    pub prev_sol_usd: Pubkey,
+    pub prev_sol_usdc: Pubkey,
+    pub prev_switchboard_feed: Pubkey,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct PythPullConfigUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub enabled: bool,
+    pub sol_usd_feed_id: [u8; 32],
+    pub sol_usdc_feed_id: [u8; 32],
+    pub min_verification: u8,
+    pub min_signatures: u8,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct OracleAggregationUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub feed_mask: u8,
+    pub min_feeds: u8,
+    pub max_spread_bps: u16,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct OracleAggregated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub feeds_read: u8,
+    pub feeds_used: u8, // passed staleness / confidence / jump
+    pub median_price_fp: i64,
+    pub spread_bps: u16, // (max - min) / median over the used feeds
+    pub max_spread_bps: u16,
+    pub ok: bool,
+    pub reason_code: u8,
+}
+
+#[event]
+pub struct ManualPriceSet {
+    pub epoch: u64,
+    pub slot: u64,
+    pub price_fp: i64,
+    pub ema_fp: i64,
+    pub conf_fp: i64,
+    pub publish_time: i64,
+}
+
+#[event]
+pub struct PriceBarClosed {
+    pub epoch: u64,
+    pub slot: u64,
+    pub start_ts: i64,
+    pub open_fp: i64,
+    pub high_fp: i64,
+    pub low_fp: i64,
+    pub close_fp: i64,
+    pub obs_count: u16,
+    pub bar_count: u16,
 }
 
 #[event]
 pub struct OracleReturnRecorded {
     pub epoch: u64,
     pub slot: u64,
-    pub idx: u8,
+    pub idx: u16,
     pub return_fp: i32,
+    pub elapsed_secs: u32,
     pub nonzero_samples: u16,
     pub oracle_price_fp: i64,
 }
@@ -1996,6 +6505,11 @@ pub struct EpochUpdated {
     pub realized_updated: bool,
     pub nonzero_samples: u16,
     pub oracle_degraded: bool,
+    pub vol_forecast_bps: u16,      // Garch mode; 0 otherwise
+    pub forecast_horizon_steps: u64, // 0 when no forecast was computed this epoch
+    pub effective_samples: u16,      // recorded samples (dt-weighted) or usable bars
+    pub vol_ci_low_bps: u16,         // 95% interval on realized_vol_bps;
+    pub vol_ci_high_bps: u16,        // [0, MAX_ANNUAL_VOL_BPS] when not updated this epoch
 }
 
 #[event]
@@ -2038,11 +6552,22 @@ pub struct NavSnapshot {
     pub staked_value_usd: i64,
     pub reserve_value_usd: i64,
     pub unrealized_pnl_usd: i64,
+    pub realized_pnl_usd: i64,
+    pub hedge_carry_accrued_usd: i64,
     pub staking_accrued_usd: i64,
     pub oracle_price_fp: i64,
     pub oracle_ok: bool,
 }
 
+#[event]
+pub struct SharePriceUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub nav_usd: i64,
+    pub total_shares: u64,
+    pub share_price_fp: i64, // NAV (USD) per share, fp 1e6
+}
+
 #[event]
 pub struct VaultSnapshot {
     pub epoch: u64,
@@ -2119,6 +6644,9 @@ pub struct HedgeConfirmed {
     pub slippage_bps: u16,
     pub avg_fill_slippage_bps: u16,
     pub hedge_fill_count: u64,
+    pub hedge_entry_price_fp: i64,
+    pub realized_pnl_usd: i64,
+    pub hedge_realized_pnl_usd: i64,
 }
 
 #[event]
@@ -2130,6 +6658,22 @@ pub struct HedgeConfirmMissed {
     pub missed_confirms: u32,
 }
 
+#[event]
+pub struct SlashingApplied {
+    pub epoch: u64,
+    pub slot: u64,
+    pub validator_id: Pubkey,
+    pub slash_bps: u16,
+    pub loss_sol: u64,
+    pub reserve_used_sol: u64,
+    pub socialized_sol: u64, // cut from staked_sol after the reserve ran out
+    pub reserve_sol: u64,
+    pub staked_sol: u64,
+    pub required_reserve_sol: u64,
+    pub reserve_ratio_ok: bool,
+    pub auto_paused: bool,
+}
+
 #[event]
 pub struct PausedSet {
     pub epoch: u64,
@@ -2220,59 +6764,162 @@ pub struct VolModelUpdated {
     pub ewma_alpha_bps: u16,
     pub min_samples: u8,
     pub min_return_spacing_slots: u64,
+    pub returns_window: u16,
+    pub return_kind: u8,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct GarchParamsUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub omega_fp2: u64,
+    pub alpha_bps: u16,
+    pub beta_bps: u16,
+    pub long_run_vol_bps: u16,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct OracleConfigUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub oracle_feed_choice: u8,
+    pub max_price_age_slots: u64,
+    pub max_confidence_bps: u16,
+    pub max_price_jump_bps: u16,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct HedgeSizingUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub target_delta_bps: u16,
+    pub beta_fp: i64,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct RiskCapsUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub max_staked_sol: u64,
+    pub max_abs_hedge_notional_usd: i64,
+    pub max_hedge_per_sol_usd_fp: i64,
+    pub min_reserve_bps: u16,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct KeeperControlsUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub max_updates_per_epoch: u16,
+    pub keeper_bond_required_lamports: u64,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct ConfirmConfigUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub max_confirm_delay_slots: u64,
+    pub config_version: u64,
+    pub config_hash: [u8; 32],
+}
+
+#[event]
+pub struct StakingYieldAccrued {
+    pub epoch: u64,
+    pub slot: u64,
+    pub elapsed_secs: i64,
+    pub staking_bps_per_day: i32,
+    pub yield_sol: u64,
+    pub yield_usd: i64, // 0 when compounded
+    pub compounded: bool,
+    pub staked_sol: u64,
+    pub staking_accrued_usd: i64,
+}
+
+#[event]
+pub struct FeesAccrued {
+    pub epoch: u64,
+    pub slot: u64,
+    pub elapsed_secs: i64,
+    pub nav_usd: i64,
+    pub management_fee_usd: i64,
+    pub performance_fee_usd: i64,
+    pub fee_shares: u64,
+    pub share_price_fp: i64, // post-fee NAV per share
+    pub high_water_mark_fp: i64,
+    pub fee_shares_unclaimed: u64,
+    pub fee_recipient: Pubkey,
+}
+
+#[event]
+pub struct FeesUpdated {
+    pub epoch: u64,
+    pub slot: u64,
+    pub management_fee_bps: u16,
+    pub performance_fee_bps: u16,
+    pub fee_recipient: Pubkey,
     pub config_version: u64,
     pub config_hash: [u8; 32],
 }
 
 #[event]
-pub struct OracleConfigUpdated {
+pub struct FeeSharesClaimed {
     pub epoch: u64,
     pub slot: u64,
-    pub oracle_feed_choice: u8,
-    pub max_price_age_slots: u64,
-    pub max_confidence_bps: u16,
-    pub max_price_jump_bps: u16,
-    pub config_version: u64,
-    pub config_hash: [u8; 32],
+    pub fee_recipient: Pubkey,
+    pub shares: u64,
+    pub position_shares: u64,
 }
 
 #[event]
-pub struct HedgeSizingUpdated {
+pub struct CarryAccrued {
     pub epoch: u64,
     pub slot: u64,
-    pub target_delta_bps: u16,
-    pub beta_fp: i64,
-    pub config_version: u64,
-    pub config_hash: [u8; 32],
+    pub elapsed_secs: i64, // since the previous accrual
+    pub hedge_notional_usd: i64,
+    pub funding_bps_per_day: i32,
+    pub borrow_bps_per_day: i32,
+    pub epoch_carry_usd: i64, // accrued during the epoch just closed
+    pub hedge_carry_accrued_usd: i64,
 }
 
 #[event]
-pub struct RiskCapsUpdated {
+pub struct StakingCompoundUpdated {
     pub epoch: u64,
     pub slot: u64,
-    pub max_staked_sol: u64,
-    pub max_abs_hedge_notional_usd: i64,
-    pub max_hedge_per_sol_usd_fp: i64,
-    pub min_reserve_bps: u16,
+    pub compound_staking_yield: bool,
     pub config_version: u64,
     pub config_hash: [u8; 32],
 }
 
 #[event]
-pub struct KeeperControlsUpdated {
+pub struct UnstakeCooldownUpdated {
     pub epoch: u64,
     pub slot: u64,
-    pub max_updates_per_epoch: u16,
-    pub keeper_bond_required_lamports: u64,
+    pub unstake_cooldown_epochs: u64,
     pub config_version: u64,
     pub config_hash: [u8; 32],
 }
 
 #[event]
-pub struct ConfirmConfigUpdated {
+pub struct CustodyInitialized {
     pub epoch: u64,
     pub slot: u64,
-    pub max_confirm_delay_slots: u64,
+    pub custody_mint: Pubkey,
+    pub custody_token_account: Pubkey,
+    pub share_mint: Pubkey,
     pub config_version: u64,
     pub config_hash: [u8; 32],
 }
@@ -2299,9 +6946,9 @@ fn compute_reason_code(interval_ok: bool, drift_ok: bool) -> u8 {
     }
 }
 
-/// -------------------------------
-/// Errors
-/// -------------------------------
+// -------------------------------
+// Errors
+// -------------------------------
 
 #[error_code]
 pub enum ErrorCode {
@@ -2346,15 +6993,402 @@ pub enum ErrorCode {
     KeeperRateLimited,
     #[msg("Keeper bond insufficient")]
     KeeperBondInsufficient,
+
+    #[msg("Deposit too small to mint shares")]
+    ZeroShares,
+    #[msg("NAV must be positive to price shares")]
+    NavNotPositive,
+    #[msg("Insufficient shares")]
+    InsufficientShares,
+    #[msg("Insufficient vault liquidity")]
+    InsufficientLiquidity,
+    #[msg("Emergency withdraw not enabled")]
+    EmergencyWithdrawDisabled,
+
+    #[msg("Unstake ticket not claimable yet")]
+    TicketNotDue,
+    #[msg("Nothing to claim")]
+    NothingToClaim,
+
+    #[msg("Token custody not enabled in this build")]
+    CustodyDisabled,
+    #[msg("Initial deposit must exceed the locked dead shares")]
+    MinInitialDeposit,
+
+    #[msg("Validator registry full")]
+    ValidatorRegistryFull,
+    #[msg("Validator not found")]
+    ValidatorNotFound,
+    #[msg("Validator caps exceeded")]
+    ValidatorCapExceeded,
+
+    #[msg("Asset book full")]
+    AssetBookFull,
+
+    #[msg("Vault registry full")]
+    VaultRegistryFull,
+
+    #[msg("Vault account layout not recognized")]
+    VaultLayoutInvalid,
+    #[msg("Vault account already on the current layout")]
+    VaultAlreadyMigrated,
+
+    #[msg("Range-based vol mode needs the price bars account")]
+    PriceBarsRequired,
+
+    #[msg("Oracle source unavailable (account not passed or backend not built)")]
+    OracleSourceUnavailable,
+
+    #[msg("Pyth price update mismatch (owner, feed id or verification level)")]
+    PriceUpdateMismatch,
+
+    #[msg("Oracle price not usable for share pricing (not ok, degraded or stale)")]
+    OraclePriceUnsafe,
+
+    #[msg("Vault has a validator registry; pass it")]
+    ValidatorRegistryRequired,
+
+    #[msg("Vault has an asset book; pass it")]
+    AssetBookRequired,
+
+    #[msg("Vault not in the registry")]
+    VaultNotRegistered,
+
+    #[msg("Oracle accounts not set; call set_oracle_accounts")]
+    OracleAccountsNotSet,
+}
+
+// -------------------------------
+// Token custody helpers (feature = "token-custody")
+// -------------------------------
+
+/// Depositor -> custody (depositor signs)
+#[cfg(feature = "token-custody")]
+fn custody_pull<'info>(
+    from: &Account<'info, TokenAccount>,
+    custody: &Account<'info, TokenAccount>,
+    owner: &Signer<'info>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    token::transfer(
+        CpiContext::new(
+            token_program.to_account_info(),
+            Transfer {
+                from: from.to_account_info(),
+                to: custody.to_account_info(),
+                authority: owner.to_account_info(),
+            },
+        ),
+        amount,
+    )
+}
+
+/// Custody -> recipient (custody_authority PDA signs)
+#[cfg(feature = "token-custody")]
+fn custody_push<'info>(
+    custody: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    custody_authority: &UncheckedAccount<'info>,
+    token_program: &Program<'info, Token>,
+    vault_key: &Pubkey,
+    custody_authority_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let bump = [custody_authority_bump];
+    let seeds: &[&[u8]] = &[b"custody_authority", vault_key.as_ref(), &bump];
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Transfer {
+                from: custody.to_account_info(),
+                to: to.to_account_info(),
+                authority: custody_authority.to_account_info(),
+            },
+            &[seeds],
+        ),
+        amount,
+    )
+}
+
+/// Mint share tokens to a depositor (custody_authority PDA is the mint authority)
+#[cfg(feature = "token-custody")]
+fn share_mint_to<'info>(
+    share_mint: &Account<'info, Mint>,
+    to: &Account<'info, TokenAccount>,
+    custody_authority: &UncheckedAccount<'info>,
+    token_program: &Program<'info, Token>,
+    vault_key: &Pubkey,
+    custody_authority_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    let bump = [custody_authority_bump];
+    let seeds: &[&[u8]] = &[b"custody_authority", vault_key.as_ref(), &bump];
+    token::mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            MintTo {
+                mint: share_mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: custody_authority.to_account_info(),
+            },
+            &[seeds],
+        ),
+        amount,
+    )
+}
+
+/// Burn share tokens from the holder (holder signs)
+#[cfg(feature = "token-custody")]
+fn share_burn_from<'info>(
+    share_mint: &Account<'info, Mint>,
+    from: &Account<'info, TokenAccount>,
+    owner: &Signer<'info>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    token::burn(
+        CpiContext::new(
+            token_program.to_account_info(),
+            Burn {
+                mint: share_mint.to_account_info(),
+                from: from.to_account_info(),
+                authority: owner.to_account_info(),
+            },
+        ),
+        amount,
+    )
+}
+
+// -------------------------------
+// Oracle adapters
+// -------------------------------
+
+/// Normalized oracle reading: fp 1e6 prices, publish_time in unix seconds
+#[derive(Clone, Copy, Default)]
+pub struct OracleReading {
+    pub price_fp: i64,
+    pub ema_fp: i64,
+    pub conf_fp: i64,
+    pub publish_time: u64,
+    pub reject_reason: u8, // nonzero: the source refused its own reading (read_oracle_checked reports it)
+}
+
+/// A price backend. Gating (staleness / confidence / jump) is shared in `read_oracle_checked`.
+pub trait OracleSource {
+    fn read(&self, acct: &AccountInfo) -> Result<OracleReading>;
+}
+
+/// Legacy push-style Pyth price account
+pub struct PythSource;
+
+impl OracleSource for PythSource {
+    fn read(&self, acct: &AccountInfo) -> Result<OracleReading> {
+        let feed: PriceFeed = load_price_feed_from_account_info(acct).map_err(|_| error!(ErrorCode::OracleNotReady))?;
+
+        // ✅ FIX: pyth_sdk::PriceFeed doesn't expose get_current_price()/get_ema_price()
+        // in the Solana Playground-friendly crates. Use the unchecked getters and do
+        // our own gating (staleness/confidence/jump) in read_oracle_checked.
+        let spot: Price = feed.get_price_unchecked();
+        let ema: Price = feed.get_ema_price_unchecked();
+
+        // Convert to fp 1e6; publish_time comes from Price.publish_time (unix seconds)
+        let (price_fp, conf_fp, publish_time) = pyth_price_to_fp_and_time(&spot)?;
+        let (ema_fp, _ema_conf_fp, _ema_publish_time) = pyth_price_to_fp_and_time(&ema)?;
+        Ok(OracleReading { price_fp, ema_fp, conf_fp, publish_time, reject_reason: 0 })
+    }
+}
+
+/// Borsh layout of the Pyth receiver's `PriceUpdateV2` account (after the discriminator)
+#[derive(AnchorDeserialize)]
+pub enum PriceUpdateVerification {
+    Partial { num_signatures: u8 },
+    Full,
+}
+
+#[derive(AnchorDeserialize)]
+pub struct PriceUpdateMessage {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub prev_publish_time: i64,
+    pub ema_price: i64,
+    pub ema_conf: u64,
 }
 
-/// -------------------------------
-/// Oracle (Pyth) Helpers
-/// -------------------------------
+#[derive(AnchorDeserialize)]
+pub struct PriceUpdateV2Data {
+    pub write_authority: Pubkey,
+    pub verification_level: PriceUpdateVerification,
+    pub price_message: PriceUpdateMessage,
+    pub posted_slot: u64,
+}
+
+/// Pyth pull oracle: a PYTH_RECEIVER_PROGRAM_ID-owned PriceUpdateV2 account for a
+/// pinned feed id; no feed id pinned = refused (ORACLE_REASON_NO_FEED_ID)
+pub struct PythPullSource {
+    pub feed_id: [u8; 32],
+    pub min_verification: u8,
+    pub min_signatures: u8,
+}
+
+impl OracleSource for PythPullSource {
+    fn read(&self, acct: &AccountInfo) -> Result<OracleReading> {
+        if self.feed_id == [0u8; 32] {
+            return Ok(OracleReading { reject_reason: ORACLE_REASON_NO_FEED_ID, ..OracleReading::default() });
+        }
+        require_keys_eq!(*acct.owner, PYTH_RECEIVER_PROGRAM_ID, ErrorCode::PriceUpdateMismatch);
+
+        let data = acct.try_borrow_data()?;
+        let disc = hashv(&[b"account:PriceUpdateV2"]).to_bytes();
+        require!(data.len() > 8 && data[..8] == disc[..8], ErrorCode::PriceUpdateMismatch);
+        let update = PriceUpdateV2Data::deserialize(&mut &data[8..]).map_err(|_| error!(ErrorCode::PriceUpdateMismatch))?;
+
+        require!(update.price_message.feed_id == self.feed_id, ErrorCode::PriceUpdateMismatch);
+        let verified = match update.verification_level {
+            PriceUpdateVerification::Full => true,
+            PriceUpdateVerification::Partial { num_signatures } => {
+                self.min_verification == PullVerification::Partial as u8 && num_signatures >= self.min_signatures
+            }
+        };
+        require!(verified, ErrorCode::PriceUpdateMismatch);
+
+        let msg = update.price_message;
+        let spot = Price { price: msg.price, conf: msg.conf, expo: msg.exponent, publish_time: msg.publish_time };
+        let ema = Price { price: msg.ema_price, conf: msg.ema_conf, expo: msg.exponent, publish_time: msg.publish_time };
+        let (price_fp, conf_fp, publish_time) = pyth_price_to_fp_and_time(&spot)?;
+        let (ema_fp, _ema_conf_fp, _ema_publish_time) = pyth_price_to_fp_and_time(&ema)?;
+        Ok(OracleReading { price_fp, ema_fp, conf_fp, publish_time, reject_reason: 0 })
+    }
+}
+
+/// Switchboard on-demand pull feed: median result as spot, submission mean as the
+/// smoothed price, submission std dev as confidence
+pub struct SwitchboardSource;
+
+#[cfg(feature = "switchboard")]
+impl OracleSource for SwitchboardSource {
+    fn read(&self, acct: &AccountInfo) -> Result<OracleReading> {
+        let owner = acct.owner.to_bytes();
+        require!(
+            owner == ON_DEMAND_MAINNET_PID.to_bytes() || owner == ON_DEMAND_DEVNET_PID.to_bytes(),
+            ErrorCode::OracleNotReady
+        );
+        let data = acct.try_borrow_data()?;
+        let feed = PullFeedAccountData::parse(data).map_err(|_| error!(ErrorCode::OracleNotReady))?;
+        if feed.result.slot == 0 {
+            // never resolved: fails the sanity gate
+            return Ok(OracleReading::default());
+        }
+
+        // results are 1e18 fixed point
+        let to_fp = |x: i128| clamp_i128_to_i64(x / 1_000_000_000_000, 0, MAX_PRICE_FP);
+        let publish_time = feed.last_update_timestamp.max(0) as u64;
+        Ok(OracleReading {
+            price_fp: to_fp(feed.result.value)?,
+            ema_fp: to_fp(feed.result.mean)?,
+            conf_fp: to_fp(feed.result.std_dev)?,
+            publish_time,
+            reject_reason: 0,
+        })
+    }
+}
+
+#[cfg(not(feature = "switchboard"))]
+impl OracleSource for SwitchboardSource {
+    fn read(&self, _acct: &AccountInfo) -> Result<OracleReading> {
+        err!(ErrorCode::OracleSourceUnavailable)
+    }
+}
+
+/// The vault's authority-signed ManualPriceFeed
+pub struct ManualSource {
+    pub vault: Pubkey,
+}
+
+impl OracleSource for ManualSource {
+    fn read(&self, acct: &AccountInfo) -> Result<OracleReading> {
+        require_keys_eq!(*acct.owner, crate::ID, ErrorCode::InvalidParams);
+        let data = acct.try_borrow_data()?;
+        let feed = ManualPriceFeed::try_deserialize(&mut &data[..])?;
+        require_keys_eq!(feed.vault, self.vault, ErrorCode::InvalidParams);
+        Ok(OracleReading {
+            price_fp: feed.price_fp,
+            ema_fp: feed.ema_fp,
+            conf_fp: feed.conf_fp,
+            publish_time: feed.publish_time.max(0) as u64,
+            reject_reason: 0,
+        })
+    }
+}
+
+/// SOL/USDC read through `sol_usdc`, converted to SOL/USD with the USDC/USD feed:
+/// price x rate, conf combined from both legs, oldest publish time. Refuses the
+/// reading (ORACLE_REASON_USDC_DEPEG / _FEED) when USDC is off peg or its feed is
+/// unusable. Disabled = USDC taken 1:1. The rate used is kept in `usdc_usd_fp`.
+pub struct UsdcCrossRateSource<'a, 'info> {
+    pub sol_usdc: &'a dyn OracleSource,
+    pub usdc_usd: &'a dyn OracleSource,
+    pub usdc_usd_acct: Option<&'a AccountInfo<'info>>,
+    pub enabled: bool,
+    pub max_depeg_bps: u16,
+    pub now_unix_ts: i64,
+    pub max_age_seconds: u64,
+    pub max_conf_bps: u16,
+    pub usdc_usd_fp: Cell<i64>,
+}
+
+impl OracleSource for UsdcCrossRateSource<'_, '_> {
+    fn read(&self, acct: &AccountInfo) -> Result<OracleReading> {
+        let mut r = self.sol_usdc.read(acct)?;
+        if !self.enabled {
+            self.usdc_usd_fp.set(PRICE_FP_SCALE);
+            return Ok(r);
+        }
+
+        let usdc_acct = self.usdc_usd_acct.ok_or(ErrorCode::OracleSourceUnavailable)?;
+        // no jump gate on the peg itself: the depeg bound covers it
+        let (rate, _ema, rate_conf, t, ok, _reason) = read_oracle_checked(
+            self.usdc_usd,
+            usdc_acct,
+            0,
+            self.now_unix_ts,
+            self.max_age_seconds,
+            self.max_conf_bps,
+            BPS_DENOM,
+            0,
+        )?;
+        self.usdc_usd_fp.set(rate);
+        if !ok {
+            r.reject_reason = ORACLE_REASON_USDC_FEED;
+            return Ok(r);
+        }
+        if compute_price_drift_bps(rate, PRICE_FP_SCALE)? > self.max_depeg_bps {
+            r.reject_reason = ORACLE_REASON_USDC_DEPEG;
+            return Ok(r);
+        }
+
+        let scale = PRICE_FP_SCALE as i128;
+        let cross = |x: i64| clamp_i128_to_i64((x as i128) * (rate as i128) / scale, 0, MAX_PRICE_FP);
+        let conf = (r.conf_fp as i128) * (rate as i128) / scale + (r.price_fp as i128) * (rate_conf as i128) / scale;
+        r.conf_fp = clamp_i128_to_i64(conf, 0, MAX_PRICE_FP)?;
+        r.price_fp = cross(r.price_fp)?;
+        r.ema_fp = cross(r.ema_fp)?;
+        r.publish_time = r.publish_time.min(t);
+        Ok(r)
+    }
+}
 
-/// Read Pyth price feed from an AccountInfo, validate staleness/confidence/jump.
+/// Read `acct` through `source`, validate staleness/confidence/jump.
 /// Returns (spot_fp, ema_fp, conf_fp, publish_time_u64, ok, reason_code)
-fn read_pyth_checked(
+#[allow(clippy::too_many_arguments)]
+fn read_oracle_checked(
+    source: &dyn OracleSource,
     acct: &AccountInfo,
     current_slot: u64,
     now_unix_ts: i64,
@@ -2363,17 +7397,13 @@ fn read_pyth_checked(
     max_jump_bps: u16,
     last_price_fp: i64,
 ) -> Result<(i64, i64, i64, u64, bool, u8)> {
-    let feed: PriceFeed = load_price_feed_from_account_info(acct).map_err(|_| error!(ErrorCode::OracleNotReady))?;
-
-    // ✅ FIX: pyth_sdk::PriceFeed doesn't expose get_current_price()/get_ema_price()
-    // in the Solana Playground-friendly crates. Use the unchecked getters and do
-    // our own gating (staleness/confidence/jump) below.
-    let spot: Price = feed.get_price_unchecked();
-    let ema: Price = feed.get_ema_price_unchecked();
+    let reading = source.read(acct)?;
+    let (spot_fp, ema_fp, spot_conf_fp, spot_publish_time) =
+        (reading.price_fp, reading.ema_fp, reading.conf_fp, reading.publish_time);
 
-    // Convert to fp 1e6; publish_time comes from Price.publish_time (unix seconds)
-    let (spot_fp, spot_conf_fp, spot_publish_time) = pyth_price_to_fp_and_time(&spot)?;
-    let (ema_fp, _ema_conf_fp, _ema_publish_time) = pyth_price_to_fp_and_time(&ema)?;
+    if reading.reject_reason != 0 {
+        return Ok((0, 0, 0, spot_publish_time, false, reading.reject_reason));
+    }
 
     // Basic sanity (treat non-positive as "not ready")
     if spot_fp <= 0 || spot_fp > MAX_PRICE_FP || ema_fp <= 0 || ema_fp > MAX_PRICE_FP {
@@ -2420,10 +7450,16 @@ fn read_pyth_checked(
 
 /// Choose feed per config:
 /// Returns (feed_used, spot_fp, ema_fp, conf_fp, publish_time_u64, ok, reason_code)
-fn read_pyth_best_effort(
+#[allow(clippy::too_many_arguments)]
+fn read_oracle_best_effort(
     choice: u8,
+    sol_usd_source: &dyn OracleSource,
     sol_usd: &AccountInfo,
+    sol_usdc_source: &dyn OracleSource,
     sol_usdc: &AccountInfo,
+    switchboard_feed: Option<&AccountInfo>,
+    manual_price: Option<&AccountInfo>,
+    vault: Pubkey,
     current_slot: u64,
     now_unix_ts: i64,
     max_age_seconds: u64,
@@ -2431,8 +7467,9 @@ fn read_pyth_best_effort(
     max_jump_bps: u16,
     last_price_fp: i64,
 ) -> Result<(u8, i64, i64, i64, u64, bool, u8)> {
-    let try_one = |acct: &AccountInfo| -> Result<(i64, i64, i64, u64, bool, u8)> {
-        read_pyth_checked(
+    let try_one = |source: &dyn OracleSource, acct: &AccountInfo| -> Result<(i64, i64, i64, u64, bool, u8)> {
+        read_oracle_checked(
+            source,
             acct,
             current_slot,
             now_unix_ts,
@@ -2445,20 +7482,30 @@ fn read_pyth_best_effort(
 
     match choice {
         x if x == OracleFeedChoice::SolUsd as u8 => {
-            let (p, e, c, t, ok, r) = try_one(sol_usd)?;
+            let (p, e, c, t, ok, r) = try_one(sol_usd_source, sol_usd)?;
             Ok((OracleFeedChoice::SolUsd as u8, p, e, c, t, ok, r))
         }
         x if x == OracleFeedChoice::SolUsdc as u8 => {
-            let (p, e, c, t, ok, r) = try_one(sol_usdc)?;
+            let (p, e, c, t, ok, r) = try_one(sol_usdc_source, sol_usdc)?;
             Ok((OracleFeedChoice::SolUsdc as u8, p, e, c, t, ok, r))
         }
+        x if x == OracleFeedChoice::Switchboard as u8 => {
+            let acct = switchboard_feed.ok_or(ErrorCode::OracleSourceUnavailable)?;
+            let (p, e, c, t, ok, r) = try_one(&SwitchboardSource, acct)?;
+            Ok((OracleFeedChoice::Switchboard as u8, p, e, c, t, ok, r))
+        }
+        x if x == OracleFeedChoice::Manual as u8 => {
+            let acct = manual_price.ok_or(ErrorCode::OracleSourceUnavailable)?;
+            let (p, e, c, t, ok, r) = try_one(&ManualSource { vault }, acct)?;
+            Ok((OracleFeedChoice::Manual as u8, p, e, c, t, ok, r))
+        }
         _ => {
             // AutoPreferUsdThenUsdc
-            let (p1, e1, c1, t1, ok1, r1) = try_one(sol_usd)?;
+            let (p1, e1, c1, t1, ok1, r1) = try_one(sol_usd_source, sol_usd)?;
             if ok1 {
                 return Ok((OracleFeedChoice::SolUsd as u8, p1, e1, c1, t1, ok1, r1));
             }
-            let (p2, e2, c2, t2, ok2, r2) = try_one(sol_usdc)?;
+            let (p2, e2, c2, t2, ok2, r2) = try_one(sol_usdc_source, sol_usdc)?;
             if ok2 {
                 return Ok((OracleFeedChoice::SolUsdc as u8, p2, e2, c2, t2, ok2, r2));
             }
@@ -2469,12 +7516,130 @@ fn read_pyth_best_effort(
                 c1,
                 t1,
                 false,
-                if r1 != 0 { r1 } else { r2.max(1) },
+                // a refused USDC leg is the reason there was no fallback
+                if r2 == ORACLE_REASON_USDC_DEPEG || r2 == ORACLE_REASON_USDC_FEED {
+                    r2
+                } else if r1 != 0 {
+                    r1
+                } else {
+                    r2.max(1)
+                },
             ))
         }
     }
 }
 
+/// Result of the median path
+pub struct OracleAggregate {
+    pub reading: OracleReading,
+    pub ok: bool,
+    pub reason: u8,
+    pub feeds_read: u8,
+    pub feeds_used: u8,
+    pub spread_bps: u16,
+}
+
+/// Median of the `feed_mask` feeds that pass the gates. A configured feed whose
+/// account is missing fails the call (a keeper can't drop a dissenting feed);
+/// one that is present but unreadable is counted as read, not used. Not ok with ORACLE_REASON_NO_QUORUM below `min_feeds` survivors, or
+/// ORACLE_REASON_DISAGREEMENT when their spread exceeds `max_spread_bps`.
+#[allow(clippy::too_many_arguments)]
+fn read_oracle_median<'a, 'info>(
+    feed_mask: u8,
+    min_feeds: u8,
+    max_spread_bps: u16,
+    sol_usd_source: &'a dyn OracleSource,
+    sol_usd: &'a AccountInfo<'info>,
+    sol_usdc_source: &'a dyn OracleSource,
+    sol_usdc: &'a AccountInfo<'info>,
+    switchboard_feed: Option<&'a AccountInfo<'info>>,
+    manual_price: Option<&'a AccountInfo<'info>>,
+    vault: Pubkey,
+    current_slot: u64,
+    now_unix_ts: i64,
+    max_age_seconds: u64,
+    max_conf_bps: u16,
+    max_jump_bps: u16,
+    last_price_fp: i64,
+) -> Result<OracleAggregate> {
+    let manual_source = ManualSource { vault };
+    let feeds: [(u8, &dyn OracleSource, Option<&'a AccountInfo<'info>>); MAX_ORACLE_FEEDS] = [
+        (ORACLE_FEED_BIT_SOL_USD, sol_usd_source, Some(sol_usd)),
+        (ORACLE_FEED_BIT_SOL_USDC, sol_usdc_source, Some(sol_usdc)),
+        (ORACLE_FEED_BIT_SWITCHBOARD, &SwitchboardSource, switchboard_feed),
+        (ORACLE_FEED_BIT_MANUAL, &manual_source, manual_price),
+    ];
+
+    let mut prices: Vec<i64> = Vec::with_capacity(MAX_ORACLE_FEEDS);
+    let mut emas: Vec<i64> = Vec::with_capacity(MAX_ORACLE_FEEDS);
+    let mut confs: Vec<i64> = Vec::with_capacity(MAX_ORACLE_FEEDS);
+    let mut oldest_publish = u64::MAX;
+    let mut feeds_read = 0u8;
+
+    for (bit, source, acct) in feeds {
+        if feed_mask & bit == 0 {
+            continue;
+        }
+        let acct = acct.ok_or(ErrorCode::OracleSourceUnavailable)?;
+        feeds_read += 1;
+
+        // an unreadable feed (wrong owner, bad data) counts as not used; a missing account still aborts
+        let (p, e, c, t, ok, _reason) = match read_oracle_checked(
+            source,
+            acct,
+            current_slot,
+            now_unix_ts,
+            max_age_seconds,
+            max_conf_bps,
+            max_jump_bps,
+            last_price_fp,
+        ) {
+            Ok(r) => r,
+            Err(err) if err == error!(ErrorCode::OracleSourceUnavailable) => return Err(err),
+            Err(_) => continue,
+        };
+        if ok {
+            prices.push(p);
+            emas.push(e);
+            confs.push(c);
+            oldest_publish = oldest_publish.min(t);
+        }
+    }
+
+    let feeds_used = prices.len() as u8;
+    if prices.is_empty() {
+        return Ok(OracleAggregate {
+            reading: OracleReading::default(),
+            ok: false,
+            reason: ORACLE_REASON_NO_QUORUM,
+            feeds_read,
+            feeds_used,
+            spread_bps: 0,
+        });
+    }
+
+    let lo = *prices.iter().min().unwrap_or(&0);
+    let hi = *prices.iter().max().unwrap_or(&0);
+    let reading = OracleReading {
+        price_fp: median_i64(&mut prices),
+        ema_fp: median_i64(&mut emas),
+        conf_fp: median_i64(&mut confs),
+        publish_time: oldest_publish,
+        reject_reason: 0,
+    };
+    let spread_bps = ((hi - lo) as i128 * (BPS_DENOM as i128) / (reading.price_fp.max(1) as i128)).min(u16::MAX as i128) as u16;
+
+    let (ok, reason) = if feeds_used < min_feeds {
+        (false, ORACLE_REASON_NO_QUORUM)
+    } else if spread_bps > max_spread_bps {
+        (false, ORACLE_REASON_DISAGREEMENT)
+    } else {
+        (true, 0)
+    };
+
+    Ok(OracleAggregate { reading, ok, reason, feeds_read, feeds_used, spread_bps })
+}
+
 /// Convert pyth_sdk::Price to fp(1e6) + publish_time (unix seconds).
 fn pyth_price_to_fp_and_time(p: &Price) -> Result<(i64, i64, u64)> {
     let expo = p.expo;
@@ -2524,9 +7689,9 @@ fn clamp_i128_to_i64(x: i128, min: i64, max: i64) -> Result<i64> {
     Ok(x as i64)
 }
 
-/// -------------------------------
-/// Deterministic math helpers
-/// -------------------------------
+// -------------------------------
+// Deterministic math helpers
+// -------------------------------
 
 fn abs_i64(x: i64) -> i64 {
     if x < 0 { -x } else { x }
@@ -2543,15 +7708,31 @@ fn weighted_vol_score_bps(realized_bps: u16, implied_bps: u16, w_realized_bps: u
     Ok((sum / (BPS_DENOM as u64)).min(MAX_VOL_BPS as u64) as u16)
 }
 
-fn compute_realized_vol_bps_mode(mode: u8, returns: &[i32; N_RETURNS], ewma_var_fp2: u128) -> Result<u16> {
-    if mode == VolMode::Ewma as u8 {
-        let std_fp = isqrt_u128(ewma_var_fp2.min(MAX_VAR_FP2));
+/// Annualized realized vol (bps, capped at MAX_ANNUAL_VOL_BPS) so it is on the same
+/// scale as keeper-fed `implied_vol_bps`.
+fn compute_realized_vol_bps_mode(
+    mode: u8,
+    samples: &[ReturnSample],
+    bars: Option<&PriceBars>,
+    model_var_fp2: u128,
+    now_ts: i64,
+) -> Result<u16> {
+    if mode == VolMode::Ewma as u8 || mode == VolMode::Garch as u8 {
+        // the EWMA / GARCH recursions already track annualized variance
+        let std_fp = isqrt_u128(model_var_fp2.min(MAX_VAR_FP2));
         return fp_to_bps(std_fp);
     }
+    if is_range_vol_mode(mode) {
+        let bars = bars.ok_or(ErrorCode::PriceBarsRequired)?;
+        return range_vol_bps(mode, bars, now_ts);
+    }
+    if samples.iter().all(|x| x.elapsed_secs == 0) {
+        return Ok(0);
+    }
     if mode == VolMode::Mad as u8 {
-        return mad_vol_bps(returns);
+        return mad_vol_bps(samples);
     }
-    stdev_vol_bps(returns)
+    stdev_vol_bps(samples)
 }
 
 fn fp_to_bps(std_fp: u128) -> Result<u16> {
@@ -2559,24 +7740,200 @@ fn fp_to_bps(std_fp: u128) -> Result<u16> {
         .checked_mul(BPS_DENOM as u128)
         .ok_or(ErrorCode::MathOverflow)?
         / (RET_FP_SCALE as u128);
-    Ok((bps_u128.min(MAX_VOL_BPS as u128)) as u16)
+    Ok((bps_u128.min(MAX_ANNUAL_VOL_BPS as u128)) as u16)
+}
+
+fn is_range_vol_mode(mode: u8) -> bool {
+    mode == VolMode::Parkinson as u8 || mode == VolMode::GarmanKlass as u8 || mode == VolMode::RogersSatchell as u8
+}
+
+/// One bar's variance (fp^2 at RET_FP_SCALE) under a range estimator
+fn bar_variance_fp2(mode: u8, bar: &OhlcBar) -> Result<i128> {
+    let fp = RET_FP_SCALE as i128;
+    let (o, h, l, c) = (
+        bar.open_fp.max(1) as i128,
+        bar.high_fp.max(1) as i128,
+        bar.low_fp.max(1) as i128,
+        bar.close_fp.max(1) as i128,
+    );
+
+    if mode == VolMode::Parkinson as u8 {
+        // ln(H/L)^2 / (4 ln 2)
+        let hl = ln_ratio_fp(h, l)?;
+        return Ok(hl * hl / fp * PARKINSON_K_FP / fp);
+    }
+    if mode == VolMode::GarmanKlass as u8 {
+        // 0.5 ln(H/L)^2 - (2 ln 2 - 1) ln(C/O)^2
+        let hl = ln_ratio_fp(h, l)?;
+        let co = ln_ratio_fp(c, o)?;
+        return Ok(hl * hl / (2 * fp) - co * co / fp * GARMAN_KLASS_C_FP / fp);
+    }
+    // Rogers-Satchell (drift-independent): ln(H/C) ln(H/O) + ln(L/C) ln(L/O)
+    let hc = ln_ratio_fp(h, c)?;
+    let ho = ln_ratio_fp(h, o)?;
+    let lc = ln_ratio_fp(l, c)?;
+    let lo = ln_ratio_fp(l, o)?;
+    Ok((hc * ho + lc * lo) / fp)
+}
+
+/// Mean per-bar variance over usable bars, per second of bar interval, scaled to a year
+fn range_vol_bps(mode: u8, bars: &PriceBars, now_ts: i64) -> Result<u16> {
+    let mut sum: i128 = 0;
+    let mut n: u128 = 0;
+    for bar in bars.usable_bars(now_ts) {
+        sum = sum.checked_add(bar_variance_fp2(mode, bar)?).ok_or(ErrorCode::MathOverflow)?;
+        n += 1;
+    }
+    if n == 0 || sum <= 0 {
+        return Ok(0);
+    }
+
+    let secs = n.checked_mul(bars.bar_secs as u128).ok_or(ErrorCode::MathOverflow)?;
+    let var = ((sum as u128).checked_mul(SECONDS_PER_YEAR as u128).ok_or(ErrorCode::MathOverflow)? / secs).min(MAX_VAR_FP2);
+    fp_to_bps(isqrt_u128(var))
+}
+
+/// Return from `prev_price_fp` to `price_fp` at RET_FP_SCALE, clamped to ±MAX_RETURN_ABS_FP
+fn compute_return_fp(price_fp: i64, prev_price_fp: i64, return_kind: u8) -> Result<i32> {
+    let p = price_fp as i128;
+    let p0 = (prev_price_fp as i128).max(1);
+    let ret = if return_kind == ReturnKind::Log as u8 {
+        ln_ratio_fp(p.max(1), p0)?
+    } else {
+        p.checked_sub(p0)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_mul(RET_FP_SCALE as i128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / p0
+    };
+    Ok(ret.clamp(-(MAX_RETURN_ABS_FP as i128), MAX_RETURN_ABS_FP as i128) as i32)
+}
+
+/// ln(p / p0) at RET_FP_SCALE via 2*atanh(y), y = (p - p0) / (p + p0); p, p0 > 0.
+/// |y| > 0.2 means |ln| > 0.4, past the return clamp, so the series only runs
+/// where it converges fast (y^2 <= 0.04: about a dozen terms).
+fn ln_ratio_fp(p: i128, p0: i128) -> Result<i128> {
+    const S: i128 = 1_000_000_000_000_000_000; // 1e18 working scale
+    let y = p
+        .checked_sub(p0)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_mul(S)
+        .ok_or(ErrorCode::MathOverflow)?
+        / p.checked_add(p0).ok_or(ErrorCode::MathOverflow)?;
+    if y.abs() > S / 5 {
+        return Ok(y.signum() * (MAX_RETURN_ABS_FP as i128));
+    }
+
+    let y2 = y * y / S;
+    let mut term = y;
+    let mut sum: i128 = 0;
+    let mut k: i128 = 1;
+    while term != 0 {
+        sum += term / k;
+        term = term * y2 / S;
+        k += 2;
+    }
+    Ok(2 * sum / (S / (RET_FP_SCALE as i128)))
+}
+
+/// One sample's variance scaled to a year: r^2 * SECONDS_PER_YEAR / dt (fp^2, clamped)
+fn annualized_var_sample_fp2(sample: ReturnSample) -> Result<u128> {
+    if sample.elapsed_secs == 0 {
+        return Ok(0);
+    }
+    let r = sample.ret_fp.unsigned_abs() as u128;
+    let var = r
+        .checked_mul(r)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_mul(SECONDS_PER_YEAR as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        / (sample.elapsed_secs as u128);
+    Ok(var.min(MAX_VAR_FP2))
+}
+
+/// Dt-weighted effective size of the recorded samples: (sum dt)^2 / sum dt^2.
+/// Equals the recorded count when spacing is regular; unfilled slots (dt = 0) don't count.
+fn effective_sample_count(samples: &[ReturnSample]) -> u16 {
+    let mut sum_dt: u128 = 0;
+    let mut sum_dt2: u128 = 0;
+    for x in samples.iter().filter(|x| x.elapsed_secs > 0) {
+        let dt = x.elapsed_secs as u128;
+        sum_dt += dt;
+        sum_dt2 += dt * dt;
+    }
+    if sum_dt2 == 0 {
+        return 0;
+    }
+    (sum_dt * sum_dt / sum_dt2).min(u16::MAX as u128) as u16
+}
+
+/// 95% interval on `vol_bps` from `n_eff` samples and the estimator's efficiency
+fn realized_vol_ci_bps(mode: u8, vol_bps: u16, n_eff: u16, ewma_alpha_bps: u16) -> (u16, u16) {
+    let mut n = n_eff as u128;
+    let eff_bps = if mode == VolMode::Mad as u8 {
+        EFF_MAD_BPS
+    } else if mode == VolMode::Parkinson as u8 {
+        EFF_PARKINSON_BPS
+    } else if mode == VolMode::GarmanKlass as u8 {
+        EFF_GARMAN_KLASS_BPS
+    } else if mode == VolMode::RogersSatchell as u8 {
+        EFF_ROGERS_SATCHELL_BPS
+    } else {
+        EFF_STDEV_BPS
+    };
+    if mode == VolMode::Ewma as u8 && ewma_alpha_bps > 0 {
+        // EWMA remembers about (2 - alpha) / alpha samples
+        let a = ewma_alpha_bps as u128;
+        n = n.min((2 * (BPS_DENOM as u128) - a) / a);
+    } else if mode == VolMode::Stdev as u8 {
+        n = n.saturating_sub(1);
+    }
+    if n == 0 {
+        return (0, MAX_ANNUAL_VOL_BPS);
+    }
+
+    // sqrt(2 n eff) at 1e6
+    let root_fp = isqrt_u128(2 * n * eff_bps * 100_000_000);
+    let half = (vol_bps as u128) * VOL_CI_Z_BPS * 1_000_000 / ((BPS_DENOM as u128) * root_fp.max(1));
+    let lo = (vol_bps as u128).saturating_sub(half) as u16;
+    let hi = ((vol_bps as u128) + half).min(MAX_ANNUAL_VOL_BPS as u128) as u16;
+    (lo, hi)
 }
 
-fn stdev_vol_bps(returns: &[i32; N_RETURNS]) -> Result<u16> {
-    let mut sum: i64 = 0;
-    for &r in returns.iter() {
-        sum = sum.checked_add(r as i64).ok_or(ErrorCode::MathOverflow)?;
+/// Variance per unit time: drift is removed per second, then sum(e_i^2) is
+/// divided by its expectation's time weight, sum(dt) - sum(dt^2) / sum(dt)
+/// (= (n - 1) dt for regular spacing), and scaled to a year. Only recorded
+/// samples count; fewer than MIN_EFFECTIVE_SAMPLES yields 0.
+fn stdev_vol_bps(samples: &[ReturnSample]) -> Result<u16> {
+    let mut sum_r: i128 = 0;
+    let mut sum_dt: i128 = 0;
+    let mut sum_dt2: i128 = 0;
+    let mut n: u16 = 0;
+    for x in samples.iter().filter(|x| x.elapsed_secs > 0) {
+        let dt = x.elapsed_secs as i128;
+        sum_r = sum_r.checked_add(x.ret_fp as i128).ok_or(ErrorCode::MathOverflow)?;
+        sum_dt = sum_dt.checked_add(dt).ok_or(ErrorCode::MathOverflow)?;
+        sum_dt2 = sum_dt2.checked_add(dt.checked_mul(dt).ok_or(ErrorCode::MathOverflow)?).ok_or(ErrorCode::MathOverflow)?;
+        n += 1;
+    }
+    if n < MIN_EFFECTIVE_SAMPLES as u16 {
+        return Ok(0);
+    }
+    let dof_dt = sum_dt - sum_dt2 / sum_dt;
+    if dof_dt <= 0 {
+        return Ok(0);
     }
-    let mean: i64 = sum / (N_RETURNS as i64);
 
     let mut var_acc: u128 = 0;
-    for &r in returns.iter() {
-        let dev: i64 = (r as i64).checked_sub(mean).ok_or(ErrorCode::MathOverflow)?;
-        let dev_abs: u128 = if dev < 0 { (-dev) as u128 } else { dev as u128 };
+    for x in samples.iter().filter(|x| x.elapsed_secs > 0) {
+        // expected drift over this sample's dt
+        let drift = sum_r.checked_mul(x.elapsed_secs as i128).ok_or(ErrorCode::MathOverflow)? / sum_dt;
+        let dev = (x.ret_fp as i128).checked_sub(drift).ok_or(ErrorCode::MathOverflow)?;
+        let dev_abs = dev.unsigned_abs();
         let dev_sq = dev_abs.checked_mul(dev_abs).ok_or(ErrorCode::MathOverflow)?;
         var_acc = var_acc.checked_add(dev_sq).ok_or(ErrorCode::MathOverflow)?;
     }
-    let mut var = var_acc / (N_RETURNS as u128);
+    let mut var = var_acc.checked_mul(SECONDS_PER_YEAR as u128).ok_or(ErrorCode::MathOverflow)? / (dof_dt as u128);
     if var > MAX_VAR_FP2 {
         var = MAX_VAR_FP2;
     }
@@ -2584,37 +7941,58 @@ fn stdev_vol_bps(returns: &[i32; N_RETURNS]) -> Result<u16> {
     fp_to_bps(std_fp)
 }
 
-fn mad_vol_bps(returns: &[i32; N_RETURNS]) -> Result<u16> {
-    let mut buf = *returns;
-    let med = median_i32(&mut buf);
-
-    let mut devs = [0i32; N_RETURNS];
-    for i in 0..N_RETURNS {
-        let d = (returns[i] as i64 - med as i64);
-        let a = if d < 0 { -d } else { d };
-        devs[i] = a.min(i32::MAX as i64) as i32;
+/// MAD of returns rescaled to a year (r * sqrt(year / dt)), then scaled to a stdev.
+fn mad_vol_bps(samples: &[ReturnSample]) -> Result<u16> {
+    let fp = RET_FP_SCALE as u128;
+    let mut scaled: Vec<i64> = Vec::with_capacity(samples.len());
+    for x in samples.iter().filter(|x| x.elapsed_secs > 0) {
+        // sqrt(year / dt) in fp
+        let factor_fp = isqrt_u128((SECONDS_PER_YEAR as u128) * fp * fp / (x.elapsed_secs as u128));
+        let z = (x.ret_fp as i128).checked_mul(factor_fp as i128).ok_or(ErrorCode::MathOverflow)? / (fp as i128);
+        scaled.push(z.clamp(i64::MIN as i128, i64::MAX as i128) as i64);
+    }
+    if scaled.is_empty() {
+        return Ok(0);
     }
 
-    let mut devs_copy = devs;
-    let mad_fp = median_i32(&mut devs_copy) as u128;
+    let mut buf = scaled.clone();
+    let med = median_i64(&mut buf);
+
+    let mut devs: Vec<i64> = scaled.iter().map(|&z| z.saturating_sub(med).saturating_abs()).collect();
+    let mad_fp = median_i64(&mut devs).max(0) as u128;
 
     let mad_scaled = mad_fp.checked_mul(14826u128).ok_or(ErrorCode::MathOverflow)? / 10000u128;
     fp_to_bps(mad_scaled)
 }
 
-fn median_i32(arr: &mut [i32; N_RETURNS]) -> i32 {
-    for i in 1..N_RETURNS {
-        let key = arr[i];
-        let mut j = i;
-        while j > 0 && arr[j - 1] > key {
-            arr[j] = arr[j - 1];
-            j -= 1;
+/// Sorts `arr` in place (O(n log n): windows go up to MAX_RETURNS_CAPACITY)
+fn median_i64(arr: &mut [i64]) -> i64 {
+    let n = arr.len();
+    if n == 0 {
+        return 0;
+    }
+    arr.sort_unstable();
+    if n % 2 == 1 {
+        return arr[n / 2];
+    }
+    let a = arr[(n / 2) - 1] as i128;
+    let b = arr[n / 2] as i128;
+    ((a + b) / 2) as i64
+}
+
+/// (x / BPS_DENOM)^n in bps, by repeated squaring
+fn pow_bps(x: u16, mut n: u64) -> u128 {
+    let denom = BPS_DENOM as u128;
+    let mut base = x as u128;
+    let mut acc = denom;
+    while n > 0 && acc > 0 {
+        if n & 1 == 1 {
+            acc = acc * base / denom;
         }
-        arr[j] = key;
+        base = base * base / denom;
+        n >>= 1;
     }
-    let a = arr[(N_RETURNS / 2) - 1] as i64;
-    let b = arr[(N_RETURNS / 2)] as i64;
-    ((a + b) / 2) as i32
+    acc
 }
 
 fn ewma_update_u128(prev: u128, x: u128, alpha_bps: u16) -> Result<u128> {
@@ -2664,7 +8042,7 @@ fn slew_limit_u16(current: u16, target: u16, max_slew_bps: u16) -> Result<u16> {
     let tar = target as i32;
     let diff = tar - cur;
 
-    let limited = if diff.abs() as u32 <= max_delta {
+    let limited = if diff.unsigned_abs() <= max_delta {
         target
     } else if diff > 0 {
         (cur + (max_delta as i32)) as u16
@@ -2725,6 +8103,46 @@ fn compute_target_hedge_notional_usd_delta(staked_sol: u64, price_fp: i64, targe
     Ok(-n)
 }
 
+/// PnL of a perp with entry notional `notional_usd` (sign = side) marked at `mark_price_fp`.
+fn hedge_pnl_usd(notional_usd: i64, entry_price_fp: i64, mark_price_fp: i64) -> Result<i64> {
+    if notional_usd == 0 || entry_price_fp <= 0 || mark_price_fp <= 0 {
+        return Ok(0);
+    }
+    let diff = (mark_price_fp as i128) - (entry_price_fp as i128);
+    let pnl = (notional_usd as i128)
+        .checked_mul(diff)
+        .ok_or(ErrorCode::MathOverflow)?
+        / (entry_price_fp as i128);
+    Ok(pnl.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+}
+
+/// Size-weighted (harmonic) avg entry after adding `add_notional_usd` at `fill_price_fp`.
+fn blend_entry_price_fp(
+    prev_notional_usd: i64,
+    prev_entry_price_fp: i64,
+    add_notional_usd: i64,
+    fill_price_fp: i64,
+) -> Result<i64> {
+    if prev_notional_usd == 0 || prev_entry_price_fp <= 0 {
+        return Ok(fill_price_fp);
+    }
+    // base-asset size scaled by 1e12 so small notionals keep precision
+    let scale = (PRICE_FP_SCALE as i128) * (PRICE_FP_SCALE as i128);
+    let q_prev = (abs_i64(prev_notional_usd) as i128).checked_mul(scale).ok_or(ErrorCode::MathOverflow)?
+        / (prev_entry_price_fp as i128);
+    let q_add = (abs_i64(add_notional_usd) as i128).checked_mul(scale).ok_or(ErrorCode::MathOverflow)?
+        / (fill_price_fp as i128);
+    let q = q_prev.checked_add(q_add).ok_or(ErrorCode::MathOverflow)?;
+    if q == 0 {
+        return Ok(fill_price_fp);
+    }
+    let total = (abs_i64(prev_notional_usd) as i128)
+        .checked_add(abs_i64(add_notional_usd) as i128)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let entry = total.checked_mul(scale).ok_or(ErrorCode::MathOverflow)? / q;
+    Ok(entry.clamp(1, MAX_PRICE_FP as i128) as i64)
+}
+
 fn compute_slippage_bps(fill_price_fp: i64, ref_price_fp: i64) -> Result<u16> {
     require!(ref_price_fp > 0, ErrorCode::InvalidParams);
     let f = fill_price_fp as i128;
@@ -2794,8 +8212,328 @@ USAGE NOTES (quick):
 1) initialize_vault(params)
 2) keeper_admin add keepers: add_keeper(...)
 3) update_oracle_price(signer=keeper, pass pyth accounts)
+   open_user_position (user) -> deposit_and_stake (mints shares against NAV)
+   request_unstake (user, opens a ticket PDA) -> mature_unstake_ticket (anyone, after cooldown)
+   -> fulfill_unstake_queue (anyone) -> claim_unstake (user)
+   withdraw_reserve (authority) / emergency_withdraw (user, emergency mode)
 4) update_implied_vol / update_carry_inputs (optional)
 5) update_epoch_and_policy (keeper)
 6) request_hedge (anyone) -> emits HedgeRequested intent
 7) confirm_hedge (keeper) -> record execution + slippage stats
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FP: i64 = PRICE_FP_SCALE;
+
+    fn vault() -> Box<VaultState> {
+        Box::new(bytemuck::Zeroable::zeroed())
+    }
+
+    #[test]
+    fn hedge_avg_entry_is_size_weighted() {
+        let mut s = vault();
+        assert_eq!(s.book_hedge_fill(1_000, 100 * FP).unwrap(), 0);
+        s.hedge_notional_usd = 1_000;
+        assert_eq!(s.hedge_entry_price_fp, 100 * FP);
+
+        // +1_000 at 200: 10 + 5 base units for 2_000 notional -> 133.333333
+        assert_eq!(s.book_hedge_fill(2_000, 200 * FP).unwrap(), 0);
+        s.hedge_notional_usd = 2_000;
+        assert_eq!(s.hedge_entry_price_fp, 133_333_333);
+        assert_eq!(s.hedge_realized_pnl_usd, 0);
+    }
+
+    #[test]
+    fn hedge_reduce_realizes_closed_part_and_keeps_entry() {
+        let mut s = vault();
+        s.hedge_notional_usd = -2_000; // short
+        s.hedge_entry_price_fp = 100 * FP;
+
+        // close half at 90: the short made 10% on 1_000
+        assert_eq!(s.book_hedge_fill(-1_000, 90 * FP).unwrap(), 100);
+        s.hedge_notional_usd = -1_000;
+        assert_eq!(s.hedge_entry_price_fp, 100 * FP);
+        assert_eq!(s.hedge_realized_pnl_usd, 100);
+    }
+
+    #[test]
+    fn hedge_flip_realizes_everything_and_reopens_at_fill() {
+        let mut s = vault();
+        s.hedge_notional_usd = 1_000; // long
+        s.hedge_entry_price_fp = 100 * FP;
+        s.hedge_realized_pnl_usd = 50;
+
+        // flip to a 500 short at 80: the long loses 20% on 1_000
+        assert_eq!(s.book_hedge_fill(-500, 80 * FP).unwrap(), -200);
+        s.hedge_notional_usd = -500;
+        assert_eq!(s.hedge_entry_price_fp, 80 * FP);
+        assert_eq!(s.hedge_realized_pnl_usd, -150);
+
+        // closing flat clears the entry
+        assert_eq!(s.book_hedge_fill(0, 88 * FP).unwrap(), -50);
+        assert_eq!(s.hedge_entry_price_fp, 0);
+        assert_eq!(s.hedge_realized_pnl_usd, -200);
+    }
+
+    #[test]
+    fn hedge_marks_to_market_in_nav() {
+        let mut s = vault();
+        s.oracle_price_fp = 100 * FP;
+        s.staked_sol = 10;
+        s.hedge_notional_usd = -1_000;
+        s.hedge_entry_price_fp = 100 * FP;
+        s.hedge_realized_pnl_usd = 25;
+        assert_eq!(s.unrealized_pnl_usd().unwrap(), 0);
+        assert_eq!(s.compute_nav_usd().unwrap(), 1_000 + 25);
+
+        // SOL +10%: stake gains 100, the short loses 100
+        s.oracle_price_fp = 110 * FP;
+        assert_eq!(s.unrealized_pnl_usd().unwrap(), -100);
+        assert_eq!(s.compute_nav_usd().unwrap(), 1_100 - 100 + 25);
+    }
+    #[test]
+    fn hedge_carry_signs_funding_by_side_and_charges_borrow_on_size() {
+        let mut s = vault();
+        s.funding_bps_per_day = 10;
+        s.borrow_bps_per_day = 4;
+
+        // short 100_000 for one day: earns 10 bps funding, pays 4 bps borrow
+        s.hedge_notional_usd = -100_000;
+        assert_eq!(s.accrue_hedge_carry(SECONDS_PER_DAY).unwrap(), 100 - 40);
+
+        // long 100_000 for one day: pays both
+        s.hedge_notional_usd = 100_000;
+        assert_eq!(s.accrue_hedge_carry(2 * SECONDS_PER_DAY).unwrap(), -100 - 40);
+        assert_eq!(s.hedge_carry_accrued_usd, 60 - 140);
+        assert_eq!(s.carry_accrued_epoch_usd, 60 - 140);
+
+        // negative funding: shorts pay, half a day
+        s.funding_bps_per_day = -10;
+        s.hedge_notional_usd = -100_000;
+        assert_eq!(s.accrue_hedge_carry(5 * SECONDS_PER_DAY / 2).unwrap(), -50 - 20);
+        assert_eq!(s.last_carry_accrual_ts, 5 * SECONDS_PER_DAY / 2);
+    }
+
+    #[test]
+    fn management_fee_time_without_nav_is_charged_later() {
+        let mut s = vault();
+        s.total_shares = 1_000_000;
+        s.management_fee_bps = 100;
+
+        // a year with no NAV charges nothing and keeps the fee clock
+        s.accrue_fees(SECONDS_PER_YEAR, 0).unwrap();
+        assert_eq!((s.fee_shares_unclaimed, s.last_fee_ts), (0, 0));
+
+        // both years are charged once NAV is back: 2% of 1_000_000
+        s.hedge_realized_pnl_usd = 1_000_000;
+        s.accrue_fees(2 * SECONDS_PER_YEAR, 0).unwrap();
+        assert_eq!(s.fee_shares_unclaimed, 20_000 * 1_000_000 / 980_000);
+        assert_eq!(s.last_fee_ts, 2 * SECONDS_PER_YEAR);
+    }
+
+    #[test]
+    fn compounded_yield_follows_validator_apy_and_requires_the_registry() {
+        let mut s = vault();
+        s.compound_staking_yield = 1;
+        s.has_validator_registry = 1;
+        s.registry_pending_yield_sol = 1_001;
+        assert!(s.sync_validator_registry(None).is_err());
+
+        let mut r = ValidatorRegistry {
+            vault: Pubkey::default(),
+            bump: 0,
+            validator_count: 2,
+            rebalance_drift_bps: 0,
+            validators: [ValidatorSlot::default(); MAX_VALIDATORS],
+        };
+        r.validators[0].delegated_sol = 1_000;
+        r.validators[0].apy_bps = 600;
+        r.validators[1].delegated_sol = 1_000;
+        r.validators[1].apy_bps = 400;
+        s.sync_validator_registry(Some(&mut r)).unwrap();
+
+        // 60/40 by delegation x APY, rounding dust to the larger earner
+        assert_eq!(r.validators[0].delegated_sol, 1_000 + 601);
+        assert_eq!(r.validators[1].delegated_sol, 1_000 + 400);
+        assert_eq!(s.registry_pending_yield_sol, 0);
+    }
+
+    #[test]
+    fn migrates_a_legacy_borsh_vault() {
+        let authority = Pubkey::new_unique();
+        let pending = Pubkey::new_from_array([7u8; 32]);
+
+        // legacy account as allocated by the Borsh initialize_vault (8-aligned like account data)
+        let mut buf = vec![0u64; VaultState::SPACE.div_ceil(8)];
+        let data = &mut bytemuck::cast_slice_mut::<u64, u8>(&mut buf)[..VaultState::SPACE];
+        data[..8].copy_from_slice(&<VaultState as Discriminator>::DISCRIMINATOR);
+        data[8..40].copy_from_slice(authority.as_ref());
+        data[40..72].copy_from_slice(pending.as_ref());
+        data[8 + 97..8 + 105].copy_from_slice(&5u64.to_le_bytes()); // config_version
+        data[8 + 153..8 + 161].copy_from_slice(&42_000u64.to_le_bytes()); // staked_sol
+        let last = LegacyVaultState::SPACE - 8;
+        data[last..LegacyVaultState::SPACE].copy_from_slice(&9u64.to_le_bytes()); // last keeper bond
+
+        assert!(LegacyVaultState::try_from_slice(&data[8..LegacyVaultState::SPACE]).is_ok(), "SPACE is the Borsh size");
+        let (old_version, legacy) = read_vault_header(&data[..LegacyVaultState::SPACE]).unwrap();
+        assert_eq!(old_version, 0, "byte 40 is pending_authority, not a version");
+        let legacy = legacy.expect("legacy length should decode the Borsh layout");
+        assert_eq!(legacy.pending_authority, pending);
+
+        // realloc'd tail is already zero; the upgrade rewrites the head
+        let state = upgrade_vault_data(data, Some(legacy), 1_700_000_000);
+        assert_eq!(state.layout_version, VAULT_LAYOUT_VERSION);
+        assert_eq!(state.authority, authority);
+        assert_eq!(state.pending_authority, pending);
+        assert_eq!(state.staked_sol, 42_000);
+        assert_eq!(state.keeper_bond_deposited_lamports[MAX_KEEPERS - 1], 9);
+        assert_eq!(state.config_version, 5 + 1, "one bump for the legacy upgrade step");
+        assert_eq!(state.returns_window, N_RETURNS as u16);
+        assert_eq!(state.max_usdc_depeg_bps, DEFAULT_MAX_USDC_DEPEG_BPS);
+        assert_eq!(state.last_fee_ts, 1_700_000_000);
+        assert_eq!(state.fee_recipient, authority);
+        // no oracle accounts were pinned before; updates wait for set_oracle_accounts
+        assert!(matches!(state.require_oracle_accounts_set(), Err(e) if e == error!(ErrorCode::OracleAccountsNotSet)));
+        assert_eq!(read_vault_header(data).unwrap().0, VAULT_LAYOUT_VERSION);
+    }
+
+    #[test]
+    fn realized_vol_is_annualized_past_the_score_cap() {
+        // +-8% a day, alternating: 8% * sqrt(365) per year (x sqrt(n / (n - 1)) for the dof)
+        let day = SECONDS_PER_DAY as u32;
+        let samples: Vec<ReturnSample> = (0..32)
+            .map(|i| ReturnSample {
+                ret_fp: if i % 2 == 0 { 80_000_000 } else { -80_000_000 },
+                elapsed_secs: day,
+            })
+            .collect();
+        let expected = 0.08 * (365.0f64 * 32.0 / 31.0).sqrt() * 10_000.0;
+
+        let bps = stdev_vol_bps(&samples).unwrap();
+        assert!((bps as f64 - expected).abs() <= 2.0, "{bps} vs {expected}");
+        assert!(bps > MAX_VOL_BPS, "annualized vol is not clamped at the score cap");
+
+        // same returns at an hourly spacing: sqrt(24) times the annual vol, up to the output cap
+        let hourly: Vec<ReturnSample> = samples.iter().map(|x| ReturnSample { elapsed_secs: 3_600, ..*x }).collect();
+        assert_eq!(stdev_vol_bps(&hourly).unwrap(), MAX_ANNUAL_VOL_BPS);
+    }
+
+    #[test]
+    fn ln_ratio_matches_known_logs() {
+        let p0 = 100 * FP as i128;
+        // (price ratio x 1e4, ln at RET_FP_SCALE)
+        for (ratio, ln) in [
+            (10_000, 0),
+            (10_100, 9_950_331),
+            (11_000, 95_310_180),
+            (9_000, -105_360_516),
+            (12_000, 182_321_557),
+            (8_000, -223_143_551),
+        ] {
+            let got = ln_ratio_fp(p0 * ratio / 10_000, p0).unwrap();
+            assert!((got - ln).abs() <= 2, "ln({ratio}e-4) = {got}, want {ln}");
+        }
+        // past the series range: the caller's return clamp applies
+        assert_eq!(ln_ratio_fp(2 * p0, p0).unwrap(), MAX_RETURN_ABS_FP as i128);
+        assert_eq!(ln_ratio_fp(p0 / 2, p0).unwrap(), -(MAX_RETURN_ABS_FP as i128));
+    }
+
+    #[test]
+    fn price_bars_left_over_a_feed_gap_age_out() {
+        let mut bars = PriceBars {
+            vault: Pubkey::default(),
+            bump: 0,
+            bar_secs: 60,
+            capacity: 4,
+            head: 0,
+            bar_count: 0,
+            current: OhlcBar::default(),
+            bars: vec![OhlcBar::default(); 4],
+        };
+        for (ts, px) in [(0, 100), (30, 101), (60, 100), (90, 102), (120, 100)] {
+            bars.observe(px * FP, ts);
+        }
+        assert_eq!(bars.usable_bar_count(120), 2);
+        // both closed bars are within capacity * bar_secs of now...
+        assert_eq!(bars.usable_bar_count(240), 2);
+        // ...until the window has moved past them
+        assert_eq!(bars.usable_bar_count(300), 1);
+        assert_eq!(bars.usable_bar_count(360), 0);
+        assert_eq!(range_vol_bps(VolMode::Parkinson as u8, &bars, 360).unwrap(), 0);
+    }
+
+    struct StubSource(Option<i64>);
+
+    impl OracleSource for StubSource {
+        fn read(&self, _acct: &AccountInfo) -> Result<OracleReading> {
+            let price_fp = self.0.ok_or(ErrorCode::InvalidParams)?;
+            Ok(OracleReading { price_fp, ema_fp: price_fp, conf_fp: 0, publish_time: 1_000, reject_reason: 0 })
+        }
+    }
+
+    #[test]
+    fn median_skips_an_unreadable_feed_but_not_a_missing_one() {
+        let (key, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut lamports, mut data) = (0u64, [0u8; 0]);
+        let acct = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        let (good, broken) = (StubSource(Some(100 * FP)), StubSource(None));
+        let two_pyth = ORACLE_FEED_BIT_SOL_USD | ORACLE_FEED_BIT_SOL_USDC;
+        let median = |mask: u8, a: &StubSource, b: &StubSource| {
+            read_oracle_median(mask, 1, 500, a, &acct, b, &acct, None, None, key, 0, 1_000, 60, 200, 2000, 0)
+        };
+
+        let agg = median(two_pyth, &broken, &good).unwrap();
+        assert!(agg.ok);
+        assert_eq!((agg.feeds_read, agg.feeds_used), (2, 1));
+        assert_eq!(agg.reading.price_fp, 100 * FP);
+
+        let agg = median(two_pyth, &broken, &broken).unwrap();
+        assert!(!agg.ok);
+        assert_eq!(agg.reason, ORACLE_REASON_NO_QUORUM);
+
+        // the switchboard bit is set but its account was not passed
+        let missing = median(two_pyth | ORACLE_FEED_BIT_SWITCHBOARD, &good, &good);
+        assert!(matches!(missing, Err(e) if e == error!(ErrorCode::OracleSourceUnavailable)));
+    }
+
+    #[test]
+    fn pyth_pull_reads_a_verified_price_update() {
+        let feed_id = [7u8; 32];
+        let mut data = hashv(&[b"account:PriceUpdateV2"]).to_bytes()[..8].to_vec();
+        data.extend_from_slice(&[0u8; 32]); // write_authority
+        data.push(1); // PriceUpdateVerification::Full
+        data.extend_from_slice(&feed_id);
+        data.extend_from_slice(&15_012_345_678i64.to_le_bytes()); // price, expo -8 -> 150.12345678
+        data.extend_from_slice(&9_000_000u64.to_le_bytes()); // conf
+        data.extend_from_slice(&(-8i32).to_le_bytes());
+        data.extend_from_slice(&1_000i64.to_le_bytes()); // publish_time
+        data.extend_from_slice(&990i64.to_le_bytes()); // prev_publish_time
+        data.extend_from_slice(&15_000_000_000i64.to_le_bytes()); // ema_price
+        data.extend_from_slice(&8_000_000u64.to_le_bytes()); // ema_conf
+        data.extend_from_slice(&42u64.to_le_bytes()); // posted_slot
+
+        let key = Pubkey::new_unique();
+        let mut lamports = 0u64;
+        let acct = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &PYTH_RECEIVER_PROGRAM_ID, false, 0);
+        let source = PythPullSource {
+            feed_id,
+            min_verification: PullVerification::Full as u8,
+            min_signatures: 0,
+        };
+
+        let r = source.read(&acct).unwrap();
+        assert_eq!((r.price_fp, r.ema_fp, r.conf_fp), (150_123_456, 150 * FP, 90_000));
+        assert_eq!((r.publish_time, r.reject_reason), (1_000, 0));
+
+        // another feed id in the same account is refused
+        let other = PythPullSource { feed_id: [8u8; 32], ..source };
+        assert!(matches!(other.read(&acct), Err(e) if e == error!(ErrorCode::PriceUpdateMismatch)));
+
+        // a slot with no feed id pinned is refused, not an error (Auto can fall back past it)
+        let unset = PythPullSource { feed_id: [0u8; 32], ..source };
+        assert_eq!(unset.read(&acct).unwrap().reject_reason, ORACLE_REASON_NO_FEED_ID);
+    }
+}
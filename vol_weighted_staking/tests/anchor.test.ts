@@ -1,3 +1,5 @@
+import { createMint, getAccount, getOrCreateAssociatedTokenAccount, mintTo, TOKEN_PROGRAM_ID } from "@solana/spl-token";
+
 declare function setTimeout(handler: (...args: any[]) => void, timeout?: number): any;
 
 function assert(cond: any, msg?: string): asserts cond {
@@ -12,6 +14,11 @@ const PRICE_FP_SCALE = 1_000_000;
 const ORACLE_FEED_SOL_USD = new PublicKey("J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix");
 const ORACLE_FEED_SOL_USDC = new PublicKey("Gnt27xtC473ZT2Mw5u8wZ68Z3gULkSTb5DuxJy7eJotD");
 
+// Pyth pull oracle (SOL/USD feed id)
+const PYTH_SOL_USD_FEED_ID = Array.from(
+  Buffer.from("ef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d", "hex")
+);
+
 const ERR = {
   InvalidParams: "Invalid parameters",
   Paused: "Program is paused",
@@ -21,6 +28,21 @@ const ERR = {
   CapExceeded: "Cap exceeded",
   PolicyCooldown: "Policy update cooldown not met",
   WrongRequestId: "Wrong request id",
+  EmergencyWithdrawDisabled: "Emergency withdraw not enabled",
+  NothingToClaim: "Nothing to claim",
+  TicketNotDue: "Unstake ticket not claimable yet",
+  VaultAlreadyMigrated: "Vault account already on the current layout",
+  PriceBarsRequired: "Range-based vol mode needs the price bars account",
+  PriceUpdateMismatch: "Pyth price update mismatch (owner, feed id or verification level)",
+  ConstraintAddress: "An address constraint was violated",
+  OracleSourceUnavailable: "Oracle source unavailable",
+  OraclePriceUnsafe: "Oracle price not usable for share pricing",
+  CustodyDisabled: "Token custody not enabled in this build",
+  ValidatorRegistryRequired: "Vault has a validator registry; pass it",
+  AssetBookRequired: "Vault has an asset book; pass it",
+  ConstraintHasOne: "A has one constraint was violated",
+  VaultNotRegistered: "Vault not in the registry",
+  OracleAccountsNotSet: "Oracle accounts not set",
 };
 
 const delayMs = (ms: number) => new Promise<void>((resolve) => setTimeout(resolve, ms));
@@ -41,9 +63,77 @@ function getDeployedProgramId(): any {
   return pg.program.programId;
 }
 
-function deriveVaultPda(authorityPk: any) {
+function deriveVaultPda(authorityPk: any, vaultIndex = 0) {
+  const programId = getDeployedProgramId();
+  const idx = Buffer.alloc(2);
+  idx.writeUInt16LE(vaultIndex);
+  return PublicKey.findProgramAddressSync([Buffer.from("vault"), authorityPk.toBuffer(), idx], programId);
+}
+
+function deriveVaultRegistryPda() {
+  return PublicKey.findProgramAddressSync([Buffer.from("vault_registry")], getDeployedProgramId());
+}
+
+function vaultName(label: string) {
+  const out = Buffer.alloc(32);
+  out.write(label.slice(0, 32));
+  return Array.from(out);
+}
+
+function derivePositionPda(vaultStatePk: any, ownerPk: any) {
+  const programId = getDeployedProgramId();
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("position"), vaultStatePk.toBuffer(), ownerPk.toBuffer()],
+    programId
+  );
+}
+
+function deriveWithdrawQueuePda(vaultStatePk: any) {
+  const programId = getDeployedProgramId();
+  return PublicKey.findProgramAddressSync([Buffer.from("withdraw_queue"), vaultStatePk.toBuffer()], programId);
+}
+
+function deriveUnstakeTicketPda(vaultStatePk: any, ticketId: any) {
+  const programId = getDeployedProgramId();
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("unstake_ticket"), vaultStatePk.toBuffer(), ticketId.toArrayLike(Buffer, "le", 8)],
+    programId
+  );
+}
+
+function deriveReturnsRingPda(vaultStatePk: any) {
+  const programId = getDeployedProgramId();
+  return PublicKey.findProgramAddressSync([Buffer.from("returns_ring"), vaultStatePk.toBuffer()], programId);
+}
+
+function deriveManualPricePda(vaultStatePk: any) {
+  const programId = getDeployedProgramId();
+  return PublicKey.findProgramAddressSync([Buffer.from("manual_price"), vaultStatePk.toBuffer()], programId);
+}
+
+function derivePriceBarsPda(vaultStatePk: any) {
+  const programId = getDeployedProgramId();
+  return PublicKey.findProgramAddressSync([Buffer.from("price_bars"), vaultStatePk.toBuffer()], programId);
+}
+
+function deriveAssetBookPda(vaultStatePk: any) {
+  const programId = getDeployedProgramId();
+  return PublicKey.findProgramAddressSync([Buffer.from("asset_book"), vaultStatePk.toBuffer()], programId);
+}
+
+function deriveCustodyPdas(vaultStatePk: any) {
   const programId = getDeployedProgramId();
-  return PublicKey.findProgramAddressSync([Buffer.from("vault"), authorityPk.toBuffer()], programId);
+  const seeds = (label: string) => [Buffer.from(label), vaultStatePk.toBuffer()];
+  return {
+    custodyTokenAccount: PublicKey.findProgramAddressSync(seeds("custody"), programId)[0],
+    custodyAuthority: PublicKey.findProgramAddressSync(seeds("custody_authority"), programId)[0],
+    shareMint: PublicKey.findProgramAddressSync(seeds("share_mint"), programId)[0],
+  };
+}
+
+function deriveValidatorRegistryPda(vaultStatePk: any) {
+  const programId = getDeployedProgramId();
+  return PublicKey.findProgramAddressSync([Buffer.from("validator_registry"), vaultStatePk.toBuffer()], programId);
 }
 
 async function fetchVault(vaultStatePda: any) {
@@ -113,6 +203,53 @@ async function ensureFunded(dest: any, minSol: number) {
   await fundFromWallet(dest, minSol);
 }
 
+// fresh vault for `owner` (vault index 0) with a returns ring and an accepted manual mark
+async function initFreshVaultWithMark(owner: any, overrides: Partial<any> = {}) {
+  await ensureFunded(owner.publicKey, 0.5);
+  const [vault] = deriveVaultPda(owner.publicKey);
+  const [ring] = deriveReturnsRingPda(vault);
+  const [manual] = deriveManualPricePda(vault);
+  const authAccounts = { authority: owner.publicKey, vaultState: vault };
+
+  await pg.program.methods
+    .initializeVault(defaultInitParams({ oracleFeedChoice: 5, ...overrides }))
+    .accounts({ ...authAccounts, vaultRegistry: null, systemProgram: SystemProgram.programId })
+    .signers([owner])
+    .rpc();
+  await pg.program.methods
+    .initializeReturnsRing(256)
+    .accounts({ ...authAccounts, returnsRing: ring, systemProgram: SystemProgram.programId })
+    .signers([owner])
+    .rpc();
+  await pg.program.methods
+    .initializeManualPriceFeed()
+    .accounts({ ...authAccounts, manualPrice: manual, systemProgram: SystemProgram.programId })
+    .signers([owner])
+    .rpc();
+  await pg.program.methods
+    .setManualPrice(new BN(150 * PRICE_FP_SCALE), new BN(0), new BN(150_000))
+    .accounts({ ...authAccounts, manualPrice: manual })
+    .signers([owner])
+    .rpc();
+  await pg.program.methods
+    .updateOraclePrice()
+    .accounts({
+      signer: owner.publicKey,
+      vaultState: vault,
+      returnsRing: ring,
+      priceBars: null,
+      pythSolUsd: ORACLE_FEED_SOL_USD,
+      pythSolUsdc: ORACLE_FEED_SOL_USDC,
+      switchboardFeed: null,
+      pythUsdcUsd: null,
+      manualPrice: manual,
+    })
+    .signers([owner])
+    .rpc();
+
+  return { vault, ring, manual, authAccounts };
+}
+
 async function ensurePythFeedsExist() {
   const usd = await pg.connection.getAccountInfo(ORACLE_FEED_SOL_USD, "confirmed");
   const usdc = await pg.connection.getAccountInfo(ORACLE_FEED_SOL_USDC, "confirmed");
@@ -158,6 +295,9 @@ function defaultInitParams(overrides: Partial<any> = {}) {
     maxPriceAgeSlots: new BN(120),
     maxConfidenceBps: 200,
     maxPriceJumpBps: 2000,
+    oracleSolUsd: ORACLE_FEED_SOL_USD,
+    oracleSolUsdc: ORACLE_FEED_SOL_USDC,
+    oracleSwitchboardFeed: PublicKey.default,
 
     targetDeltaBps: 10_000,
     lstBetaFp: new BN(1 * PRICE_FP_SCALE),
@@ -167,6 +307,10 @@ function defaultInitParams(overrides: Partial<any> = {}) {
 
     maxUpdatesPerEpoch: 50,
     keeperBondRequiredLamports: new BN(0),
+
+    unstakeCooldownEpochs: new BN(1),
+    vaultIndex: 0,
+    name: vaultName("vwsa-default"),
   };
 
   return { ...base, ...overrides };
@@ -180,8 +324,94 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
 
   let vaultStatePda: any;
   let vaultBump = 0;
+  let positionPda: any;
+  let withdrawQueuePda: any;
+  let returnsRingPda: any;
+  let priceBarsPda: any;
+  let manualPricePda: any;
+  let assetBookPda: any;
   let pythOk = true;
 
+  // publish `priceFp` (default: the current mark) through the manual feed and accept it
+  async function setManualMark(priceFp?: number) {
+    const authAccounts = { authority: authority.publicKey, vaultState: vaultStatePda };
+    const v = await fetchVault(vaultStatePda);
+    const p = priceFp ?? (v.oraclePriceFp.toNumber() > 0 ? v.oraclePriceFp.toNumber() : 150 * PRICE_FP_SCALE);
+    await pg.program.methods
+      .setManualPrice(new BN(p), new BN(0), new BN(Math.floor(p / 1000)))
+      .accounts({ ...authAccounts, manualPrice: manualPricePda })
+      .rpc();
+    await pg.program.methods
+      .setOracleConfig(5, v.maxPriceAgeSlots, v.maxConfidenceBps, v.maxPriceJumpBps)
+      .accounts(authAccounts)
+      .rpc();
+    try {
+      await pg.program.methods
+        .updateOraclePrice()
+        .accounts({
+          signer: keeper1.publicKey,
+          vaultState: vaultStatePda,
+          returnsRing: returnsRingPda,
+          priceBars: priceBarsPda,
+          pythSolUsd: ORACLE_FEED_SOL_USD,
+          pythSolUsdc: ORACLE_FEED_SOL_USDC,
+          switchboardFeed: null,
+          pythUsdcUsd: null,
+          manualPrice: manualPricePda,
+        })
+        .signers([keeper1])
+        .rpc();
+    } finally {
+      await pg.program.methods
+        .setOracleConfig(v.oracleFeedChoice, v.maxPriceAgeSlots, v.maxConfidenceBps, v.maxPriceJumpBps)
+        .accounts(authAccounts)
+        .rpc();
+    }
+  }
+
+  // record `n` alternating +/-1% manual marks, spaced past min_return_spacing_slots
+  async function recordManualReturns(owner: any, vault: any, ring: any, manual: any, n: number) {
+    const spacing = defaultInitParams().minReturnSpacingSlots.toNumber();
+    for (let i = 0; i < n; i++) {
+      await waitForSlots(spacing + 1);
+      const priceFp = (i % 2 === 0 ? 151_500 : 148_500) * (PRICE_FP_SCALE / 1000);
+      await pg.program.methods
+        .setManualPrice(new BN(priceFp), new BN(0), new BN(Math.floor(priceFp / 1000)))
+        .accounts({ authority: owner.publicKey, vaultState: vault, manualPrice: manual })
+        .signers([owner])
+        .rpc();
+      await pg.program.methods
+        .updateOraclePrice()
+        .accounts({
+          signer: owner.publicKey,
+          vaultState: vault,
+          returnsRing: ring,
+          priceBars: null,
+          pythSolUsd: ORACLE_FEED_SOL_USD,
+          pythSolUsdc: ORACLE_FEED_SOL_USDC,
+          switchboardFeed: null,
+          pythUsdcUsd: null,
+          manualPrice: manual,
+        })
+        .signers([owner])
+        .rpc();
+    }
+  }
+
+  // run one epoch update and return its EpochUpdated event (must carry a realized update)
+  async function epochUpdateWithRealized(owner: any, vault: any, ring: any) {
+    const { events } = await withEventListener("EpochUpdated", async () =>
+      pg.program.methods
+        .updateEpochAndPolicy()
+        .accounts({ signer: owner.publicKey, vaultState: vault, returnsRing: ring, priceBars: null })
+        .signers([owner])
+        .rpc()
+    );
+    const e = events.find((x: any) => x.realizedUpdated);
+    assert(!!e, "EpochUpdated with a realized update not observed");
+    return e;
+  }
+
   it("Setup: fund actors and derive PDA", async () => {
     await ensureFunded(user.publicKey, 0.25);
     await ensureFunded(keeper1.publicKey, 0.25);
@@ -194,6 +424,13 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
     console.log(`ProgramID = ${getDeployedProgramId().toBase58()}`);
     console.log(`vaultState PDA = ${vaultStatePda.toBase58()} bump=${vaultBump}`);
 
+    [positionPda] = derivePositionPda(vaultStatePda, authority.publicKey);
+    [withdrawQueuePda] = deriveWithdrawQueuePda(vaultStatePda);
+    [returnsRingPda] = deriveReturnsRingPda(vaultStatePda);
+    [priceBarsPda] = derivePriceBarsPda(vaultStatePda);
+    [manualPricePda] = deriveManualPricePda(vaultStatePda);
+    [assetBookPda] = deriveAssetBookPda(vaultStatePda);
+
     pythOk = await ensurePythFeedsExist();
     assert(!!vaultStatePda, "vaultStatePda missing");
   });
@@ -216,6 +453,7 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
           .accounts({
             authority: authority.publicKey,
             vaultState: vaultStatePda,
+            vaultRegistry: null,
             systemProgram: SystemProgram.programId,
           })
           .rpc();
@@ -226,6 +464,30 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
       const v = await fetchVault(vaultStatePda);
       assert(v.authority.equals(authority.publicKey), "authority mismatch");
       assert(v.configVersion.toNumber() >= 1, "configVersion should be >= 1");
+      assert(v.oracleSolUsd.equals(ORACLE_FEED_SOL_USD), "SOL/USD account should be pinned at init");
+      assert(v.oracleSolUsdc.equals(ORACLE_FEED_SOL_USDC), "SOL/USDC account should be pinned at init");
+    });
+
+    it("Fails without pinned oracle accounts", async () => {
+      const tempAuth = Keypair.generate();
+      await ensureFunded(tempAuth.publicKey, 0.15);
+
+      const [pda] = deriveVaultPda(tempAuth.publicKey);
+      const params = defaultInitParams({ oracleSolUsd: PublicKey.default });
+
+      await expectFail(
+        pg.program.methods
+          .initializeVault(params)
+          .accounts({
+            authority: tempAuth.publicKey,
+            vaultState: pda,
+            vaultRegistry: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([tempAuth])
+          .rpc(),
+        ERR.InvalidParams
+      );
     });
 
     it("Fails with invalid policy bounds (min > max)", async () => {
@@ -241,6 +503,7 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
           .accounts({
             authority: tempAuth.publicKey,
             vaultState: pda,
+            vaultRegistry: null,
             systemProgram: SystemProgram.programId,
           })
           .signers([tempAuth])
@@ -262,6 +525,7 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
           .accounts({
             authority: tempAuth.publicKey,
             vaultState: pda,
+            vaultRegistry: null,
             systemProgram: SystemProgram.programId,
           })
           .signers([tempAuth])
@@ -283,6 +547,7 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
           .accounts({
             authority: tempAuth.publicKey,
             vaultState: pda,
+            vaultRegistry: null,
             systemProgram: SystemProgram.programId,
           })
           .signers([tempAuth])
@@ -305,6 +570,7 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
           .accounts({
             authority: tempAuth.publicKey,
             vaultState: pda,
+            vaultRegistry: null,
             systemProgram: SystemProgram.programId,
           })
           .signers([tempAuth])
@@ -316,6 +582,237 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
         assert(v.oracleFeedChoice === c, `oracleFeedChoice expected ${c} got ${v.oracleFeedChoice}`);
       }
     });
+
+    it("Lists a second vault of the same authority in the global registry", async () => {
+      const [registryPda] = deriveVaultRegistryPda();
+      if (!(await pg.connection.getAccountInfo(registryPda, "confirmed"))) {
+        await pg.program.methods
+          .initializeVaultRegistry()
+          .accounts({ payer: authority.publicKey, vaultRegistry: registryPda, systemProgram: SystemProgram.programId })
+          .rpc();
+      }
+
+      const [variantPda] = deriveVaultPda(authority.publicKey, 1);
+      if (!(await pg.connection.getAccountInfo(variantPda, "confirmed"))) {
+        await pg.program.methods
+          .initializeVault(defaultInitParams({ vaultIndex: 1, name: vaultName("vwsa-variant-1"), minReserveBps: 2000 }))
+          .accounts({
+            authority: authority.publicKey,
+            vaultState: variantPda,
+            vaultRegistry: registryPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      }
+      const syncAccounts = (vault: any) => ({ authority: authority.publicKey, vaultState: vault, vaultRegistry: registryPda });
+      await pg.program.methods.syncVaultRegistry().accounts(syncAccounts(vaultStatePda)).rpc();
+      await pg.program.methods.syncVaultRegistry().accounts(syncAccounts(variantPda)).rpc();
+
+      // only the vault authority can list its vault
+      await expectFail(
+        pg.program.methods
+          .syncVaultRegistry()
+          .accounts({ authority: keeper1.publicKey, vaultState: variantPda, vaultRegistry: registryPda })
+          .signers([keeper1])
+          .rpc(),
+        ERR.ConstraintHasOne
+      );
+
+      const r = await pg.program.account.vaultRegistry.fetch(registryPda);
+      const entries = (r.entries as any[]).slice(0, r.vaultCount);
+      const main = entries.find((e) => e.vault.equals(vaultStatePda));
+      const variant = entries.find((e) => e.vault.equals(variantPda));
+      assert(!!main && !!variant, "both vaults should be listed");
+      assert(variant.vaultIndex === 1, "variant index mismatch");
+      assert(!Buffer.from(main.configHash).equals(Buffer.from(variant.configHash)), "variants should differ by config hash");
+
+      // only the vault's current authority can drop its entry (and list it again)
+      await expectFail(
+        pg.program.methods
+          .deregisterVault()
+          .accounts({ authority: keeper1.publicKey, vaultState: variantPda, vaultRegistry: registryPda })
+          .signers([keeper1])
+          .rpc(),
+        ERR.ConstraintHasOne
+      );
+      const deregister = (vault: any) => pg.program.methods.deregisterVault().accounts(syncAccounts(vault)).rpc();
+      await deregister(variantPda);
+      const r1 = await pg.program.account.vaultRegistry.fetch(registryPda);
+      assert(r1.vaultCount === r.vaultCount - 1, "deregister should free the slot");
+      assert(!(r1.entries as any[]).slice(0, r1.vaultCount).some((e) => e.vault.equals(variantPda)), "variant should be gone");
+      await expectFail(deregister(variantPda), ERR.VaultNotRegistered);
+      await pg.program.methods.syncVaultRegistry().accounts(syncAccounts(variantPda)).rpc();
+    });
+
+    it("Stamps the layout version and rejects migrating a current-layout vault", async () => {
+      const v = await fetchVault(vaultStatePda);
+      assert(v.layoutVersion === 1, "layout version should be stamped at init");
+
+      await expectFail(
+        pg.program.methods
+          .migrateVault()
+          .accounts({
+            authority: authority.publicKey,
+            vaultState: vaultStatePda,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc(),
+        ERR.VaultAlreadyMigrated
+      );
+    });
+
+    it("Initializes the returns ring (or reuses existing) and widens the window", async () => {
+      if (!(await pg.connection.getAccountInfo(returnsRingPda, "confirmed"))) {
+        await expectFail(
+          pg.program.methods
+            .initializeReturnsRing(2048)
+            .accounts({
+              authority: authority.publicKey,
+              vaultState: vaultStatePda,
+              returnsRing: returnsRingPda,
+              systemProgram: SystemProgram.programId,
+            })
+            .rpc(),
+          ERR.InvalidParams
+        );
+        await pg.program.methods
+          .initializeReturnsRing(256)
+          .accounts({
+            authority: authority.publicKey,
+            vaultState: vaultStatePda,
+            returnsRing: returnsRingPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      }
+      const ring = await pg.program.account.returnsRing.fetch(returnsRingPda);
+      assert(ring.capacity === 256 && ring.samples.length === 256, "ring capacity mismatch");
+
+      const p = defaultInitParams();
+      const volAccounts = { authority: authority.publicKey, vaultState: vaultStatePda, returnsRing: returnsRingPda, priceBars: null };
+      await expectFail(
+        pg.program.methods
+          .setVolModel(p.volMode, p.ewmaAlphaBps, p.minSamples, p.minReturnSpacingSlots, 512, 0)
+          .accounts(volAccounts)
+          .rpc(),
+        ERR.InvalidParams
+      );
+      await pg.program.methods
+        .setVolModel(p.volMode, p.ewmaAlphaBps, p.minSamples, p.minReturnSpacingSlots, 128, 0)
+        .accounts(volAccounts)
+        .rpc();
+
+      const v = await fetchVault(vaultStatePda);
+      assert(v.returnsWindow === 128, "returns window should update");
+    });
+
+    it("Selects log returns in the vol model (config hash changes)", async () => {
+      const p = defaultInitParams();
+      const volAccounts = { authority: authority.publicKey, vaultState: vaultStatePda, returnsRing: returnsRingPda, priceBars: null };
+      await expectFail(
+        pg.program.methods
+          .setVolModel(p.volMode, p.ewmaAlphaBps, p.minSamples, p.minReturnSpacingSlots, 128, 2)
+          .accounts(volAccounts)
+          .rpc(),
+        ERR.InvalidParams
+      );
+      // a single sample has no n - 1 estimate
+      await expectFail(
+        pg.program.methods
+          .setVolModel(p.volMode, p.ewmaAlphaBps, 1, p.minReturnSpacingSlots, 128, 1)
+          .accounts(volAccounts)
+          .rpc(),
+        ERR.InvalidParams
+      );
+
+      const v0 = await fetchVault(vaultStatePda);
+      await pg.program.methods
+        .setVolModel(p.volMode, p.ewmaAlphaBps, p.minSamples, p.minReturnSpacingSlots, 128, 1)
+        .accounts(volAccounts)
+        .rpc();
+
+      const v1 = await fetchVault(vaultStatePda);
+      assert(v1.returnKind === 1, "return kind should switch to log");
+      assert(!Buffer.from(v1.configHash).equals(Buffer.from(v0.configHash)), "config hash should change");
+    });
+
+    it("Initializes OHLC price bars (or reuses existing) and accepts range vol modes", async () => {
+      if (!(await pg.connection.getAccountInfo(priceBarsPda, "confirmed"))) {
+        const barAccounts = {
+          authority: authority.publicKey,
+          vaultState: vaultStatePda,
+          priceBars: priceBarsPda,
+          systemProgram: SystemProgram.programId,
+        };
+        await expectFail(
+          pg.program.methods.initializePriceBars(4, 60).accounts(barAccounts).rpc(),
+          ERR.InvalidParams
+        );
+        await pg.program.methods.initializePriceBars(64, 60).accounts(barAccounts).rpc();
+      }
+      const bars = await pg.program.account.priceBars.fetch(priceBarsPda);
+      assert(bars.capacity === 64 && bars.bars.length === 64 && bars.barSecs === 60, "price bars mismatch");
+
+      const p = defaultInitParams();
+      const volAccounts = {
+        authority: authority.publicKey,
+        vaultState: vaultStatePda,
+        returnsRing: returnsRingPda,
+        priceBars: priceBarsPda,
+      };
+      await expectFail(
+        pg.program.methods
+          .setVolModel(6, p.ewmaAlphaBps, p.minSamples, p.minReturnSpacingSlots, 128, 1)
+          .accounts(volAccounts)
+          .rpc(),
+        ERR.InvalidParams
+      );
+      // range modes need the bars account
+      await expectFail(
+        pg.program.methods
+          .setVolModel(4, p.ewmaAlphaBps, p.minSamples, p.minReturnSpacingSlots, 128, 1)
+          .accounts({ ...volAccounts, priceBars: null })
+          .rpc(),
+        ERR.PriceBarsRequired
+      );
+      await pg.program.methods
+        .setVolModel(4, p.ewmaAlphaBps, p.minSamples, p.minReturnSpacingSlots, 128, 1)
+        .accounts(volAccounts)
+        .rpc();
+      assert((await fetchVault(vaultStatePda)).volMode === 4, "vol mode should be Garman-Klass");
+
+      await pg.program.methods
+        .setVolModel(p.volMode, p.ewmaAlphaBps, p.minSamples, p.minReturnSpacingSlots, 128, 1)
+        .accounts(volAccounts)
+        .rpc();
+    });
+
+    it("Validates GARCH stationarity and selects the Garch vol mode", async () => {
+      const authAccounts = { authority: authority.publicKey, vaultState: vaultStatePda };
+      // 0.04 annualized variance (20% vol) * (1 - 0.95)
+      const omega = new BN("2000000000000000");
+
+      await expectFail(
+        pg.program.methods.setGarchParams(omega, 600, 9400).accounts(authAccounts).rpc(),
+        ERR.InvalidParams
+      );
+      await pg.program.methods.setGarchParams(omega, 800, 8700).accounts(authAccounts).rpc();
+
+      const p = defaultInitParams();
+      const volAccounts = { authority: authority.publicKey, vaultState: vaultStatePda, returnsRing: returnsRingPda, priceBars: null };
+      await pg.program.methods
+        .setVolModel(6, p.ewmaAlphaBps, p.minSamples, p.minReturnSpacingSlots, 128, 1)
+        .accounts(volAccounts)
+        .rpc();
+
+      const v = await fetchVault(vaultStatePda);
+      assert(v.volMode === 6 && v.garchAlphaBps === 800 && v.garchBetaBps === 8700, "garch config mismatch");
+
+      await pg.program.methods
+        .setVolModel(p.volMode, p.ewmaAlphaBps, p.minSamples, p.minReturnSpacingSlots, 128, 1)
+        .accounts(volAccounts)
+        .rpc();
+    });
   });
 
   describe("Keeper Operations", () => {
@@ -374,7 +871,7 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
     it("Enforces keeper rate limit", async () => {
       await pg.program.methods
         .updateEpochAndPolicy()
-        .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda })
+        .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda, returnsRing: returnsRingPda, priceBars: priceBarsPda })
         .signers([keeper1])
         .rpc();
 
@@ -412,6 +909,45 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
   });
 
   describe("Oracle Price Updates (Pyth)", () => {
+    it("Pins the oracle accounts and rejects a substituted feed", async () => {
+      const authAccounts = { authority: authority.publicKey, vaultState: vaultStatePda };
+      await expectFail(
+        pg.program.methods
+          .setOracleAccounts(PublicKey.default, ORACLE_FEED_SOL_USDC, PublicKey.default)
+          .accounts(authAccounts)
+          .rpc(),
+        ERR.InvalidParams
+      );
+      await pg.program.methods
+        .setOracleAccounts(ORACLE_FEED_SOL_USD, ORACLE_FEED_SOL_USDC, PublicKey.default)
+        .accounts(authAccounts)
+        .rpc();
+
+      const v = await fetchVault(vaultStatePda);
+      assert(v.oracleSolUsd.equals(ORACLE_FEED_SOL_USD), "SOL/USD account should be pinned");
+      assert(v.oracleSolUsdc.equals(ORACLE_FEED_SOL_USDC), "SOL/USDC account should be pinned");
+
+      // swapped feeds fail the address constraint
+      await expectFail(
+        pg.program.methods
+          .updateOraclePrice()
+          .accounts({
+            signer: keeper1.publicKey,
+            vaultState: vaultStatePda,
+            returnsRing: returnsRingPda,
+            priceBars: priceBarsPda,
+            pythSolUsd: ORACLE_FEED_SOL_USDC,
+            pythSolUsdc: ORACLE_FEED_SOL_USD,
+            switchboardFeed: null,
+            pythUsdcUsd: null,
+            manualPrice: null,
+          })
+          .signers([keeper1])
+          .rpc(),
+        ERR.ConstraintAddress
+      );
+    });
+
     it("Updates oracle price from SOL/USD feed", async () => {
       if (!pythOk) return;
 
@@ -425,8 +961,13 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
         .accounts({
           signer: keeper1.publicKey,
           vaultState: vaultStatePda,
+          returnsRing: returnsRingPda,
+          priceBars: priceBarsPda,
           pythSolUsd: ORACLE_FEED_SOL_USD,
           pythSolUsdc: ORACLE_FEED_SOL_USDC,
+          switchboardFeed: null,
+          pythUsdcUsd: null,
+          manualPrice: null,
         })
         .signers([keeper1])
         .rpc();
@@ -451,8 +992,13 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
         .accounts({
           signer: keeper1.publicKey,
           vaultState: vaultStatePda,
+          returnsRing: returnsRingPda,
+          priceBars: priceBarsPda,
           pythSolUsd: ORACLE_FEED_SOL_USD,
           pythSolUsdc: ORACLE_FEED_SOL_USDC,
+          switchboardFeed: null,
+          pythUsdcUsd: null,
+          manualPrice: null,
         })
         .signers([keeper1])
         .rpc();
@@ -462,63 +1008,386 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
       const v = await fetchVault(vaultStatePda);
       assert(v.oraclePriceFp.toNumber() > 0, "oracle_price_fp should be > 0");
     });
-  });
 
-  describe("Epoch and Policy Updates", () => {
-    it("Enforces policy update cooldown", async () => {
-      await pg.program.methods
-        .setPolicyStability(new BN(10), 1000, 100, 2000)
-        .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
-        .rpc();
+    it("Refuses the SOL/USDC leg when the USDC/USD cross-rate is off peg", async () => {
+      const authAccounts = { authority: authority.publicKey, vaultState: vaultStatePda };
+      await expectFail(
+        pg.program.methods.setUsdcCrossRate(ORACLE_FEED_SOL_USD, Array(32).fill(0), 0).accounts(authAccounts).rpc(),
+        ERR.InvalidParams
+      );
+      if (!pythOk) return;
 
-      await pg.program.methods
-        .updateEpochAndPolicy()
-        .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda })
-        .signers([keeper1])
-        .rpc();
+      // SOL/USD stands in for USDC/USD: a "USDC" worth ~$100+ is far off peg
+      await pg.program.methods.setUsdcCrossRate(ORACLE_FEED_SOL_USD, Array(32).fill(0), 100).accounts(authAccounts).rpc();
+      await pg.program.methods.setOracleConfig(2, new BN(120), 200, 2000).accounts(authAccounts).rpc();
+      // pull mode would read the pinned USDC/USD account by feed id, and none is set
+      await expectFail(
+        pg.program.methods.setPythPullConfig(true, PYTH_SOL_USD_FEED_ID, Array(32).fill(0), 1, 0).accounts(authAccounts).rpc(),
+        ERR.InvalidParams
+      );
 
+      const oracleAccounts = {
+        signer: keeper1.publicKey,
+        vaultState: vaultStatePda,
+        returnsRing: returnsRingPda,
+        priceBars: priceBarsPda,
+        pythSolUsd: ORACLE_FEED_SOL_USD,
+        pythSolUsdc: ORACLE_FEED_SOL_USDC,
+        switchboardFeed: null,
+        pythUsdcUsd: ORACLE_FEED_SOL_USD,
+        manualPrice: null,
+      };
+      // the pinned USDC/USD feed can't be left out
       await expectFail(
         pg.program.methods
-          .updateEpochAndPolicy()
-          .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda })
+          .updateOraclePrice()
+          .accounts({ ...oracleAccounts, pythUsdcUsd: null })
           .signers([keeper1])
           .rpc(),
-        ERR.PolicyCooldown
+        ERR.OracleSourceUnavailable
       );
 
-      await waitForSlots(12);
+      const { events } = await withEventListener("oraclePriceUpdated", () =>
+        pg.program.methods.updateOraclePrice().accounts(oracleAccounts).signers([keeper1]).rpc()
+      );
+      const v = await fetchVault(vaultStatePda);
+      assert(v.oracleOk === 0 && v.oracleDegraded === 1, "off-peg USDC should refuse the SOL/USDC leg");
+      if (events.length > 0) {
+        assert(events[0].impliedUsdcUsdFp.toNumber() > 2 * PRICE_FP_SCALE, "event should report the USDC/USD rate used");
+      }
 
-      await pg.program.methods
-        .updateEpochAndPolicy()
-        .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda })
-        .signers([keeper1])
-        .rpc();
+      // unpin (USDC 1:1) and recover on the USD feed
+      await pg.program.methods.setUsdcCrossRate(PublicKey.default, Array(32).fill(0), 100).accounts(authAccounts).rpc();
+      await pg.program.methods.setOracleConfig(1, new BN(120), 200, 2000).accounts(authAccounts).rpc();
+      const { events: recovered } = await withEventListener("oraclePriceUpdated", () =>
+        pg.program.methods
+          .updateOraclePrice()
+          .accounts({ ...oracleAccounts, pythUsdcUsd: null })
+          .signers([keeper1])
+          .rpc()
+      );
+      if (recovered.length > 0) {
+        assert(recovered[0].impliedUsdcUsdFp.toNumber() === PRICE_FP_SCALE, "no pinned feed should report 1:1, not 0");
+      }
+    });
+
+    it("Updates oracle price from the manual source (no external feed)", async () => {
+      const authAccounts = { authority: authority.publicKey, vaultState: vaultStatePda };
+      if (!(await pg.connection.getAccountInfo(manualPricePda, "confirmed"))) {
+        await pg.program.methods
+          .initializeManualPriceFeed()
+          .accounts({ ...authAccounts, manualPrice: manualPricePda, systemProgram: SystemProgram.programId })
+          .rpc();
+      }
+
+      await expectFail(
+        pg.program.methods
+          .setManualPrice(new BN(0), new BN(0), new BN(0))
+          .accounts({ ...authAccounts, manualPrice: manualPricePda })
+          .rpc(),
+        ERR.InvalidParams
+      );
+
+      // stay inside the jump gate when a Pyth price was already accepted
+      const last = (await fetchVault(vaultStatePda)).oraclePriceFp.toNumber();
+      const priceFp = last > 0 ? last : 150 * PRICE_FP_SCALE;
+      await pg.program.methods
+        .setManualPrice(new BN(priceFp), new BN(0), new BN(Math.floor(priceFp / 1000)))
+        .accounts({ ...authAccounts, manualPrice: manualPricePda })
+        .rpc();
+
+      await pg.program.methods.setOracleConfig(5, new BN(120), 200, 2000).accounts(authAccounts).rpc();
+      await pg.program.methods
+        .updateOraclePrice()
+        .accounts({
+          signer: keeper1.publicKey,
+          vaultState: vaultStatePda,
+          returnsRing: returnsRingPda,
+          priceBars: priceBarsPda,
+          pythSolUsd: ORACLE_FEED_SOL_USD,
+          pythSolUsdc: ORACLE_FEED_SOL_USDC,
+          switchboardFeed: null,
+          pythUsdcUsd: null,
+          manualPrice: manualPricePda,
+        })
+        .signers([keeper1])
+        .rpc();
+
+      const v = await fetchVault(vaultStatePda);
+      assert(v.oraclePriceFp.toNumber() === priceFp, "manual price should be accepted");
+      assert(v.oracleOk === 1, "manual price should pass the gates");
+
+      await pg.program.methods.setOracleConfig(1, new BN(120), 200, 2000).accounts(authAccounts).rpc();
+    });
+
+    it("Rejects legacy price accounts once Pyth pull mode is pinned", async () => {
+      const authAccounts = { authority: authority.publicKey, vaultState: vaultStatePda };
+      const zeroId = Array(32).fill(0);
+
+      // enabling needs at least one feed id
+      await expectFail(
+        pg.program.methods
+          .setPythPullConfig(true, zeroId, zeroId, 1, 0)
+          .accounts(authAccounts)
+          .rpc(),
+        ERR.InvalidParams
+      );
+
+      await pg.program.methods
+        .setPythPullConfig(true, PYTH_SOL_USD_FEED_ID, zeroId, 1, 0)
+        .accounts(authAccounts)
+        .rpc();
+      await pg.program.methods.setOracleConfig(1, new BN(120), 200, 2000).accounts(authAccounts).rpc();
+
+      const oracleAccounts = {
+        signer: keeper1.publicKey,
+        vaultState: vaultStatePda,
+        returnsRing: returnsRingPda,
+        priceBars: priceBarsPda,
+        pythSolUsd: ORACLE_FEED_SOL_USD,
+        pythSolUsdc: ORACLE_FEED_SOL_USDC,
+        switchboardFeed: null,
+        pythUsdcUsd: null,
+        manualPrice: null,
+      };
+      // the legacy push account is not owned by the receiver program
+      await expectFail(
+        pg.program.methods.updateOraclePrice().accounts(oracleAccounts).signers([keeper1]).rpc(),
+        ERR.PriceUpdateMismatch
+      );
+
+      // the SOL/USDC slot has no feed id: refused (degraded, reason 24), not a failed tx
+      await pg.program.methods.setOracleConfig(2, new BN(120), 200, 2000).accounts(authAccounts).rpc();
+      await pg.program.methods.updateOraclePrice().accounts(oracleAccounts).signers([keeper1]).rpc();
+      assert((await fetchVault(vaultStatePda)).oracleOk === 0, "a slot without a feed id should be refused");
+
+      await pg.program.methods
+        .setPythPullConfig(false, PYTH_SOL_USD_FEED_ID, zeroId, 1, 0)
+        .accounts(authAccounts)
+        .rpc();
+      await pg.program.methods.setOracleConfig(1, new BN(120), 200, 2000).accounts(authAccounts).rpc();
+      const v = await fetchVault(vaultStatePda);
+      assert(v.pythPullEnabled === 0, "pull mode should be off again");
+      await setManualMark();
+    });
+
+    it("Aggregates feeds by median and trips the disagreement breaker", async () => {
+      const authAccounts = { authority: authority.publicKey, vaultState: vaultStatePda };
+      const FEED_SOL_USD = 1;
+      const FEED_MANUAL = 8;
+
+      // Median needs its feed set first; a quorum above the feed count is rejected
+      await expectFail(
+        pg.program.methods.setOracleAggregation(FEED_MANUAL, 2, 50).accounts(authAccounts).rpc(),
+        ERR.InvalidParams
+      );
+      if (!pythOk) return;
+
+      await pg.program.methods.setOracleAggregation(FEED_SOL_USD | FEED_MANUAL, 2, 50).accounts(authAccounts).rpc();
+      await pg.program.methods.setOracleConfig(6, new BN(120), 200, 2000).accounts(authAccounts).rpc();
+
+      // manual feed 5% above the last accepted Pyth price
+      const last = (await fetchVault(vaultStatePda)).oraclePriceFp.toNumber();
+      const skewed = Math.floor(last * 1.05);
+      await pg.program.methods
+        .setManualPrice(new BN(skewed), new BN(0), new BN(Math.floor(skewed / 1000)))
+        .accounts({ ...authAccounts, manualPrice: manualPricePda })
+        .rpc();
+
+      const oracleAccounts = {
+        signer: keeper1.publicKey,
+        vaultState: vaultStatePda,
+        returnsRing: returnsRingPda,
+        priceBars: priceBarsPda,
+        pythSolUsd: ORACLE_FEED_SOL_USD,
+        pythSolUsdc: ORACLE_FEED_SOL_USDC,
+        switchboardFeed: null,
+        pythUsdcUsd: null,
+        manualPrice: manualPricePda,
+      };
+      const { events } = await withEventListener("oracleAggregated", () =>
+        pg.program.methods.updateOraclePrice().accounts(oracleAccounts).signers([keeper1]).rpc()
+      );
+
+      const v = await fetchVault(vaultStatePda);
+      if (events.length > 0 && events[0].feedsUsed === 2) {
+        assert(events[0].reasonCode === 21, "expected the disagreement reason code");
+        assert(v.oracleDegraded === 1, "disagreement should degrade the oracle");
+      }
+
+      // back to the single USD feed (clears the degraded flag)
+      await pg.program.methods.setOracleConfig(1, new BN(120), 200, 2000).accounts(authAccounts).rpc();
+      await pg.program.methods
+        .updateOraclePrice()
+        .accounts({ ...oracleAccounts, manualPrice: null })
+        .signers([keeper1])
+        .rpc();
+    });
+  });
+
+  describe("Epoch and Policy Updates", () => {
+    it("Enforces policy update cooldown", async () => {
+      await pg.program.methods
+        .setPolicyStability(new BN(10), 1000, 100, 2000)
+        .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
+        .rpc();
+
+      await pg.program.methods
+        .updateEpochAndPolicy()
+        .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda, returnsRing: returnsRingPda, priceBars: priceBarsPda })
+        .signers([keeper1])
+        .rpc();
+
+      await expectFail(
+        pg.program.methods
+          .updateEpochAndPolicy()
+          .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda, returnsRing: returnsRingPda, priceBars: priceBarsPda })
+          .signers([keeper1])
+          .rpc(),
+        ERR.PolicyCooldown
+      );
+
+      await waitForSlots(12);
+
+      await pg.program.methods
+        .updateEpochAndPolicy()
+        .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda, returnsRing: returnsRingPda, priceBars: priceBarsPda })
+        .signers([keeper1])
+        .rpc();
 
       await pg.program.methods
         .setPolicyStability(new BN(5), 1000, 100, 2000)
         .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
         .rpc();
     });
+
+    it("Reports the GARCH forecast horizon after real samples", async () => {
+      const owner = Keypair.generate();
+      const { vault, ring, manual, authAccounts } = await initFreshVaultWithMark(owner);
+      const p = defaultInitParams();
+
+      // 0.04 annualized variance (20% vol) * (1 - 0.95)
+      await pg.program.methods
+        .setGarchParams(new BN("2000000000000000"), 800, 8700)
+        .accounts(authAccounts)
+        .signers([owner])
+        .rpc();
+      await pg.program.methods
+        .setVolModel(6, p.ewmaAlphaBps, p.minSamples, p.minReturnSpacingSlots, 128, 1)
+        .accounts({ ...authAccounts, returnsRing: ring, priceBars: null })
+        .signers([owner])
+        .rpc();
+      await recordManualReturns(owner, vault, ring, manual, p.minSamples + 2);
+
+      const e = await epochUpdateWithRealized(owner, vault, ring);
+      assert(e.volForecastBps > 0, "Garch mode should report a forecast");
+      assert(e.forecastHorizonSteps.toNumber() >= 1, "forecast horizon should be at least one step");
+
+      const v = await fetchVault(vault);
+      assert(v.volForecastBps === e.volForecastBps, "stored forecast should match the event");
+    });
+
+    it("Reports effective samples and a vol confidence interval after real samples", async () => {
+      const owner = Keypair.generate();
+      const { vault, ring, manual } = await initFreshVaultWithMark(owner);
+      const p = defaultInitParams();
+      await recordManualReturns(owner, vault, ring, manual, p.minSamples + 2);
+
+      const e = await epochUpdateWithRealized(owner, vault, ring);
+      assert(e.effectiveSamples >= p.minSamples, `effectiveSamples ${e.effectiveSamples} below minSamples`);
+      assert(e.realizedVolBps > 0, "realized vol should be non-zero after real samples");
+      assert(
+        e.volCiLowBps <= e.realizedVolBps && e.realizedVolBps <= e.volCiHighBps,
+        `realized ${e.realizedVolBps} outside CI [${e.volCiLowBps}, ${e.volCiHighBps}]`
+      );
+      assert(e.volCiHighBps > e.volCiLowBps, "CI should have non-zero width");
+      assert(e.volCiHighBps <= 30_000, "CI upper bound should be capped at 300% annualized");
+    });
+  });
+
+  describe("User Positions", () => {
+    it("Opens a user position PDA (or reuses existing)", async () => {
+      const existing = await pg.connection.getAccountInfo(positionPda, "confirmed");
+      if (!existing) {
+        await pg.program.methods
+          .openUserPosition()
+          .accounts({
+            owner: authority.publicKey,
+            vaultState: vaultStatePda,
+            userPosition: positionPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      }
+
+      const p = await pg.program.account.userPosition.fetch(positionPda);
+      assert(p.owner.equals(authority.publicKey), "position owner mismatch");
+      assert(p.vault.equals(vaultStatePda), "position vault mismatch");
+    });
   });
 
   describe("Risk Guardrails", () => {
+    it("Refuses to price shares off a rejected or degraded mark", async () => {
+      const depositAccounts = {
+        owner: authority.publicKey,
+        vaultState: vaultStatePda,
+        userPosition: positionPda,
+        validatorRegistry: null,
+      };
+      await setManualMark();
+      const v0 = await fetchVault(vaultStatePda);
+
+      // a print 2x the mark fails the jump gate: degraded, and the mark stays put
+      await setManualMark(v0.oraclePriceFp.toNumber() * 2);
+      const v1 = await fetchVault(vaultStatePda);
+      assert(v1.oracleOk === 0 && v1.oracleDegraded === 1, "jumping print should degrade the oracle");
+      assert(v1.oraclePriceFp.eq(v0.oraclePriceFp), "rejected print must not become the mark");
+
+      await expectFail(
+        pg.program.methods.depositAndStake(new BN(1000)).accounts(depositAccounts).rpc(),
+        ERR.OraclePriceUnsafe
+      );
+
+      await setManualMark(v0.oraclePriceFp.toNumber());
+      assert((await fetchVault(vaultStatePda)).oracleOk === 1, "mark should recover");
+    });
+
     it("Deposits reserve + enforces reserve ratio", async () => {
       await pg.program.methods
         .setRiskCaps(new BN(10_000), new BN(2_000_000), new BN(400 * PRICE_FP_SCALE), 2000)
         .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
         .rpc();
 
+      const depositAccounts = {
+        owner: authority.publicKey,
+        vaultState: vaultStatePda,
+        userPosition: positionPda,
+        validatorRegistry: null,
+      };
+
+      await setManualMark();
       await expectFail(
-        pg.program.methods.depositAndStake(new BN(1000)).accounts({ vaultState: vaultStatePda }).rpc(),
+        pg.program.methods.depositAndStake(new BN(1000)).accounts(depositAccounts).rpc(),
         ERR.ReserveTooLow
       );
 
       await pg.program.methods.depositReserve(new BN(300)).accounts({ vaultState: vaultStatePda }).rpc();
-      await pg.program.methods.depositAndStake(new BN(1000)).accounts({ vaultState: vaultStatePda }).rpc();
+
+      const p0 = await pg.program.account.userPosition.fetch(positionPda);
+      const v0 = await fetchVault(vaultStatePda);
+      await pg.program.methods.depositAndStake(new BN(1000)).accounts(depositAccounts).rpc();
+
+      const p1 = await pg.program.account.userPosition.fetch(positionPda);
+      assert(p1.shares.gt(p0.shares), "position shares should increase");
+      assert(p1.depositedSol.toNumber() === p0.depositedSol.toNumber() + 1000, "deposited_sol should track deposit");
+      assert(p1.costBasisUsd.gt(p0.costBasisUsd), "cost basis should increase");
 
       const v = await fetchVault(vaultStatePda);
-      assert(v.reserveSol.toNumber() >= 300, "reserve should increase");
-      assert(v.stakedSol.toNumber() >= 1000, "staked should increase");
+      assert(v.stakedSol.eq(v0.stakedSol.add(new BN(1000))), "staked should increase by the deposit");
+      assert(v.reserveSol.gte(v0.reserveSol), "reserve should hold the deposit");
+      // every minted share is the depositor's, or locked on the first mint
+      const locked = v.lockedShares.sub(v0.lockedShares).add(v0.totalShares.isZero() ? new BN(1000) : new BN(0));
+      assert(v.totalShares.eq(v0.totalShares.add(p1.shares.sub(p0.shares)).add(locked)), "total_shares should add up");
 
       await pg.program.methods
         .setRiskCaps(new BN(10_000), new BN(2_000_000), new BN(400 * PRICE_FP_SCALE), 500)
@@ -526,14 +1395,56 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
         .rpc();
     });
 
+    it("Prices the first mint against NAV already in the vault", async () => {
+      const owner = Keypair.generate();
+      const { vault, authAccounts } = await initFreshVaultWithMark(owner);
+
+      // reserve funded before any share exists is NAV the first depositor must not capture
+      await pg.program.methods.depositReserve(new BN(5_000)).accounts({ vaultState: vault }).rpc();
+      const [position] = derivePositionPda(vault, owner.publicKey);
+      await pg.program.methods
+        .openUserPosition()
+        .accounts({ owner: owner.publicKey, vaultState: vault, userPosition: position, systemProgram: SystemProgram.programId })
+        .signers([owner])
+        .rpc();
+
+      const { events } = await withEventListener("SharesMinted", async () =>
+        pg.program.methods
+          .depositAndStake(new BN(8_000))
+          .accounts({ owner: owner.publicKey, vaultState: vault, userPosition: position, validatorRegistry: null })
+          .signers([owner])
+          .rpc()
+      );
+
+      const v = await fetchVault(vault);
+      const p = await pg.program.account.userPosition.fetch(position);
+      assert(v.lockedShares.gt(new BN(0)), "pre-existing NAV should back locked shares");
+      assert(v.totalShares.eq(p.shares.add(new BN(1000)).add(v.lockedShares)), "first mint = user + dead + locked shares");
+      if (events.length > 0) {
+        assert(events[0].navLockedShares.eq(v.lockedShares), "event should report the locked shares");
+        assert(events[0].navLockedShares.eq(events[0].navBeforeUsd), "first mint is priced 1 share per NAV unit");
+      }
+      // the depositor's slice of NAV is their deposit less the dead shares, not the reserve
+      assert(p.shares.eq(p.costBasisUsd.sub(new BN(1000))), "position should be worth its deposit only");
+    });
+
     it("Enforces staked cap", async () => {
       await pg.program.methods
         .setRiskCaps(new BN(1200), new BN(2_000_000), new BN(400 * PRICE_FP_SCALE), 500)
         .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
         .rpc();
+      await setManualMark();
 
       await expectFail(
-        pg.program.methods.depositAndStake(new BN(10_000)).accounts({ vaultState: vaultStatePda }).rpc(),
+        pg.program.methods
+          .depositAndStake(new BN(10_000))
+          .accounts({
+            owner: authority.publicKey,
+            vaultState: vaultStatePda,
+            userPosition: positionPda,
+            validatorRegistry: null,
+          })
+          .rpc(),
         ERR.CapExceeded
       );
 
@@ -544,6 +1455,204 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
     });
   });
 
+  describe("Unstake Queue", () => {
+    it("Initializes the withdraw queue (or reuses existing)", async () => {
+      const existing = await pg.connection.getAccountInfo(withdrawQueuePda, "confirmed");
+      if (!existing) {
+        await pg.program.methods
+          .initializeWithdrawQueue()
+          .accounts({
+            authority: authority.publicKey,
+            vaultState: vaultStatePda,
+            withdrawQueue: withdrawQueuePda,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      }
+
+      const q = await pg.program.account.withdrawQueue.fetch(withdrawQueuePda);
+      assert(q.vault.equals(vaultStatePda), "queue vault mismatch");
+    });
+
+    it("Enqueues an unstake ticket that is not claimable before cooldown", async () => {
+      const p0 = await pg.program.account.userPosition.fetch(positionPda);
+      if (p0.shares.isZero()) {
+        console.log("Position has no shares; skipping unstake queue.");
+        return;
+      }
+
+      await pg.program.methods
+        .setUnstakeCooldown(new BN(2))
+        .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
+        .rpc();
+
+      const shares = p0.shares.div(new BN(20)).add(new BN(1));
+      const q0 = await pg.program.account.withdrawQueue.fetch(withdrawQueuePda);
+      const [ticketPda] = deriveUnstakeTicketPda(vaultStatePda, q0.nextTicketId);
+      await setManualMark();
+      await pg.program.methods
+        .requestUnstake(shares)
+        .accounts({
+          owner: authority.publicKey,
+          vaultState: vaultStatePda,
+          userPosition: positionPda,
+          validatorRegistry: null,
+          withdrawQueue: withdrawQueuePda,
+          ticket: ticketPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const v = await fetchVault(vaultStatePda);
+      const q = await pg.program.account.withdrawQueue.fetch(withdrawQueuePda);
+      const t: any = await pg.program.account.unstakeTicket.fetch(ticketPda);
+      assert(t.id.eq(q0.nextTicketId), "ticket id should come from the queue counter");
+      assert(q.nextTicketId.eq(q0.nextTicketId.add(new BN(1))), "queue counter should advance");
+      assert(t.owner.equals(authority.publicKey), "ticket owner mismatch");
+      assert(t.claimableEpoch.eq(v.epoch.add(new BN(2))), "claimable epoch should be epoch + cooldown");
+      assert(v.deactivatingSol.gte(t.amountSol), "deactivating_sol should hold the ticket");
+
+      await expectFail(
+        pg.program.methods
+          .matureUnstakeTicket()
+          .accounts({ vaultState: vaultStatePda, withdrawQueue: withdrawQueuePda, ticket: ticketPda })
+          .rpc(),
+        ERR.TicketNotDue
+      );
+
+      await pg.program.methods
+        .fulfillUnstakeQueue()
+        .accounts({ vaultState: vaultStatePda, withdrawQueue: withdrawQueuePda })
+        .rpc();
+
+      await expectFail(
+        pg.program.methods
+          .claimUnstake()
+          .accounts({
+            owner: authority.publicKey,
+            vaultState: vaultStatePda,
+            withdrawQueue: withdrawQueuePda,
+            ticket: ticketPda,
+          })
+          .rpc(),
+        ERR.NothingToClaim
+      );
+    });
+  });
+
+  describe("Validator Registry", () => {
+    it("Initializes the registry and adds a validator within weight limits", async () => {
+      // fresh vault: once a registry exists every stake-moving flow must pass it
+      const owner = Keypair.generate();
+      const { vault } = await initFreshVaultWithMark(owner);
+      const [registry] = deriveValidatorRegistryPda(vault);
+      await pg.program.methods
+        .initializeValidatorRegistry(500)
+        .accounts({
+          authority: owner.publicKey,
+          vaultState: vault,
+          validatorRegistry: registry,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+      assert((await fetchVault(vault)).hasValidatorRegistry === 1, "vault should record its registry");
+
+      const adminAccounts = {
+        authority: owner.publicKey,
+        vaultState: vault,
+        validatorRegistry: registry,
+      };
+      const r0 = await pg.program.account.validatorRegistry.fetch(registry);
+
+      const vote = Keypair.generate().publicKey;
+      await expectFail(
+        pg.program.methods.addValidator(vote, 10_001, new BN(1_000_000)).accounts(adminAccounts).signers([owner]).rpc(),
+        ERR.InvalidParams
+      );
+      await pg.program.methods.addValidator(vote, 10_000, new BN(1_000_000)).accounts(adminAccounts).signers([owner]).rpc();
+
+      const r1 = await pg.program.account.validatorRegistry.fetch(registry);
+      assert(r1.validatorCount === r0.validatorCount + 1, "validator count should increase");
+      assert(r1.validators[r1.validatorCount - 1].voteAccount.equals(vote), "vote account mismatch");
+
+      const [position] = derivePositionPda(vault, owner.publicKey);
+      await pg.program.methods
+        .openUserPosition()
+        .accounts({ owner: owner.publicKey, vaultState: vault, userPosition: position, systemProgram: SystemProgram.programId })
+        .signers([owner])
+        .rpc();
+      await expectFail(
+        pg.program.methods
+          .depositAndStake(new BN(8_000))
+          .accounts({ owner: owner.publicKey, vaultState: vault, userPosition: position, validatorRegistry: null })
+          .signers([owner])
+          .rpc(),
+        ERR.ValidatorRegistryRequired
+      );
+      await pg.program.methods
+        .depositAndStake(new BN(8_000))
+        .accounts({ owner: owner.publicKey, vaultState: vault, userPosition: position, validatorRegistry: registry })
+        .signers([owner])
+        .rpc();
+      const r2 = await pg.program.account.validatorRegistry.fetch(registry);
+      assert(r2.validators[0].delegatedSol.eq(new BN(8_000)), "deposit should be allocated to the validator");
+
+      await pg.program.methods
+        .rebalanceDelegations()
+        .accounts({ signer: owner.publicKey, vaultState: vault, validatorRegistry: registry })
+        .signers([owner])
+        .rpc();
+    });
+  });
+
+  describe("Asset Book", () => {
+    it("Initializes the asset book and registers an LST feed", async () => {
+      const existing = await pg.connection.getAccountInfo(assetBookPda, "confirmed");
+      if (!existing) {
+        await expectFail(
+          pg.program.methods
+            .initializeAssetBook(2)
+            .accounts({
+              authority: authority.publicKey,
+              vaultState: vaultStatePda,
+              assetBook: assetBookPda,
+              systemProgram: SystemProgram.programId,
+            })
+            .rpc(),
+          ERR.InvalidParams
+        );
+        await pg.program.methods
+          .initializeAssetBook(0)
+          .accounts({
+            authority: authority.publicKey,
+            vaultState: vaultStatePda,
+            assetBook: assetBookPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      }
+
+      assert((await fetchVault(vaultStatePda)).hasAssetBook === 1, "vault should record its asset book");
+
+      const b0 = await pg.program.account.assetBook.fetch(assetBookPda);
+      if (b0.assetCount >= 4) {
+        console.log("Asset book full; skipping add.");
+        return;
+      }
+
+      const mint = Keypair.generate().publicKey;
+      await pg.program.methods
+        .addAsset(mint, ORACLE_FEED_SOL_USD, new BN(PRICE_FP_SCALE))
+        .accounts({ authority: authority.publicKey, vaultState: vaultStatePda, assetBook: assetBookPda })
+        .rpc();
+
+      const b1 = await pg.program.account.assetBook.fetch(assetBookPda);
+      assert(b1.assetCount === b0.assetCount + 1, "asset count should increase");
+      assert(b1.assets[b1.assetCount - 1].mint.equals(mint), "asset mint mismatch");
+    });
+  });
+
   describe("Hedge Request and Confirmation", () => {
     it("Requests hedge (best-effort) and never crashes", async () => {
       await pg.program.methods
@@ -557,8 +1666,13 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
           .accounts({
             signer: keeper1.publicKey,
             vaultState: vaultStatePda,
+            returnsRing: returnsRingPda,
+            priceBars: priceBarsPda,
             pythSolUsd: ORACLE_FEED_SOL_USD,
             pythSolUsdc: ORACLE_FEED_SOL_USDC,
+            switchboardFeed: null,
+            pythUsdcUsd: null,
+            manualPrice: null,
           })
           .signers([keeper1])
           .rpc();
@@ -566,19 +1680,25 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
 
       await waitForSlots(2);
 
+      // the vault has an asset book, so a request can't skip its targets
+      await expectFail(
+        pg.program.methods.requestHedge().accounts({ vaultState: vaultStatePda, assetBook: null }).rpc(),
+        ERR.AssetBookRequired
+      );
+
       try {
-        await pg.program.methods.requestHedge().accounts({ vaultState: vaultStatePda }).rpc();
+        await pg.program.methods.requestHedge().accounts({ vaultState: vaultStatePda, assetBook: assetBookPda }).rpc();
       } catch (e: any) {
         console.log(`requestHedge not triggered: ${String(e?.message ?? e)}`);
       }
 
       const v = await fetchVault(vaultStatePda);
-      assert(typeof v.requestOutstanding === "boolean", "requestOutstanding should be boolean");
+      assert(v.requestOutstanding === 0 || v.requestOutstanding === 1, "requestOutstanding should be a 0/1 flag");
     });
 
     it("Confirms hedge if outstanding, otherwise skips", async () => {
       const v0 = await fetchVault(vaultStatePda);
-      if (!v0.requestOutstanding) {
+      if (v0.requestOutstanding === 0) {
         console.log("No outstanding request; skipping confirm.");
         return;
       }
@@ -588,17 +1708,17 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
 
       await pg.program.methods
         .confirmHedge(requestId, new BN(0), new BN(100 * PRICE_FP_SCALE))
-        .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda })
+        .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda, assetBook: assetBookPda })
         .signers([keeper1])
         .rpc();
 
       const v1 = await fetchVault(vaultStatePda);
-      assert(v1.requestOutstanding === false, "requestOutstanding should clear");
+      assert(v1.requestOutstanding === 0, "requestOutstanding should clear");
     });
 
     it("Wrong request id fails if outstanding, otherwise skips", async () => {
       const v0 = await fetchVault(vaultStatePda);
-      if (!v0.requestOutstanding) {
+      if (v0.requestOutstanding === 0) {
         console.log("No outstanding request; skipping wrong id test.");
         return;
       }
@@ -611,7 +1731,7 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
       await expectFail(
         pg.program.methods
           .confirmHedge(wrongId, new BN(0), new BN(100 * PRICE_FP_SCALE))
-          .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda })
+          .accounts({ signer: keeper1.publicKey, vaultState: vaultStatePda, assetBook: assetBookPda })
           .signers([keeper1])
           .rpc(),
         ERR.WrongRequestId
@@ -633,6 +1753,255 @@ describe("vol_weighted_staking (Pyth devnet integration) — Solana Playground",
         .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
         .rpc();
     });
+
+    it("Emergency withdraw requires the flag and bypasses pause", async () => {
+      const p0 = await pg.program.account.userPosition.fetch(positionPda);
+      if (p0.shares.isZero()) {
+        console.log("Position has no shares; skipping emergency withdraw.");
+        return;
+      }
+
+      const withdrawAccounts = {
+        owner: authority.publicKey,
+        vaultState: vaultStatePda,
+        userPosition: positionPda,
+        validatorRegistry: null,
+      };
+      const shares = p0.shares.div(new BN(10)).add(new BN(1));
+
+      await expectFail(
+        pg.program.methods.emergencyWithdraw(shares).accounts(withdrawAccounts).rpc(),
+        ERR.EmergencyWithdrawDisabled
+      );
+
+      await pg.program.methods
+        .setEmergencyWithdrawEnabled(true)
+        .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
+        .rpc();
+      await pg.program.methods
+        .setPaused(true)
+        .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
+        .rpc();
+
+      try {
+        const v0 = await fetchVault(vaultStatePda);
+        await pg.program.methods.emergencyWithdraw(shares).accounts(withdrawAccounts).rpc();
+
+        const v1 = await fetchVault(vaultStatePda);
+        const p1 = await pg.program.account.userPosition.fetch(positionPda);
+        assert(p1.shares.eq(p0.shares.sub(shares)), "shares should be burned");
+        assert(v1.stakedSol.lt(v0.stakedSol), "staked should decrease");
+      } finally {
+        await pg.program.methods
+          .setPaused(false)
+          .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
+          .rpc();
+        await pg.program.methods
+          .setEmergencyWithdrawEnabled(false)
+          .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
+          .rpc();
+      }
+    });
+
+    it("Slashing draws down the reserve before staked_sol", async () => {
+      const keeperAccounts = { signer: authority.publicKey, vaultState: vaultStatePda, validatorRegistry: null };
+      await expectFail(
+        pg.program.methods.applySlashingEvent(0, Keypair.generate().publicKey).accounts(keeperAccounts).rpc(),
+        ERR.InvalidParams
+      );
+
+      const v0 = await fetchVault(vaultStatePda);
+      const loss = v0.stakedSol.muln(100).divn(10_000);
+      await pg.program.methods.applySlashingEvent(100, Keypair.generate().publicKey).accounts(keeperAccounts).rpc();
+
+      const v1 = await fetchVault(vaultStatePda);
+      // SOL owed to matured tickets is not part of the buffer
+      const freeReserve = v0.reserveSol.sub(v0.withdrawOwedSol);
+      if (loss.lte(freeReserve)) {
+        assert(v1.stakedSol.eq(v0.stakedSol), "reserve should absorb the loss");
+        assert(v1.reserveSol.eq(v0.reserveSol.sub(loss)), "reserve should drop by the loss");
+      } else {
+        assert(v1.reserveSol.eq(v0.withdrawOwedSol), "owed SOL should survive the slash");
+        assert(v1.paused === 1, "exhausted buffer should auto-pause");
+        await pg.program.methods
+          .setPaused(false)
+          .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
+          .rpc();
+      }
+      assert(v1.totalSlashedSol.eq(v0.totalSlashedSol.add(loss)), "total slashed should track the loss");
+    });
+
+    it("Sets fees within caps (config hash changes)", async () => {
+      const feeAccounts = { authority: authority.publicKey, vaultState: vaultStatePda };
+      await expectFail(
+        pg.program.methods.setFees(501, 1000, authority.publicKey).accounts(feeAccounts).rpc(),
+        ERR.InvalidParams
+      );
+
+      const v0 = await fetchVault(vaultStatePda);
+      await pg.program.methods.setFees(100, 1000, authority.publicKey).accounts(feeAccounts).rpc();
+
+      const v1 = await fetchVault(vaultStatePda);
+      assert(v1.managementFeeBps === 100 && v1.performanceFeeBps === 1000, "fee rates mismatch");
+      assert(v1.feeRecipient.equals(authority.publicKey), "fee recipient mismatch");
+      assert(!Buffer.from(v1.configHash).equals(Buffer.from(v0.configHash)), "config hash should change");
+
+      await pg.program.methods.setFees(0, 0, authority.publicKey).accounts(feeAccounts).rpc();
+    });
+
+    it("Toggles staking yield compounding (config version bumps)", async () => {
+      const v0 = await fetchVault(vaultStatePda);
+      await pg.program.methods
+        .setStakingCompound(v0.compoundStakingYield === 0)
+        .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
+        .rpc();
+
+      const v1 = await fetchVault(vaultStatePda);
+      assert(v1.compoundStakingYield === 1 - v0.compoundStakingYield, "compound flag should flip");
+      assert(v1.configVersion.gt(v0.configVersion), "config version should bump");
+      assert(v1.lastAccrualTs.gte(v0.lastAccrualTs), "accrual clock should not go backwards");
+
+      await pg.program.methods
+        .setStakingCompound(v0.compoundStakingYield === 1)
+        .accounts({ authority: authority.publicKey, vaultState: vaultStatePda })
+        .rpc();
+    });
+  });
+
+  describe("Token Custody", () => {
+    it("Round-trips custody tokens through deposit, unstake claim and reserve withdraw", async () => {
+      // fresh vault: custody can only be set up before any balance exists
+      const owner = Keypair.generate();
+      const { vault, authAccounts } = await initFreshVaultWithMark(owner, {
+        minReserveBps: 0,
+        unstakeCooldownEpochs: new BN(0),
+      });
+      const pdas = deriveCustodyPdas(vault);
+
+      const mint = await createMint(pg.connection, owner, owner.publicKey, null, 9);
+      try {
+        await pg.program.methods
+          .initializeCustody()
+          .accounts({
+            ...authAccounts,
+            custodyMint: mint,
+            ...pdas,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+            rent: web3.SYSVAR_RENT_PUBKEY,
+          })
+          .signers([owner])
+          .rpc();
+      } catch (e: any) {
+        if (String(e?.message ?? e).includes(ERR.CustodyDisabled)) {
+          console.log("Program built without token-custody; skipping custody round-trip.");
+          return;
+        }
+        throw e;
+      }
+
+      const minted = 1_000_000;
+      const userToken = (await getOrCreateAssociatedTokenAccount(pg.connection, owner, mint, owner.publicKey)).address;
+      const userShares = (await getOrCreateAssociatedTokenAccount(pg.connection, owner, pdas.shareMint, owner.publicKey))
+        .address;
+      await mintTo(pg.connection, owner, mint, userToken, owner, minted);
+      const balance = async (account: any) => Number((await getAccount(pg.connection, account, "confirmed")).amount);
+
+      // reserve leg in
+      await pg.program.methods
+        .depositReserve(new BN(100_000))
+        .accounts({
+          depositor: owner.publicKey,
+          vaultState: vault,
+          depositorTokenAccount: userToken,
+          custodyTokenAccount: pdas.custodyTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([owner])
+        .rpc();
+      assert((await balance(pdas.custodyTokenAccount)) === 100_000, "reserve deposit should land in custody");
+
+      // stake leg in: tokens into custody, share tokens out
+      const [position] = derivePositionPda(vault, owner.publicKey);
+      await pg.program.methods
+        .openUserPosition()
+        .accounts({ owner: owner.publicKey, vaultState: vault, userPosition: position, systemProgram: SystemProgram.programId })
+        .signers([owner])
+        .rpc();
+      const custodyAccounts = {
+        owner: owner.publicKey,
+        vaultState: vault,
+        userPosition: position,
+        validatorRegistry: null,
+        userTokenAccount: userToken,
+        shareMint: pdas.shareMint,
+        userShareAccount: userShares,
+        custodyTokenAccount: pdas.custodyTokenAccount,
+        custodyAuthority: pdas.custodyAuthority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      };
+      await pg.program.methods.depositAndStake(new BN(500_000)).accounts(custodyAccounts).signers([owner]).rpc();
+
+      const p1 = await pg.program.account.userPosition.fetch(position);
+      assert((await balance(pdas.custodyTokenAccount)) === 600_000, "stake deposit should land in custody");
+      assert((await balance(userToken)) === minted - 600_000, "depositor should be debited");
+      assert((await balance(userShares)) === p1.shares.toNumber(), "share tokens should match the position");
+
+      // stake leg out: burn shares, mature the ticket, fill it and claim tokens back
+      const [queue] = deriveWithdrawQueuePda(vault);
+      await pg.program.methods
+        .initializeWithdrawQueue()
+        .accounts({ ...authAccounts, withdrawQueue: queue, systemProgram: SystemProgram.programId })
+        .signers([owner])
+        .rpc();
+      const q0 = await pg.program.account.withdrawQueue.fetch(queue);
+      const [ticket] = deriveUnstakeTicketPda(vault, q0.nextTicketId);
+      await pg.program.methods
+        .requestUnstake(p1.shares)
+        .accounts({ ...custodyAccounts, withdrawQueue: queue, ticket, systemProgram: SystemProgram.programId })
+        .signers([owner])
+        .rpc();
+      assert((await balance(userShares)) === 0, "share tokens should be burned");
+
+      const t: any = await pg.program.account.unstakeTicket.fetch(ticket);
+      await pg.program.methods.matureUnstakeTicket().accounts({ vaultState: vault, withdrawQueue: queue, ticket }).rpc();
+      await pg.program.methods.fulfillUnstakeQueue().accounts({ vaultState: vault, withdrawQueue: queue }).rpc();
+      const beforeClaim = await balance(userToken);
+      await pg.program.methods
+        .claimUnstake()
+        .accounts({
+          owner: owner.publicKey,
+          vaultState: vault,
+          withdrawQueue: queue,
+          ticket,
+          ownerTokenAccount: userToken,
+          custodyTokenAccount: pdas.custodyTokenAccount,
+          custodyAuthority: pdas.custodyAuthority,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([owner])
+        .rpc();
+      assert((await balance(userToken)) === beforeClaim + t.amountSol.toNumber(), "claim should pay the ticket in tokens");
+      assert(!(await pg.connection.getAccountInfo(ticket, "confirmed")), "fully claimed ticket should be closed");
+
+      // reserve leg out
+      const v0 = await fetchVault(vault);
+      await pg.program.methods
+        .withdrawReserve(v0.reserveSol.sub(v0.withdrawOwedSol))
+        .accounts({
+          ...authAccounts,
+          destinationTokenAccount: userToken,
+          custodyTokenAccount: pdas.custodyTokenAccount,
+          custodyAuthority: pdas.custodyAuthority,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([owner])
+        .rpc();
+
+      const v1 = await fetchVault(vault);
+      assert(v1.reserveSol.eq(v1.withdrawOwedSol), "only owed SOL should stay in the reserve");
+      assert((await balance(userToken)) + (await balance(pdas.custodyTokenAccount)) === minted, "tokens are moved, never created");
+    });
   });
 });
 